@@ -112,7 +112,7 @@ export class LegasiClient {
     const [positionPDA] = getPositionPDA(this.provider.wallet.publicKey);
     
     const tx = await this.lendingProgram.methods
-      .initializePosition()
+      .initializePosition(0)
       .accounts({
         position: positionPDA,
         owner: this.provider.wallet.publicKey,
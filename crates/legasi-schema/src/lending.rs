@@ -0,0 +1,480 @@
+//! Accounts owned by the lending program
+
+use anchor_lang::prelude::*;
+use bytemuck::Pod;
+use legasi_core::constants::{BORROW_PERIOD_SECONDS, MAX_BORROW_TYPES, MAX_COLLATERAL_TYPES};
+use legasi_core::errors::LegasiError;
+use legasi_core::state::MarginMode;
+
+use crate::lending_program;
+
+/// User lending position (multi-collateral, multi-borrow)
+///
+/// An owner can hold several positions (sub-accounts) told apart by
+/// `position_id`, each with its own collateral, debt and risk. Position 0 is
+/// the primary one and keeps the original `[b"position", owner]` address.
+/// An isolated position (`MarginMode::Isolated`) holds a single
+/// collateral/borrow pair, so GAD on it never sells another pair's collateral.
+///
+/// Zero-copy: handlers borrow the account data in place through
+/// `AccountLoader` instead of decoding and re-encoding it. Deposits and
+/// borrows live in fixed slot arrays; only the first `collateral_count` /
+/// `borrow_count` slots are in use, the rest stay zeroed. Accounts in an
+/// older layout have a different length and must go through
+/// `migrate_position` before they load.
+///
+/// New fields are carved out of `reserved`.
+#[account(zero_copy, "account")]
+#[derive(InitSpace)]
+pub struct Position {
+    pub owner: Pubkey,
+    pub(crate) collaterals: [CollateralDeposit; MAX_COLLATERAL_TYPES],
+    pub(crate) borrows: [BorrowedAmount; MAX_BORROW_TYPES],
+    pub last_update: i64,
+    pub last_gad_crank: i64,
+    pub total_gad_liquidated_usd: u64,
+    pub reputation: Reputation,
+    /// Sequence number of the last event emitted for this position
+    pub event_seq: u64,
+    pub(crate) collateral_count: u8,
+    pub(crate) borrow_count: u8,
+    /// Boolean as a byte, see `is_gad_enabled`
+    pub(crate) gad_enabled: u8,
+    pub bump: u8,
+    /// Layout version (`Position::VERSION`)
+    pub version: u8,
+    /// Sub-account index among the owner's positions
+    pub position_id: u8,
+    /// `MarginMode` as a byte, see `margin_mode`
+    pub(crate) margin_mode: u8,
+    /// Open `Delegate` accounts on this position
+    pub(crate) delegate_count: u8,
+    /// Zeroed space for future fields, sized so the layout has no padding
+    pub reserved: [u8; 64],
+}
+
+owned_by!(Position, lending_program::ID);
+
+impl Position {
+    /// Current layout version
+    pub const VERSION: u8 = 2;
+
+    /// PDA seed for sub-account `position_id`, after `[b"position", owner]`.
+    /// Empty for the primary position, which predates sub-accounts
+    pub fn id_seed(position_id: &u8) -> &[u8] {
+        match position_id {
+            0 => &[],
+            _ => std::slice::from_ref(position_id),
+        }
+    }
+
+    /// Address and bump of `owner`'s position `position_id`
+    pub fn address(owner: &Pubkey, position_id: u8) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"position", owner.as_ref(), Self::id_seed(&position_id)],
+            &lending_program::ID,
+        )
+    }
+
+    /// Advance and return the event sequence number
+    pub fn next_event_seq(&mut self) -> u64 {
+        self.event_seq = self.event_seq.saturating_add(1);
+        self.event_seq
+    }
+
+    /// Collateral slots in use
+    pub fn collaterals(&self) -> &[CollateralDeposit] {
+        &self.collaterals[..self.collateral_count as usize]
+    }
+
+    pub fn collaterals_mut(&mut self) -> &mut [CollateralDeposit] {
+        &mut self.collaterals[..self.collateral_count as usize]
+    }
+
+    /// Borrow slots in use
+    pub fn borrows(&self) -> &[BorrowedAmount] {
+        &self.borrows[..self.borrow_count as usize]
+    }
+
+    pub fn borrows_mut(&mut self) -> &mut [BorrowedAmount] {
+        &mut self.borrows[..self.borrow_count as usize]
+    }
+
+    /// No collateral, debt or delegates left, so the account can be closed
+    pub fn is_empty(&self) -> bool {
+        self.collateral_count == 0 && self.borrow_count == 0 && self.delegate_count == 0
+    }
+
+    pub fn delegate_count(&self) -> u8 {
+        self.delegate_count
+    }
+
+    /// Count a newly granted delegate
+    pub fn record_delegate_added(&mut self) -> std::result::Result<(), LegasiError> {
+        self.delegate_count = self
+            .delegate_count
+            .checked_add(1)
+            .ok_or(LegasiError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_delegate_revoked(&mut self) {
+        self.delegate_count = self.delegate_count.saturating_sub(1);
+    }
+
+    pub fn is_gad_enabled(&self) -> bool {
+        self.gad_enabled != 0
+    }
+
+    pub fn set_gad_enabled(&mut self, enabled: bool) {
+        self.gad_enabled = enabled as u8;
+    }
+
+    pub fn margin_mode(&self) -> MarginMode {
+        match self.margin_mode {
+            1 => MarginMode::Isolated,
+            _ => MarginMode::Cross,
+        }
+    }
+
+    /// Switch margin mode. Isolating needs at most one collateral and one
+    /// borrow asset
+    pub fn set_margin_mode(&mut self, mode: MarginMode) -> std::result::Result<(), LegasiError> {
+        if mode == MarginMode::Isolated && (self.collateral_count > 1 || self.borrow_count > 1) {
+            return Err(LegasiError::IsolatedMarginPair);
+        }
+        self.margin_mode = mode as u8;
+        Ok(())
+    }
+
+    /// Deposited amount of `mint`
+    pub fn collateral_amount(&self, mint: &Pubkey) -> u64 {
+        self.collaterals()
+            .iter()
+            .find(|c| c.mint == *mint)
+            .map_or(0, |c| c.amount)
+    }
+
+    /// Debt in `mint`, principal + accrued interest
+    pub fn debt_amount(&self, mint: &Pubkey) -> u64 {
+        self.borrows()
+            .iter()
+            .find(|b| b.mint == *mint)
+            .map_or(0, |b| b.amount.saturating_add(b.accrued_interest))
+    }
+
+    /// Add `amount` to the `mint` deposit, taking a free slot if there is none
+    pub fn add_collateral(
+        &mut self,
+        mint: Pubkey,
+        amount: u64,
+    ) -> std::result::Result<(), LegasiError> {
+        if let Some(deposit) = self.collaterals_mut().iter_mut().find(|c| c.mint == mint) {
+            deposit.amount = deposit
+                .amount
+                .checked_add(amount)
+                .ok_or(LegasiError::MathOverflow)?;
+            return Ok(());
+        }
+        if self.margin_mode() == MarginMode::Isolated && self.collateral_count > 0 {
+            return Err(LegasiError::IsolatedMarginPair);
+        }
+        let slot = self
+            .collaterals
+            .get_mut(self.collateral_count as usize)
+            .ok_or(LegasiError::MaxCollateralTypesReached)?;
+        *slot = CollateralDeposit { mint, amount };
+        self.collateral_count += 1;
+        Ok(())
+    }
+
+    /// Add `amount` of principal to the `mint` borrow, taking a free slot if
+    /// there is none
+    pub fn add_debt(&mut self, mint: Pubkey, amount: u64) -> std::result::Result<(), LegasiError> {
+        if let Some(borrow) = self.borrows_mut().iter_mut().find(|b| b.mint == mint) {
+            borrow.amount = borrow
+                .amount
+                .checked_add(amount)
+                .ok_or(LegasiError::MathOverflow)?;
+            return Ok(());
+        }
+        if self.margin_mode() == MarginMode::Isolated && self.borrow_count > 0 {
+            return Err(LegasiError::IsolatedMarginPair);
+        }
+        let slot = self
+            .borrows
+            .get_mut(self.borrow_count as usize)
+            .ok_or(LegasiError::MaxBorrowTypesReached)?;
+        *slot = BorrowedAmount {
+            mint,
+            amount,
+            accrued_interest: 0,
+        };
+        self.borrow_count += 1;
+        Ok(())
+    }
+
+    /// Keep only the deposits matching `keep`, freeing the other slots
+    pub fn retain_collaterals(&mut self, keep: impl FnMut(&CollateralDeposit) -> bool) {
+        self.collateral_count = retain(&mut self.collaterals, self.collateral_count, keep);
+    }
+
+    /// Keep only the borrows matching `keep`, freeing the other slots
+    pub fn retain_borrows(&mut self, keep: impl FnMut(&BorrowedAmount) -> bool) {
+        self.borrow_count = retain(&mut self.borrows, self.borrow_count, keep);
+    }
+}
+
+/// Compacts the first `count` slots to those matching `keep` and zeroes the
+/// rest, returning the new count
+fn retain<T: Pod>(slots: &mut [T], count: u8, mut keep: impl FnMut(&T) -> bool) -> u8 {
+    let mut kept = 0;
+    for i in 0..count as usize {
+        if keep(&slots[i]) {
+            slots[kept] = slots[i];
+            kept += 1;
+        }
+    }
+    slots[kept..count as usize].fill(T::zeroed());
+    kept as u8
+}
+
+/// Single collateral deposit entry
+#[zero_copy]
+#[derive(InitSpace)]
+pub struct CollateralDeposit {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Single borrow entry
+#[zero_copy]
+#[derive(InitSpace)]
+pub struct BorrowedAmount {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub accrued_interest: u64,
+}
+
+/// On-chain reputation score
+#[zero_copy]
+#[derive(InitSpace, Default)]
+pub struct Reputation {
+    pub total_repaid_usd: u64,
+    pub successful_repayments: u32,
+    pub gad_events: u32,
+    pub account_age_days: u32,
+    pub reserved: u32,
+}
+
+impl Reputation {
+    pub fn get_score(&self) -> u32 {
+        let base = std::cmp::min(self.successful_repayments * 50, 500);
+        let age_bonus = std::cmp::min(self.account_age_days / 30 * 10, 100);
+        base.saturating_add(age_bonus)
+            .saturating_sub(self.gad_events * 100)
+    }
+
+    /// Returns LTV bonus in basis points based on reputation
+    pub fn get_ltv_bonus_bps(&self) -> u16 {
+        match self.get_score() {
+            s if s >= 400 => 500, // +5% LTV
+            s if s >= 200 => 300, // +3% LTV
+            s if s >= 100 => 100, // +1% LTV
+            _ => 0,
+        }
+    }
+}
+
+/// Agent configuration for autonomous operations
+///
+/// Versioned like `Position`: new fields go at the end, out of `reserved`.
+#[account("account")]
+#[derive(InitSpace)]
+pub struct AgentConfig {
+    /// The position this config extends
+    pub position: Pubkey,
+    /// Human operator (can intervene if needed)
+    pub operator: Pubkey,
+    /// Maximum USDC that can be borrowed per day
+    pub daily_borrow_limit: u64,
+    /// Amount borrowed in current period
+    pub daily_borrowed: u64,
+    /// Period reset timestamp
+    pub period_start: i64,
+    /// Auto-repay incoming USDC to reduce debt
+    pub auto_repay_enabled: bool,
+    /// x402 payment endpoint enabled
+    pub x402_enabled: bool,
+    /// Webhook URL for low balance alerts (stored off-chain, this is just a flag)
+    pub alerts_enabled: bool,
+    /// Minimum collateral ratio before alert (in bps)
+    pub alert_threshold_bps: u16,
+    pub bump: u8,
+    /// Layout version (`AgentConfig::VERSION` once migrated)
+    pub version: u8,
+    /// Zeroed space for future fields
+    pub reserved: [u8; 32],
+}
+
+owned_by!(AgentConfig, lending_program::ID);
+
+impl AgentConfig {
+    /// Current layout version
+    pub const VERSION: u8 = 1;
+
+    /// Check if agent can borrow more today
+    pub fn can_borrow(&self, amount: u64, current_time: i64) -> bool {
+        within_daily_limit(
+            self.daily_borrow_limit,
+            self.daily_borrowed,
+            self.period_start,
+            amount,
+            current_time,
+        )
+    }
+
+    /// `can_borrow`, as `DailyLimitExceeded` when it can't
+    pub fn require_within_limit(
+        &self,
+        amount: u64,
+        current_time: i64,
+    ) -> std::result::Result<(), LegasiError> {
+        if !self.can_borrow(amount, current_time) {
+            return Err(LegasiError::DailyLimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Record a borrow against daily limit
+    pub fn record_borrow(&mut self, amount: u64, current_time: i64) {
+        record_daily_borrow(
+            &mut self.daily_borrowed,
+            &mut self.period_start,
+            amount,
+            current_time,
+        );
+    }
+}
+
+/// Scoped authority over a position granted to another key (a session key)
+///
+/// `authority` signs agent instructions in the owner's place, limited to
+/// `permissions` (`DELEGATE_*`), its own daily borrow limit on top of the
+/// agent config's, and `expires_at`. Collateral and settings stay with the
+/// owner, who revokes the delegate by closing the account. The position
+/// counts its delegates and can't be closed until all are revoked, so a
+/// delegate never outlives its position into a re-opened sub-account.
+#[account("account")]
+#[derive(InitSpace)]
+pub struct Delegate {
+    /// The position this delegate acts on
+    pub position: Pubkey,
+    /// The position's owner, who granted and can revoke the delegate
+    pub owner: Pubkey,
+    /// The delegated key
+    pub authority: Pubkey,
+    /// `DELEGATE_*` bits
+    pub permissions: u8,
+    /// Maximum borrowed per day through this delegate
+    pub daily_borrow_limit: u64,
+    /// Amount borrowed in current period
+    pub daily_borrowed: u64,
+    /// Period reset timestamp
+    pub period_start: i64,
+    /// Unix timestamp the delegate stops working at
+    pub expires_at: i64,
+    pub bump: u8,
+    /// Layout version (`Delegate::VERSION`)
+    pub version: u8,
+    /// Zeroed space for future fields
+    pub reserved: [u8; 32],
+}
+
+owned_by!(Delegate, lending_program::ID);
+
+impl Delegate {
+    /// Current layout version
+    pub const VERSION: u8 = 1;
+
+    pub fn is_expired(&self, current_time: i64) -> bool {
+        current_time >= self.expires_at
+    }
+
+    /// Whether every bit of `permission` was granted
+    pub fn has_permission(&self, permission: u8) -> bool {
+        self.permissions & permission == permission
+    }
+
+    /// Check the delegate may act with `permission` at `current_time`
+    pub fn authorize(
+        &self,
+        permission: u8,
+        current_time: i64,
+    ) -> std::result::Result<(), LegasiError> {
+        if self.is_expired(current_time) {
+            return Err(LegasiError::DelegateExpired);
+        }
+        if !self.has_permission(permission) {
+            return Err(LegasiError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    /// Check if the delegate can borrow more today
+    pub fn can_borrow(&self, amount: u64, current_time: i64) -> bool {
+        within_daily_limit(
+            self.daily_borrow_limit,
+            self.daily_borrowed,
+            self.period_start,
+            amount,
+            current_time,
+        )
+    }
+
+    /// `can_borrow`, as `DailyLimitExceeded` when it can't
+    pub fn require_within_limit(
+        &self,
+        amount: u64,
+        current_time: i64,
+    ) -> std::result::Result<(), LegasiError> {
+        if !self.can_borrow(amount, current_time) {
+            return Err(LegasiError::DailyLimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Record a borrow against daily limit
+    pub fn record_borrow(&mut self, amount: u64, current_time: i64) {
+        record_daily_borrow(
+            &mut self.daily_borrowed,
+            &mut self.period_start,
+            amount,
+            current_time,
+        );
+    }
+}
+
+/// Whether `amount` fits under `limit`, starting a new period once the
+/// current one is over
+fn within_daily_limit(
+    limit: u64,
+    borrowed: u64,
+    period_start: i64,
+    amount: u64,
+    current_time: i64,
+) -> bool {
+    if current_time - period_start >= BORROW_PERIOD_SECONDS {
+        return amount <= limit;
+    }
+    borrowed.saturating_add(amount) <= limit
+}
+
+fn record_daily_borrow(borrowed: &mut u64, period_start: &mut i64, amount: u64, current_time: i64) {
+    if current_time - *period_start >= BORROW_PERIOD_SECONDS {
+        *period_start = current_time;
+        *borrowed = amount;
+    } else {
+        *borrowed = borrowed.saturating_add(amount);
+    }
+}
@@ -0,0 +1,385 @@
+//! Account schemas shared across the Legasi programs
+//!
+//! Programs read each other's accounts through these types only, so every
+//! account has a single layout. Anchor checks `Account<T>` against
+//! `T::owner()`: core accounts are re-exported from `legasi_core`, and the
+//! accounts owned by the lending and LP programs are defined here with their
+//! owning program as owner.
+//!
+//! Those are declared `#[account("account")]`, or
+//! `#[account(zero_copy, "account")]` for zero-copy ones. The explicit
+//! namespace keeps Anchor's default `account:<Name>` discriminator but skips
+//! its `Owner` impl (which would point at the declaring crate); `owned_by!`
+//! sets the owner.
+
+macro_rules! owned_by {
+    ($account:ty, $program:path) => {
+        impl anchor_lang::Owner for $account {
+            fn owner() -> Pubkey {
+                $program
+            }
+        }
+    };
+}
+
+pub mod lending;
+pub mod lp;
+pub mod migrate;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use legasi_core::accounting::{lending_program, lp_program};
+pub use legasi_core::state::{
+    Borrowable, Collateral, PriceFeed, Protocol, PushOracle, QueuedChange,
+};
+pub use lending::*;
+pub use lp::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::*;
+    use anchor_lang::solana_program::hash::hash;
+    use anchor_lang::{Discriminator, Owner};
+    use bytemuck::Zeroable;
+    use legasi_core::constants::{
+        BORROW_PERIOD_SECONDS, DELEGATE_ALL, DELEGATE_BORROW, DELEGATE_REPAY, DELEGATE_X402,
+        MAX_BORROW_TYPES, MAX_COLLATERAL_TYPES,
+    };
+    use legasi_core::errors::LegasiError;
+    use legasi_core::state::{AssetKind, EModeCategory, MarginMode};
+    use migrate::zero_copy_data;
+
+    fn anchor_discriminator(name: &str) -> [u8; 8] {
+        hash(format!("account:{name}").as_bytes()).to_bytes()[..8]
+            .try_into()
+            .unwrap()
+    }
+
+    fn encode<T: AccountSerialize>(account: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    fn position() -> Position {
+        let mut position = Position::zeroed();
+        position.owner = Pubkey::new_unique();
+        position
+            .add_collateral(Pubkey::new_unique(), 5_000_000_000)
+            .unwrap();
+        position
+            .add_debt(Pubkey::new_unique(), 100_000_000)
+            .unwrap();
+        position.borrows_mut()[0].accrued_interest = 42;
+        position.last_update = 1_700_000_000;
+        position.event_seq = 7;
+        position.bump = 254;
+        position.version = Position::VERSION;
+        position
+    }
+
+    #[test]
+    fn test_discriminators_match_anchor_defaults() {
+        // Accounts created by the owning program decode under the shared type
+        assert_eq!(Position::DISCRIMINATOR, anchor_discriminator("Position"));
+        assert_eq!(
+            AgentConfig::DISCRIMINATOR,
+            anchor_discriminator("AgentConfig")
+        );
+        assert_eq!(Delegate::DISCRIMINATOR, anchor_discriminator("Delegate"));
+        assert_eq!(LpPool::DISCRIMINATOR, anchor_discriminator("LpPool"));
+        assert_eq!(Protocol::DISCRIMINATOR, anchor_discriminator("Protocol"));
+        assert_eq!(
+            Borrowable::DISCRIMINATOR,
+            anchor_discriminator("Borrowable")
+        );
+        assert_eq!(
+            Collateral::DISCRIMINATOR,
+            anchor_discriminator("Collateral")
+        );
+        assert_eq!(PriceFeed::DISCRIMINATOR, anchor_discriminator("PriceFeed"));
+    }
+
+    #[test]
+    fn test_owners_are_owning_programs() {
+        assert_eq!(Position::owner(), lending_program::ID);
+        assert_eq!(AgentConfig::owner(), lending_program::ID);
+        assert_eq!(Delegate::owner(), lending_program::ID);
+        assert_eq!(LpPool::owner(), lp_program::ID);
+        assert_eq!(Protocol::owner(), legasi_core::ID);
+        assert_eq!(Borrowable::owner(), legasi_core::ID);
+        assert_eq!(Collateral::owner(), legasi_core::ID);
+        assert_eq!(PriceFeed::owner(), legasi_core::ID);
+    }
+
+    #[test]
+    fn test_init_space_is_pinned() {
+        // Changing a shared layout breaks existing accounts in every program;
+        // update these only together with a migration
+        assert_eq!(Position::INIT_SPACE, 1184);
+        assert_eq!(AgentConfig::INIT_SPACE, 127);
+        assert_eq!(Delegate::INIT_SPACE, 163);
+        assert_eq!(LpPool::INIT_SPACE, 97);
+        assert_eq!(Borrowable::INIT_SPACE, 89);
+        assert_eq!(Protocol::INIT_SPACE, 280);
+        assert_eq!(Collateral::INIT_SPACE, 103);
+    }
+
+    #[test]
+    fn test_borrowable_field_offsets() {
+        let borrowable = Borrowable {
+            mint: Pubkey::new_unique(),
+            oracle: Pubkey::new_unique(),
+            interest_rate_bps: 500,
+            decimals: 6,
+            is_active: true,
+            total_borrowed: 0x1111_2222_3333_4444,
+            total_available: 0x5555_6666_7777_8888,
+            kind: AssetKind::Stablecoin,
+            emode_category: EModeCategory::Stablecoins,
+            pause_flags: 0,
+            bump: 255,
+        };
+        let data = encode(&borrowable);
+
+        // discriminator, mint, oracle, rate, decimals, is_active
+        let offset = 8 + 32 + 32 + 2 + 1 + 1;
+        let read_u64 = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        assert_eq!(read_u64(offset), borrowable.total_borrowed);
+        assert_eq!(read_u64(offset + 8), borrowable.total_available);
+        assert_eq!(data.len(), 8 + Borrowable::INIT_SPACE);
+    }
+
+    #[test]
+    fn test_position_is_padding_free() {
+        // Zero-copy accounts are read in place, so the space allocated for
+        // them must be exactly the struct
+        assert_eq!(Position::INIT_SPACE, std::mem::size_of::<Position>());
+        assert_eq!(std::mem::align_of::<Position>(), 8);
+
+        // Offsets clients filter on (the SDK's POSITION_LAYOUT adds the discriminator)
+        assert_eq!(std::mem::offset_of!(Position, owner), 0);
+        assert_eq!(std::mem::offset_of!(Position, position_id), 1117);
+        assert_eq!(std::mem::offset_of!(Position, margin_mode), 1118);
+        assert_eq!(std::mem::offset_of!(Position, delegate_count), 1119);
+    }
+
+    #[test]
+    fn test_position_round_trip() {
+        let position = position();
+        let data = zero_copy_data(&position);
+        let decoded = Position::try_deserialize(&mut data.as_slice()).unwrap();
+
+        assert_eq!(decoded.owner, position.owner);
+        let collateral_mint = position.collaterals()[0].mint;
+        let borrow_mint = position.borrows()[0].mint;
+        assert_eq!(decoded.collateral_amount(&collateral_mint), 5_000_000_000);
+        assert_eq!(decoded.debt_amount(&borrow_mint), 100_000_042);
+        assert_eq!((decoded.event_seq, decoded.bump), (7, 254));
+    }
+
+    #[test]
+    fn test_position_addresses() {
+        let owner = Pubkey::new_unique();
+
+        // The primary position keeps its pre-sub-account address
+        let legacy =
+            Pubkey::find_program_address(&[b"position", owner.as_ref()], &lending_program::ID);
+        assert_eq!(Position::address(&owner, 0), legacy);
+
+        let addresses: std::collections::BTreeSet<_> = (0..=u8::MAX)
+            .map(|id| Position::address(&owner, id).0)
+            .collect();
+        assert_eq!(addresses.len(), 256);
+        assert!(!addresses.contains(&Position::address(&Pubkey::new_unique(), 1).0));
+    }
+
+    #[test]
+    fn test_position_slots() {
+        let mut position = position();
+        let sol = Pubkey::new_unique();
+
+        // Deposits in a held mint share its slot
+        position.add_collateral(sol, 1).unwrap();
+        position.add_collateral(sol, 2).unwrap();
+        assert_eq!(position.collaterals().len(), 2);
+        assert_eq!(position.collateral_amount(&sol), 3);
+
+        while position.collaterals().len() < MAX_COLLATERAL_TYPES {
+            position.add_collateral(Pubkey::new_unique(), 1).unwrap();
+        }
+        assert!(matches!(
+            position.add_collateral(Pubkey::new_unique(), 1),
+            Err(LegasiError::MaxCollateralTypesReached)
+        ));
+        assert!(matches!(
+            position.add_collateral(sol, u64::MAX),
+            Err(LegasiError::MathOverflow)
+        ));
+
+        while position.borrows().len() < MAX_BORROW_TYPES {
+            position.add_debt(Pubkey::new_unique(), 1).unwrap();
+        }
+        assert!(matches!(
+            position.add_debt(Pubkey::new_unique(), 1),
+            Err(LegasiError::MaxBorrowTypesReached)
+        ));
+
+        // Removing entries compacts the live slots and frees the rest
+        assert!(!position.is_empty());
+        position.retain_collaterals(|c| c.mint == sol);
+        assert_eq!(position.collaterals().len(), 1);
+        assert_eq!(position.collateral_amount(&sol), 3);
+        assert!(position.collaterals[1..]
+            .iter()
+            .all(|c| c.mint == Pubkey::default() && c.amount == 0));
+
+        position.retain_borrows(|b| b.accrued_interest > 0);
+        assert_eq!(position.borrows().len(), 1);
+        assert_eq!(position.borrows()[0].amount, 100_000_000);
+        position.add_debt(sol, 5).unwrap();
+        assert_eq!(position.debt_amount(&sol), 5);
+
+        position.retain_collaterals(|_| false);
+        assert!(!position.is_empty());
+        position.retain_borrows(|_| false);
+        assert!(position.is_empty());
+
+        // Open delegates keep the position from closing
+        position.record_delegate_added().unwrap();
+        assert!(!position.is_empty());
+        position.record_delegate_revoked();
+        assert!(position.is_empty());
+    }
+
+    #[test]
+    fn test_isolated_margin_holds_one_pair() {
+        let mut position = position();
+        assert_eq!(position.margin_mode(), MarginMode::Cross);
+        position.set_margin_mode(MarginMode::Isolated).unwrap();
+
+        // More of the held assets is fine, a second asset is not
+        let collateral_mint = position.collaterals()[0].mint;
+        let borrow_mint = position.borrows()[0].mint;
+        position.add_collateral(collateral_mint, 1).unwrap();
+        position.add_debt(borrow_mint, 1).unwrap();
+        assert!(matches!(
+            position.add_collateral(Pubkey::new_unique(), 1),
+            Err(LegasiError::IsolatedMarginPair)
+        ));
+        assert!(matches!(
+            position.add_debt(Pubkey::new_unique(), 1),
+            Err(LegasiError::IsolatedMarginPair)
+        ));
+
+        // A cross position with several assets can't be isolated
+        position.set_margin_mode(MarginMode::Cross).unwrap();
+        position.add_collateral(Pubkey::new_unique(), 1).unwrap();
+        assert!(matches!(
+            position.set_margin_mode(MarginMode::Isolated),
+            Err(LegasiError::IsolatedMarginPair)
+        ));
+        assert_eq!(position.margin_mode(), MarginMode::Cross);
+
+        // The mode survives a round trip through account data
+        position.retain_collaterals(|c| c.mint == collateral_mint);
+        position.set_margin_mode(MarginMode::Isolated).unwrap();
+        let data = zero_copy_data(&position);
+        let decoded = Position::try_deserialize(&mut data.as_slice()).unwrap();
+        assert_eq!(decoded.margin_mode(), MarginMode::Isolated);
+    }
+
+    fn delegate() -> Delegate {
+        Delegate {
+            position: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            permissions: DELEGATE_X402 | DELEGATE_REPAY,
+            daily_borrow_limit: 100,
+            daily_borrowed: 0,
+            period_start: 0,
+            expires_at: 1_000,
+            bump: 255,
+            version: Delegate::VERSION,
+            reserved: [0; 32],
+        }
+    }
+
+    #[test]
+    fn test_delegate_scope() {
+        let delegate = delegate();
+        assert!(delegate.has_permission(DELEGATE_X402));
+        assert!(delegate.has_permission(DELEGATE_X402 | DELEGATE_REPAY));
+        assert!(!delegate.has_permission(DELEGATE_BORROW));
+        assert!(!delegate.has_permission(DELEGATE_ALL));
+
+        assert!(!delegate.is_expired(999));
+        assert!(delegate.is_expired(1_000));
+
+        assert!(delegate.authorize(DELEGATE_REPAY, 999).is_ok());
+        assert!(matches!(
+            delegate.authorize(DELEGATE_BORROW, 999),
+            Err(LegasiError::Unauthorized)
+        ));
+        assert!(matches!(
+            delegate.authorize(DELEGATE_REPAY, 1_000),
+            Err(LegasiError::DelegateExpired)
+        ));
+    }
+
+    #[test]
+    fn test_daily_borrow_limit_resets_each_period() {
+        let mut delegate = delegate();
+        delegate.period_start = 1_000;
+
+        delegate.record_borrow(60, 1_000);
+        assert!(delegate.can_borrow(40, 1_000 + BORROW_PERIOD_SECONDS - 1));
+        assert!(!delegate.can_borrow(41, 1_000 + BORROW_PERIOD_SECONDS - 1));
+
+        // A new period starts from the first borrow after the old one ends
+        let later = 1_000 + BORROW_PERIOD_SECONDS;
+        assert!(delegate.can_borrow(100, later));
+        delegate.record_borrow(100, later);
+        assert_eq!(
+            (delegate.daily_borrowed, delegate.period_start),
+            (100, later)
+        );
+        assert!(!delegate.can_borrow(1, later + 1));
+        assert!(matches!(
+            delegate.require_within_limit(1, later + 1),
+            Err(LegasiError::DailyLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_agent_config_limit_error() {
+        let mut config = AgentConfig {
+            position: Pubkey::new_unique(),
+            operator: Pubkey::new_unique(),
+            daily_borrow_limit: 100,
+            daily_borrowed: 0,
+            period_start: 0,
+            auto_repay_enabled: false,
+            x402_enabled: true,
+            alerts_enabled: false,
+            alert_threshold_bps: 0,
+            bump: 255,
+            version: AgentConfig::VERSION,
+            reserved: [0; 32],
+        };
+        assert!(config.require_within_limit(100, 0).is_ok());
+        config.record_borrow(100, 0);
+        assert!(matches!(
+            config.require_within_limit(1, 1),
+            Err(LegasiError::DailyLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_discriminator_rejects_other_accounts() {
+        let data = zero_copy_data(&position());
+        assert!(AgentConfig::try_deserialize(&mut data.as_slice()).is_err());
+        assert!(LpPool::try_deserialize(&mut data.as_slice()).is_err());
+    }
+}
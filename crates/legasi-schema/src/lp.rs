@@ -0,0 +1,20 @@
+//! Accounts owned by the LP program
+
+use anchor_lang::prelude::*;
+
+use crate::lp_program;
+
+/// LP pool for a borrowable asset
+#[account("account")]
+#[derive(InitSpace)]
+pub struct LpPool {
+    pub borrowable_mint: Pubkey,
+    pub lp_token_mint: Pubkey,
+    pub total_deposits: u64,
+    pub total_shares: u64,
+    pub total_borrowed: u64,
+    pub interest_earned: u64,
+    pub bump: u8,
+}
+
+owned_by!(LpPool, lp_program::ID);
@@ -0,0 +1,409 @@
+//! Upgrades from superseded account layouts
+//!
+//! An account's layout is identified by its length: each version is
+//! allocated at `8 + INIT_SPACE` of its own layout. The `upgrade_*`
+//! functions return the account in the current layout, or `None` when it is
+//! already current.
+
+use anchor_lang::prelude::*;
+use anchor_lang::{Discriminator, ZeroCopy};
+use bytemuck::Zeroable;
+use legasi_core::errors::LegasiError;
+
+use crate::{AgentConfig, BorrowedAmount, CollateralDeposit, Position, Reputation};
+
+/// Borsh `CollateralDeposit` of the Vec-based `Position` layouts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct LegacyCollateralDeposit {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Borsh `BorrowedAmount` of the Vec-based `Position` layouts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct LegacyBorrowedAmount {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub accrued_interest: u64,
+}
+
+/// Borsh `Reputation` of the Vec-based `Position` layouts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct LegacyReputation {
+    pub successful_repayments: u32,
+    pub total_repaid_usd: u64,
+    pub gad_events: u32,
+    pub account_age_days: u32,
+}
+
+impl From<LegacyReputation> for Reputation {
+    fn from(old: LegacyReputation) -> Self {
+        Self {
+            total_repaid_usd: old.total_repaid_usd,
+            successful_repayments: old.successful_repayments,
+            gad_events: old.gad_events,
+            account_age_days: old.account_age_days,
+            reserved: 0,
+        }
+    }
+}
+
+/// `Position` before `version` and `reserved` (layout v0)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct PositionV0 {
+    pub owner: Pubkey,
+    #[max_len(8)]
+    pub collaterals: Vec<LegacyCollateralDeposit>,
+    #[max_len(4)]
+    pub borrows: Vec<LegacyBorrowedAmount>,
+    pub last_update: i64,
+    pub last_gad_crank: i64,
+    pub gad_enabled: bool,
+    pub total_gad_liquidated_usd: u64,
+    pub reputation: LegacyReputation,
+    pub event_seq: u64,
+    pub bump: u8,
+}
+
+/// Borsh `Position` with 8 collateral and 4 borrow slots (layout v1)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct PositionV1 {
+    pub owner: Pubkey,
+    #[max_len(8)]
+    pub collaterals: Vec<LegacyCollateralDeposit>,
+    #[max_len(4)]
+    pub borrows: Vec<LegacyBorrowedAmount>,
+    pub last_update: i64,
+    pub last_gad_crank: i64,
+    pub gad_enabled: bool,
+    pub total_gad_liquidated_usd: u64,
+    pub reputation: LegacyReputation,
+    pub event_seq: u64,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; 64],
+}
+
+impl From<PositionV0> for PositionV1 {
+    fn from(old: PositionV0) -> Self {
+        Self {
+            owner: old.owner,
+            collaterals: old.collaterals,
+            borrows: old.borrows,
+            last_update: old.last_update,
+            last_gad_crank: old.last_gad_crank,
+            gad_enabled: old.gad_enabled,
+            total_gad_liquidated_usd: old.total_gad_liquidated_usd,
+            reputation: old.reputation,
+            event_seq: old.event_seq,
+            bump: old.bump,
+            version: 1,
+            reserved: [0; 64],
+        }
+    }
+}
+
+impl From<PositionV1> for Position {
+    fn from(old: PositionV1) -> Self {
+        // Legacy slot counts are below the current capacity
+        let mut position = Position::zeroed();
+        for (slot, deposit) in position.collaterals.iter_mut().zip(&old.collaterals) {
+            *slot = CollateralDeposit {
+                mint: deposit.mint,
+                amount: deposit.amount,
+            };
+        }
+        for (slot, borrow) in position.borrows.iter_mut().zip(&old.borrows) {
+            *slot = BorrowedAmount {
+                mint: borrow.mint,
+                amount: borrow.amount,
+                accrued_interest: borrow.accrued_interest,
+            };
+        }
+        position.owner = old.owner;
+        position.collateral_count = old.collaterals.len() as u8;
+        position.borrow_count = old.borrows.len() as u8;
+        position.last_update = old.last_update;
+        position.last_gad_crank = old.last_gad_crank;
+        position.set_gad_enabled(old.gad_enabled);
+        position.total_gad_liquidated_usd = old.total_gad_liquidated_usd;
+        position.reputation = old.reputation.into();
+        position.event_seq = old.event_seq;
+        position.bump = old.bump;
+        position.version = Position::VERSION;
+        position
+    }
+}
+
+/// `AgentConfig` before `version` and `reserved` (layout v0)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct AgentConfigV0 {
+    pub position: Pubkey,
+    pub operator: Pubkey,
+    pub daily_borrow_limit: u64,
+    pub daily_borrowed: u64,
+    pub period_start: i64,
+    pub auto_repay_enabled: bool,
+    pub x402_enabled: bool,
+    pub alerts_enabled: bool,
+    pub alert_threshold_bps: u16,
+    pub bump: u8,
+}
+
+impl From<AgentConfigV0> for AgentConfig {
+    fn from(old: AgentConfigV0) -> Self {
+        Self {
+            position: old.position,
+            operator: old.operator,
+            daily_borrow_limit: old.daily_borrow_limit,
+            daily_borrowed: old.daily_borrowed,
+            period_start: old.period_start,
+            auto_repay_enabled: old.auto_repay_enabled,
+            x402_enabled: old.x402_enabled,
+            alerts_enabled: old.alerts_enabled,
+            alert_threshold_bps: old.alert_threshold_bps,
+            bump: old.bump,
+            version: AgentConfig::VERSION,
+            reserved: [0; 32],
+        }
+    }
+}
+
+/// Account data after the discriminator, if it is a `T`
+fn body<T: Discriminator>(data: &[u8]) -> std::result::Result<&[u8], LegasiError> {
+    match data.split_first_chunk::<8>() {
+        Some((discriminator, body)) if *discriminator == T::DISCRIMINATOR => Ok(body),
+        _ => Err(LegasiError::UnknownAccountLayout),
+    }
+}
+
+fn decode<T: AnchorDeserialize>(mut body: &[u8]) -> std::result::Result<T, LegasiError> {
+    T::deserialize(&mut body).map_err(|_| LegasiError::UnknownAccountLayout)
+}
+
+/// Account data of a zero-copy account: discriminator, then the raw struct
+pub fn zero_copy_data<T: ZeroCopy>(account: &T) -> Vec<u8> {
+    let mut data = T::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(account));
+    data
+}
+
+/// `Position` account data in the current layout
+pub fn upgrade_position(data: &[u8]) -> std::result::Result<Option<Box<Position>>, LegasiError> {
+    let body = body::<Position>(data)?;
+    let v1 = match body.len() {
+        len if len == PositionV0::INIT_SPACE => decode::<PositionV0>(body)?.into(),
+        len if len == PositionV1::INIT_SPACE => decode::<PositionV1>(body)?,
+        len if len == Position::INIT_SPACE => {
+            return match bytemuck::pod_read_unaligned::<Position>(body).version {
+                Position::VERSION => Ok(None),
+                _ => Err(LegasiError::UnknownAccountLayout),
+            };
+        }
+        _ => return Err(LegasiError::UnknownAccountLayout),
+    };
+    match v1.version {
+        1 => Ok(Some(Box::new(v1.into()))),
+        _ => Err(LegasiError::UnknownAccountLayout),
+    }
+}
+
+/// `AgentConfig` account data in the current layout
+pub fn upgrade_agent_config(data: &[u8]) -> std::result::Result<Option<AgentConfig>, LegasiError> {
+    let body = body::<AgentConfig>(data)?;
+    match body.len() {
+        len if len == AgentConfigV0::INIT_SPACE => Ok(Some(decode::<AgentConfigV0>(body)?.into())),
+        len if len == AgentConfig::INIT_SPACE => match decode::<AgentConfig>(body)?.version {
+            AgentConfig::VERSION => Ok(None),
+            _ => Err(LegasiError::UnknownAccountLayout),
+        },
+        _ => Err(LegasiError::UnknownAccountLayout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Account data as allocated by the program: discriminator, body, zero padding
+    fn account_data<T: Discriminator + AnchorSerialize>(body: &T, space: usize) -> Vec<u8> {
+        let mut data = T::DISCRIMINATOR.to_vec();
+        body.serialize(&mut data).unwrap();
+        data.resize(8 + space, 0);
+        data
+    }
+
+    fn position_v0() -> PositionV0 {
+        PositionV0 {
+            owner: Pubkey::new_unique(),
+            collaterals: vec![LegacyCollateralDeposit {
+                mint: Pubkey::new_unique(),
+                amount: 5_000_000_000,
+            }],
+            borrows: vec![LegacyBorrowedAmount {
+                mint: Pubkey::new_unique(),
+                amount: 100_000_000,
+                accrued_interest: 42,
+            }],
+            last_update: 1_700_000_000,
+            last_gad_crank: 1_700_000_100,
+            gad_enabled: true,
+            total_gad_liquidated_usd: 7,
+            reputation: LegacyReputation {
+                successful_repayments: 3,
+                total_repaid_usd: 300_000_000,
+                gad_events: 1,
+                account_age_days: 90,
+            },
+            event_seq: 12,
+            bump: 253,
+        }
+    }
+
+    fn agent_config_v0() -> AgentConfigV0 {
+        AgentConfigV0 {
+            position: Pubkey::new_unique(),
+            operator: Pubkey::new_unique(),
+            daily_borrow_limit: 1_000_000_000,
+            daily_borrowed: 250_000_000,
+            period_start: 1_700_000_000,
+            auto_repay_enabled: true,
+            x402_enabled: true,
+            alerts_enabled: false,
+            alert_threshold_bps: 8_000,
+            bump: 251,
+        }
+    }
+
+    // Legacy layouts share the live accounts' discriminators
+    impl Discriminator for PositionV0 {
+        const DISCRIMINATOR: [u8; 8] = Position::DISCRIMINATOR;
+    }
+
+    impl Discriminator for PositionV1 {
+        const DISCRIMINATOR: [u8; 8] = Position::DISCRIMINATOR;
+    }
+
+    impl Discriminator for AgentConfigV0 {
+        const DISCRIMINATOR: [u8; 8] = AgentConfig::DISCRIMINATOR;
+    }
+
+    #[test]
+    fn test_legacy_layouts_are_pinned() {
+        // Sizes of the accounts deployed before versioning
+        assert_eq!(PositionV0::INIT_SPACE, 606);
+        assert_eq!(PositionV1::INIT_SPACE, 671);
+        assert_eq!(AgentConfigV0::INIT_SPACE, 94);
+    }
+
+    /// Checks `position` carries over every field of `old`
+    fn assert_upgraded(position: &Position, old: &PositionV0) {
+        assert_eq!(position.owner, old.owner);
+        assert_eq!(position.collaterals().len(), 1);
+        assert_eq!(
+            position.collateral_amount(&old.collaterals[0].mint),
+            5_000_000_000
+        );
+        assert_eq!(position.borrows().len(), 1);
+        assert_eq!(position.debt_amount(&old.borrows[0].mint), 100_000_042);
+        assert_eq!(position.last_update, old.last_update);
+        assert_eq!(position.last_gad_crank, old.last_gad_crank);
+        assert!(position.is_gad_enabled());
+        assert_eq!(position.total_gad_liquidated_usd, 7);
+        assert_eq!(position.reputation.total_repaid_usd, 300_000_000);
+        assert_eq!(position.reputation.get_score(), 80);
+        assert_eq!((position.event_seq, position.bump), (12, 253));
+        assert_eq!(position.version, Position::VERSION);
+        assert_eq!(position.position_id, 0);
+        assert_eq!(
+            position.margin_mode(),
+            legasi_core::state::MarginMode::Cross
+        );
+        assert_eq!(position.delegate_count(), 0);
+        assert_eq!(position.reserved, [0; 64]);
+    }
+
+    #[test]
+    fn test_upgrade_position_from_v0() {
+        let old = position_v0();
+        let data = account_data(&old, PositionV0::INIT_SPACE);
+
+        let position = upgrade_position(&data).unwrap().unwrap();
+        assert_upgraded(&position, &old);
+
+        // The upgraded account loads as a current one
+        let migrated = zero_copy_data(&*position);
+        assert_eq!(migrated.len(), 8 + Position::INIT_SPACE);
+        let loaded = Position::try_deserialize(&mut migrated.as_slice()).unwrap();
+        assert_upgraded(&loaded, &old);
+        assert!(upgrade_position(&migrated).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_upgrade_position_from_v1() {
+        let old = position_v0();
+        let data = account_data(&PositionV1::from(old.clone()), PositionV1::INIT_SPACE);
+
+        let position = upgrade_position(&data).unwrap().unwrap();
+        assert_upgraded(&position, &old);
+        assert!(upgrade_position(&zero_copy_data(&*position))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_upgrade_agent_config_from_v0() {
+        let old = agent_config_v0();
+        let data = account_data(&old, AgentConfigV0::INIT_SPACE);
+
+        // Fixed-size v0 configs have no room for the new fields
+        assert!(AgentConfig::try_deserialize(&mut data.as_slice()).is_err());
+
+        let config = upgrade_agent_config(&data).unwrap().unwrap();
+        assert_eq!(config.position, old.position);
+        assert_eq!(config.operator, old.operator);
+        assert_eq!(config.daily_borrow_limit, 1_000_000_000);
+        assert_eq!(config.daily_borrowed, 250_000_000);
+        assert_eq!(config.period_start, old.period_start);
+        assert!(config.auto_repay_enabled && config.x402_enabled);
+        assert!(!config.alerts_enabled);
+        assert_eq!(config.alert_threshold_bps, 8_000);
+        assert_eq!(config.bump, 251);
+        assert_eq!(config.version, AgentConfig::VERSION);
+
+        let migrated = account_data(&config, AgentConfig::INIT_SPACE);
+        assert!(upgrade_agent_config(&migrated).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_upgrade_rejects_unknown_layouts() {
+        let position = *upgrade_position(&account_data(&position_v0(), PositionV0::INIT_SPACE))
+            .unwrap()
+            .unwrap();
+
+        // Another account type
+        let config = account_data(&agent_config_v0(), AgentConfigV0::INIT_SPACE);
+        assert!(matches!(
+            upgrade_position(&config),
+            Err(LegasiError::UnknownAccountLayout)
+        ));
+
+        // Unknown size
+        let mut truncated = zero_copy_data(&position);
+        truncated.pop();
+        assert!(matches!(
+            upgrade_position(&truncated),
+            Err(LegasiError::UnknownAccountLayout)
+        ));
+
+        // Newer than this program
+        let newer = Position {
+            version: Position::VERSION + 1,
+            ..position
+        };
+        assert!(matches!(
+            upgrade_position(&zero_copy_data(&newer)),
+            Err(LegasiError::UnknownAccountLayout)
+        ));
+    }
+}
@@ -0,0 +1,352 @@
+//! Fixtures for running account validation in unit tests
+//!
+//! Builds the `AccountInfo`s an instruction would receive and runs its
+//! `Accounts::try_accounts`, so tests can show that substituted accounts are
+//! rejected without a validator. `run` goes further and executes whole
+//! instructions, CPIs included. Enabled by the `testing` feature.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+
+use anchor_lang::error::ErrorOrigin;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+use anchor_lang::solana_program::program_utils::limited_deserialize;
+use anchor_lang::solana_program::system_instruction::SystemInstruction;
+use anchor_lang::solana_program::{bpf_loader_upgradeable, entrypoint::SUCCESS, system_program};
+use anchor_lang::{prelude::*, Bumps, ZeroCopy};
+use anchor_spl::token::spl_token;
+
+use crate::migrate::zero_copy_data;
+
+/// Owned backing storage for one `AccountInfo`
+#[derive(Clone)]
+pub struct TestAccount {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub executable: bool,
+}
+
+impl TestAccount {
+    /// Writable, rent-funded account
+    pub fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            key,
+            owner,
+            lamports: 1_000_000_000,
+            data,
+            is_signer: false,
+            is_writable: true,
+            executable: false,
+        }
+    }
+
+    /// Anchor account owned by `T`'s owning program
+    pub fn anchor<T: AccountSerialize + Owner>(key: Pubkey, account: &T) -> Self {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        Self::new(key, T::owner(), data)
+    }
+
+    /// Zero-copy account owned by `T`'s owning program
+    pub fn zero_copy<T: ZeroCopy + Owner>(key: Pubkey, account: &T) -> Self {
+        Self::new(key, T::owner(), zero_copy_data(account))
+    }
+
+    /// System-owned transaction signer
+    pub fn signer(key: Pubkey) -> Self {
+        Self {
+            is_signer: true,
+            ..Self::new(key, system_program::ID, Vec::new())
+        }
+    }
+
+    /// Executable program account
+    pub fn program(id: Pubkey) -> Self {
+        Self {
+            is_writable: false,
+            executable: true,
+            ..Self::new(id, bpf_loader_upgradeable::ID, Vec::new())
+        }
+    }
+
+    /// Initialized SPL token account
+    pub fn token_account(key: Pubkey, mint: Pubkey, authority: Pubkey, amount: u64) -> Self {
+        let account = spl_token::state::Account {
+            mint,
+            owner: authority,
+            amount,
+            delegate: COption::None,
+            state: spl_token::state::AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        };
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        account.pack_into_slice(&mut data);
+        Self::new(key, spl_token::ID, data)
+    }
+
+    /// Initialized SPL mint
+    pub fn mint(key: Pubkey, decimals: u8) -> Self {
+        let mint = spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        mint.pack_into_slice(&mut data);
+        Self::new(key, spl_token::ID, data)
+    }
+
+    /// Stored anchor account
+    pub fn read<T: AccountDeserialize>(&self) -> T {
+        T::try_deserialize(&mut &self.data[..]).unwrap()
+    }
+
+    /// Stored zero-copy account
+    pub fn read_zero_copy<T: ZeroCopy>(&self) -> T {
+        bytemuck::pod_read_unaligned(&self.data[8..8 + std::mem::size_of::<T>()])
+    }
+
+    /// Balance of a token account
+    pub fn token_amount(&self) -> u64 {
+        spl_token::state::Account::unpack(&self.data)
+            .unwrap()
+            .amount
+    }
+
+    pub fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            self.executable,
+            0,
+        )
+    }
+}
+
+/// Accounts with every field zeroed (empty vecs, first enum variants)
+pub fn zeroed<T: AnchorDeserialize + Space>() -> T {
+    T::deserialize(&mut &vec![0; T::INIT_SPACE][..]).unwrap()
+}
+
+/// Zero-copy accounts with every byte zeroed
+pub fn zeroed_copy<T: ZeroCopy>() -> T {
+    T::zeroed()
+}
+
+/// `AccountInfo`s for `accounts`, in instruction order
+pub fn infos(accounts: &mut [TestAccount]) -> Vec<AccountInfo<'_>> {
+    accounts.iter_mut().map(TestAccount::info).collect()
+}
+
+/// Runs `T`'s account validation as the program's entrypoint would
+pub fn try_accounts<'info, T>(
+    program_id: &Pubkey,
+    infos: &'info [AccountInfo<'info>],
+    ix_data: &[u8],
+) -> Result<()>
+where
+    T: Accounts<'info, T::Bumps> + Bumps,
+    T::Bumps: Default,
+{
+    let mut bumps = T::Bumps::default();
+    T::try_accounts(
+        program_id,
+        &mut &infos[..],
+        ix_data,
+        &mut bumps,
+        &mut BTreeSet::new(),
+    )
+    .map(|_| ())
+}
+
+/// Whether `result` is `code` (an anchor or program error) raised for the
+/// account named `account` (token constraints don't record the account, only
+/// the code is checked)
+pub fn rejected(result: &Result<()>, code: impl Into<u32>, account: &str) -> bool {
+    match result {
+        Err(Error::AnchorError(err)) => {
+            err.error_code_number == code.into()
+                && match &err.error_origin {
+                    Some(ErrorOrigin::AccountName(name)) => name == account,
+                    _ => true,
+                }
+        }
+        _ => false,
+    }
+}
+
+/// Whether `result` failed with `code` (an anchor or program error)
+pub fn failed_with(result: &ProgramResult, code: impl Into<u32>) -> bool {
+    matches!(result, Err(ProgramError::Custom(c)) if *c == code.into())
+}
+
+/// A program's `entry`, as generated by `#[program]`
+pub type Entry = for<'info> fn(&Pubkey, &'info [AccountInfo<'info>], &[u8]) -> ProgramResult;
+
+thread_local! {
+    /// Per test thread, since the stubs are process-wide
+    static UNIX_TIMESTAMP: Cell<i64> = const { Cell::new(0) };
+    static SLOT: Cell<u64> = const { Cell::new(0) };
+    /// Programs `run` executes CPIs into
+    static PROGRAMS: RefCell<Vec<(Pubkey, Entry)>> = const { RefCell::new(Vec::new()) };
+    /// Programs executing under `run`, innermost last
+    static CALLERS: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `entry` with `callers` extended by `program_id`
+fn execute<'info>(
+    entry: Entry,
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    CALLERS.with(|callers| callers.borrow_mut().push(*program_id));
+    let result = entry(program_id, accounts, data);
+    CALLERS.with(|callers| callers.borrow_mut().pop());
+    result
+}
+
+/// Transfers natively; the system program's other instructions stay no-ops,
+/// so `init` accounts must still be pre-created
+fn system_program_entry(_: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if let Ok(SystemInstruction::Transfer { lamports }) = limited_deserialize(data, 1024) {
+        let (from, to) = (&accounts[0], &accounts[1]);
+        if !from.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let balance = from.lamports();
+        **from.try_borrow_mut_lamports()? = balance
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        **to.try_borrow_mut_lamports()? += lamports;
+    }
+    Ok(())
+}
+
+/// Serves the Rent sysvar, which `init` constraints read, the Clock sysvar
+/// handlers read, and CPIs made under `run`
+struct TestStubs;
+
+impl SyscallStubs for TestStubs {
+    /// Passes the caller's accounts through with the privileges the
+    /// instruction asks for, after checking every requested signer either
+    /// signed the caller or is a PDA of the caller signed with `signers_seeds`
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let Some(caller) = CALLERS.with(|callers| callers.borrow().last().copied()) else {
+            return Ok(());
+        };
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if meta.is_signer && !info.is_signer && !signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let mut info = info.clone();
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            accounts.push(info);
+        }
+        // The callee may keep references for as long as the accounts live
+        let accounts = Box::leak(accounts.into_boxed_slice());
+
+        let program_id = &instruction.program_id;
+        if *program_id == spl_token::ID {
+            spl_token::processor::Processor::process(program_id, accounts, &instruction.data)
+        } else if *program_id == system_program::ID {
+            system_program_entry(program_id, accounts, &instruction.data)
+        } else if let Some(entry) = PROGRAMS.with(|programs| {
+            programs
+                .borrow()
+                .iter()
+                .find(|(id, _)| id == program_id)
+                .map(|(_, entry)| *entry)
+        }) {
+            execute(entry, program_id, accounts, &instruction.data)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the runtime passes a pointer to a `Rent`
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            unix_timestamp: UNIX_TIMESTAMP.with(Cell::get),
+            slot: SLOT.with(Cell::get),
+            ..Clock::default()
+        };
+        // SAFETY: the runtime passes a pointer to a `Clock`
+        unsafe { *(var_addr as *mut Clock) = clock };
+        SUCCESS
+    }
+}
+
+/// Install before validating contexts with `init` accounts. CPIs made
+/// outside `run` stay no-ops, and system account creation always does, so
+/// pre-create `init` accounts: owned by the program, data zeroed
+pub fn install_rent_stub() {
+    set_syscall_stubs(Box::new(TestStubs));
+}
+
+/// Executes an instruction as `program_id`'s entrypoint would, with its CPIs:
+/// SPL token and system transfers run natively, `programs` run through their
+/// `entry`, and CPIs to any other program are no-ops. Account changes are
+/// written back to `accounts`.
+pub fn run(
+    program_id: Pubkey,
+    entry: Entry,
+    programs: &[(Pubkey, Entry)],
+    accounts: &mut [TestAccount],
+    ix_data: &[u8],
+) -> ProgramResult {
+    install_rent_stub();
+    PROGRAMS.with(|registered| *registered.borrow_mut() = programs.to_vec());
+    let infos = infos(accounts);
+    execute(entry, &program_id, &infos, ix_data)
+}
+
+/// Sets the time `Clock::get` returns on this thread, for running handlers
+pub fn set_clock(unix_timestamp: i64) {
+    install_rent_stub();
+    UNIX_TIMESTAMP.with(|time| time.set(unix_timestamp));
+}
+
+/// Sets the slot `Clock::get` returns on this thread
+pub fn set_slot(slot: u64) {
+    install_rent_stub();
+    SLOT.with(|current| current.set(slot));
+}
@@ -0,0 +1,359 @@
+//! Protocol-wide accounting
+//!
+//! `Collateral.total_deposited` and `Borrowable.total_borrowed` /
+//! `total_available` are kept live in native units. Those accounts are owned
+//! by core, so the lending, LP, GAD and leverage programs report every change
+//! through `record_collateral_change` / `record_borrowable_change`, signed by
+//! their `[ACCOUNTING_SEED]` PDA. The insurance fund on `Protocol` is credited
+//! the same way, through `record_insurance_fee`.
+//!
+//! USD totals on `Protocol` are recomputed from price feeds by the
+//! permissionless `refresh_protocol_stats`, which must be given every
+//! registered asset.
+
+use anchor_lang::prelude::*;
+
+use crate::constants::BPS_DENOMINATOR;
+use crate::errors::LegasiError;
+use crate::state::{
+    AccountingSource, Borrowable, BorrowableChange, Collateral, CollateralChange, PriceFeed,
+    Protocol,
+};
+use crate::valuation::{load_core_account, require_price_feed_address, value_usd};
+
+/// Seed of the PDA each program signs accounting CPIs with
+pub const ACCOUNTING_SEED: &[u8] = b"accounting";
+
+pub mod lending_program {
+    use anchor_lang::prelude::*;
+    declare_id!("9356RoSbLTzWE55ab6GktcTocaNhPuBEDZvsmqjkCZYw");
+}
+
+pub mod lp_program {
+    use anchor_lang::prelude::*;
+    declare_id!("CTwY4VSeueesSBc95G38X3WJYPriJEzyxjcCaZAc5LbY");
+}
+
+pub mod gad_program {
+    use anchor_lang::prelude::*;
+    declare_id!("89E84ALdDdGGNuJAxho2H45aC25kqNdGg7QtwTJ3pngK");
+}
+
+pub mod leverage_program {
+    use anchor_lang::prelude::*;
+    declare_id!("AVATHjGrdQ1KqtjHQ4gwRcuAYjwwScwgPsujLDpiA2g3");
+}
+
+pub mod flash_program {
+    use anchor_lang::prelude::*;
+    declare_id!("Fj8CJNK1gBAuNR7dFbKLDckSstKmZn8ihTGwFXxfY93m");
+}
+
+impl AccountingSource {
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            AccountingSource::Lending => lending_program::ID,
+            AccountingSource::Lp => lp_program::ID,
+            AccountingSource::Gad => gad_program::ID,
+            AccountingSource::Leverage => leverage_program::ID,
+            AccountingSource::Flash => flash_program::ID,
+        }
+    }
+
+    /// Accounting PDA of the source program
+    pub fn authority(&self) -> Pubkey {
+        Pubkey::find_program_address(&[ACCOUNTING_SEED], &self.program_id()).0
+    }
+
+    pub fn require_authority(&self, signer: &Pubkey) -> std::result::Result<(), LegasiError> {
+        if *signer != self.authority() {
+            return Err(LegasiError::Unauthorized);
+        }
+        Ok(())
+    }
+}
+
+// Decreases saturate: positions opened before the aggregates were tracked
+// must still be able to withdraw and repay.
+
+impl Collateral {
+    pub fn record_change(
+        &mut self,
+        change: CollateralChange,
+    ) -> std::result::Result<(), LegasiError> {
+        match change {
+            CollateralChange::Deposited(amount) => {
+                self.total_deposited = self
+                    .total_deposited
+                    .checked_add(amount)
+                    .ok_or(LegasiError::MathOverflow)?;
+            }
+            CollateralChange::Withdrawn(amount) => {
+                self.total_deposited = self.total_deposited.saturating_sub(amount);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Borrowable {
+    pub fn record_change(
+        &mut self,
+        change: BorrowableChange,
+    ) -> std::result::Result<(), LegasiError> {
+        match change {
+            BorrowableChange::Supplied(amount) => {
+                self.total_available = self
+                    .total_available
+                    .checked_add(amount)
+                    .ok_or(LegasiError::MathOverflow)?;
+            }
+            BorrowableChange::Withdrawn(amount) => {
+                self.total_available = self.total_available.saturating_sub(amount);
+            }
+            BorrowableChange::Borrowed(amount) => {
+                self.total_borrowed = self
+                    .total_borrowed
+                    .checked_add(amount)
+                    .ok_or(LegasiError::MathOverflow)?;
+                self.total_available = self.total_available.saturating_sub(amount);
+            }
+            BorrowableChange::Repaid {
+                principal,
+                received,
+            } => {
+                self.total_borrowed = self.total_borrowed.saturating_sub(principal);
+                self.total_available = self
+                    .total_available
+                    .checked_add(received)
+                    .ok_or(LegasiError::MathOverflow)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Share of supplied liquidity currently lent out (bps)
+    pub fn utilization_bps(&self) -> u64 {
+        let supplied = self.total_borrowed as u128 + self.total_available as u128;
+        if supplied == 0 {
+            return 0;
+        }
+        (self.total_borrowed as u128 * BPS_DENOMINATOR as u128 / supplied) as u64
+    }
+}
+
+/// Accounts a program signs accounting CPIs with
+#[cfg(feature = "cpi")]
+pub struct AccountingCpi<'info> {
+    pub core_program: AccountInfo<'info>,
+    /// The calling program's `[ACCOUNTING_SEED]` PDA
+    pub authority: AccountInfo<'info>,
+    pub authority_bump: u8,
+    pub source: AccountingSource,
+}
+
+#[cfg(feature = "cpi")]
+impl<'info> AccountingCpi<'info> {
+    pub fn record_collateral(
+        &self,
+        collateral: AccountInfo<'info>,
+        change: CollateralChange,
+    ) -> Result<()> {
+        let bump = [self.authority_bump];
+        let seeds: &[&[u8]] = &[ACCOUNTING_SEED, &bump];
+        crate::cpi::record_collateral_change(
+            CpiContext::new_with_signer(
+                self.core_program.clone(),
+                crate::cpi::accounts::RecordCollateralChange {
+                    authority: self.authority.clone(),
+                    collateral,
+                },
+                &[seeds],
+            ),
+            self.source,
+            change,
+        )
+    }
+
+    pub fn record_borrowable(
+        &self,
+        borrowable: AccountInfo<'info>,
+        change: BorrowableChange,
+    ) -> Result<()> {
+        let bump = [self.authority_bump];
+        let seeds: &[&[u8]] = &[ACCOUNTING_SEED, &bump];
+        crate::cpi::record_borrowable_change(
+            CpiContext::new_with_signer(
+                self.core_program.clone(),
+                crate::cpi::accounts::RecordBorrowableChange {
+                    authority: self.authority.clone(),
+                    borrowable,
+                },
+                &[seeds],
+            ),
+            self.source,
+            change,
+        )
+    }
+
+    pub fn record_insurance_fee(&self, protocol: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let bump = [self.authority_bump];
+        let seeds: &[&[u8]] = &[ACCOUNTING_SEED, &bump];
+        crate::cpi::record_insurance_fee(
+            CpiContext::new_with_signer(
+                self.core_program.clone(),
+                crate::cpi::accounts::RecordInsuranceFee {
+                    authority: self.authority.clone(),
+                    protocol,
+                },
+                &[seeds],
+            ),
+            self.source,
+            amount,
+        )
+    }
+}
+
+/// Protocol-wide USD totals (6 decimals)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProtocolStats {
+    pub total_collateral_usd: u64,
+    pub total_borrowed_usd: u64,
+}
+
+/// Value every registered asset from `(Collateral | Borrowable, PriceFeed)` pairs
+/// Each asset must appear exactly once so the totals can't be understated.
+pub fn load_protocol_stats(
+    accounts: &[AccountInfo],
+    protocol: &Protocol,
+    now: i64,
+) -> Result<ProtocolStats> {
+    let pairs = accounts.chunks_exact(2);
+    require!(
+        pairs.remainder().is_empty(),
+        LegasiError::IncompleteAssetList
+    );
+
+    let mut collateral_mints: Vec<Pubkey> = Vec::new();
+    let mut borrowable_mints: Vec<Pubkey> = Vec::new();
+    let mut stats = ProtocolStats::default();
+    for pair in pairs {
+        let price_feed: PriceFeed = load_core_account(&pair[1])?;
+        if let Ok(collateral) = load_core_account::<Collateral>(&pair[0]) {
+            require!(
+                !collateral_mints.contains(&collateral.mint),
+                LegasiError::IncompleteAssetList
+            );
+            collateral_mints.push(collateral.mint);
+            require_price_feed_address(&collateral.mint, &price_feed, pair[1].key)?;
+            let value = value_usd(
+                collateral.total_deposited,
+                collateral.decimals,
+                price_feed.fresh_price(now)?,
+            )?;
+            stats.total_collateral_usd = stats
+                .total_collateral_usd
+                .checked_add(value)
+                .ok_or(LegasiError::MathOverflow)?;
+        } else {
+            let borrowable: Borrowable = load_core_account(&pair[0])?;
+            require!(
+                !borrowable_mints.contains(&borrowable.mint),
+                LegasiError::IncompleteAssetList
+            );
+            borrowable_mints.push(borrowable.mint);
+            require_price_feed_address(&borrowable.mint, &price_feed, pair[1].key)?;
+            let value = value_usd(
+                borrowable.total_borrowed,
+                borrowable.decimals,
+                price_feed.fresh_price(now)?,
+            )?;
+            stats.total_borrowed_usd = stats
+                .total_borrowed_usd
+                .checked_add(value)
+                .ok_or(LegasiError::MathOverflow)?;
+        }
+    }
+
+    require!(
+        collateral_mints.len() == protocol.collateral_count as usize
+            && borrowable_mints.len() == protocol.borrowable_count as usize,
+        LegasiError::IncompleteAssetList
+    );
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AssetKind, EModeCategory};
+
+    fn borrowable() -> Borrowable {
+        Borrowable {
+            mint: Pubkey::new_unique(),
+            oracle: Pubkey::new_unique(),
+            interest_rate_bps: 500,
+            decimals: 6,
+            is_active: true,
+            total_borrowed: 0,
+            total_available: 0,
+            kind: AssetKind::Stablecoin,
+            emode_category: EModeCategory::Stablecoins,
+            pause_flags: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn test_borrowable_aggregates_track_lifecycle() {
+        let mut usdc = borrowable();
+        usdc.record_change(BorrowableChange::Supplied(1_000))
+            .unwrap();
+        usdc.record_change(BorrowableChange::Borrowed(600)).unwrap();
+        assert_eq!((usdc.total_borrowed, usdc.total_available), (600, 400));
+        assert_eq!(usdc.utilization_bps(), 6_000);
+
+        // 200 principal + 10 interest repaid
+        usdc.record_change(BorrowableChange::Repaid {
+            principal: 200,
+            received: 210,
+        })
+        .unwrap();
+        assert_eq!((usdc.total_borrowed, usdc.total_available), (400, 610));
+
+        // GAD write-down clears debt without returning liquidity
+        usdc.record_change(BorrowableChange::Repaid {
+            principal: 100,
+            received: 0,
+        })
+        .unwrap();
+        assert_eq!((usdc.total_borrowed, usdc.total_available), (300, 610));
+
+        usdc.record_change(BorrowableChange::Withdrawn(10)).unwrap();
+        assert_eq!(usdc.total_available, 600);
+    }
+
+    #[test]
+    fn test_decreases_saturate_for_untracked_balances() {
+        let mut usdc = borrowable();
+        usdc.record_change(BorrowableChange::Repaid {
+            principal: 50,
+            received: 50,
+        })
+        .unwrap();
+        assert_eq!((usdc.total_borrowed, usdc.total_available), (0, 50));
+        assert_eq!(borrowable().utilization_bps(), 0);
+    }
+
+    #[test]
+    fn test_accounting_authority_is_program_pda() {
+        let lending = AccountingSource::Lending;
+        let (expected, _) = Pubkey::find_program_address(&[ACCOUNTING_SEED], &lending_program::ID);
+        assert!(lending.require_authority(&expected).is_ok());
+        // Another program's PDA can't report on the lending program's behalf
+        assert!(matches!(
+            lending.require_authority(&AccountingSource::Gad.authority()),
+            Err(LegasiError::Unauthorized)
+        ));
+    }
+}
@@ -0,0 +1,434 @@
+//! Asset configuration updates
+//!
+//! Validation shared by registration, direct config updates and timelocked
+//! changes. Max LTV decreases are phased in linearly over
+//! `LTV_RAMP_DURATION` so existing borrowers aren't pushed over the limit at
+//! once; increases apply immediately.
+//!
+//! Direct updates only reduce risk (lower max LTV, deactivate); every other
+//! change goes through the timelock (see `timelock`).
+
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::LegasiError;
+use crate::state::{
+    AssetKind, AssetMetadata, Borrowable, BorrowableConfigUpdate, Collateral,
+    CollateralConfigUpdate,
+};
+
+/// 0 < LTV < liquidation threshold <= 100%, bounded bonus, and a liquidation
+/// at the threshold must still be covered by the collateral
+pub fn validate_collateral_risk(
+    max_ltv_bps: u16,
+    liquidation_threshold_bps: u16,
+    liquidation_bonus_bps: u16,
+) -> std::result::Result<(), LegasiError> {
+    let valid = max_ltv_bps > 0
+        && max_ltv_bps < liquidation_threshold_bps
+        && liquidation_threshold_bps as u64 <= BPS_DENOMINATOR
+        && liquidation_bonus_bps <= MAX_LIQUIDATION_BONUS_BPS
+        && (liquidation_threshold_bps as u64) * (BPS_DENOMINATOR + liquidation_bonus_bps as u64)
+            <= BPS_DENOMINATOR * BPS_DENOMINATOR;
+    if !valid {
+        return Err(LegasiError::InvalidRiskParameters);
+    }
+    Ok(())
+}
+
+pub fn validate_interest_rate(interest_rate_bps: u16) -> std::result::Result<(), LegasiError> {
+    if interest_rate_bps > MAX_INTEREST_RATE_BPS {
+        return Err(LegasiError::InvalidRiskParameters);
+    }
+    Ok(())
+}
+
+/// Native SOL is listed only under NATIVE_SOL_MINT and can't be borrowed
+/// (borrows are paid out of SPL token pools)
+pub fn validate_asset_metadata(
+    mint: &Pubkey,
+    metadata: &AssetMetadata,
+    borrowable: bool,
+) -> std::result::Result<(), LegasiError> {
+    let native = metadata.kind == AssetKind::Native;
+    if native != (*mint == NATIVE_SOL_MINT) || (native && borrowable) {
+        return Err(LegasiError::AssetNotSupported);
+    }
+    Ok(())
+}
+
+impl Collateral {
+    /// Max LTV in force at `now`
+    pub fn effective_max_ltv_bps(&self, now: i64) -> u16 {
+        if now >= self.ltv_ramp_end_time || self.ltv_ramp_start_bps <= self.max_ltv_bps {
+            return self.max_ltv_bps;
+        }
+        let elapsed = now.saturating_sub(self.ltv_ramp_start_time).max(0) as u128;
+        let duration = (self.ltv_ramp_end_time - self.ltv_ramp_start_time).max(1) as u128;
+        let drop = (self.ltv_ramp_start_bps - self.max_ltv_bps) as u128;
+        self.ltv_ramp_start_bps - (drop * elapsed / duration) as u16
+    }
+
+    /// Most that may be owed (USD, 6 decimals) against `collateral_usd` of
+    /// this asset: the max LTV in force at `now` plus `bonus_bps`
+    pub fn max_borrow_usd(
+        &self,
+        collateral_usd: u64,
+        bonus_bps: u64,
+        now: i64,
+    ) -> std::result::Result<u64, LegasiError> {
+        let ltv_bps = (self.effective_max_ltv_bps(now) as u64).saturating_add(bonus_bps);
+        let max_borrow = collateral_usd as u128 * ltv_bps as u128 / BPS_DENOMINATOR as u128;
+        u64::try_from(max_borrow).map_err(|_| LegasiError::MathOverflow)
+    }
+
+    /// Set risk parameters; a max LTV decrease ramps from the LTV in force
+    pub fn set_risk_params(
+        &mut self,
+        max_ltv_bps: u16,
+        liquidation_threshold_bps: u16,
+        liquidation_bonus_bps: u16,
+        now: i64,
+    ) -> std::result::Result<(), LegasiError> {
+        validate_collateral_risk(
+            max_ltv_bps,
+            liquidation_threshold_bps,
+            liquidation_bonus_bps,
+        )?;
+
+        let current_ltv_bps = self.effective_max_ltv_bps(now);
+        if max_ltv_bps < current_ltv_bps {
+            // The threshold must stay above the LTV still in force during the ramp
+            if liquidation_threshold_bps <= current_ltv_bps {
+                return Err(LegasiError::InvalidRiskParameters);
+            }
+            self.ltv_ramp_start_bps = current_ltv_bps;
+            self.ltv_ramp_start_time = now;
+            self.ltv_ramp_end_time = now.saturating_add(LTV_RAMP_DURATION);
+        } else {
+            self.ltv_ramp_start_bps = max_ltv_bps;
+            self.ltv_ramp_start_time = now;
+            self.ltv_ramp_end_time = now;
+        }
+
+        self.max_ltv_bps = max_ltv_bps;
+        self.liquidation_threshold_bps = liquidation_threshold_bps;
+        self.liquidation_bonus_bps = liquidation_bonus_bps;
+        Ok(())
+    }
+
+    /// Apply a direct update: a max LTV decrease (ramped) or deactivation
+    /// Fields set to their current value are ignored; any other change
+    /// must be queued behind the timelock
+    pub fn apply_config_update(
+        &mut self,
+        update: &CollateralConfigUpdate,
+        now: i64,
+    ) -> std::result::Result<(), LegasiError> {
+        if update
+            .liquidation_threshold_bps
+            .is_some_and(|bps| bps != self.liquidation_threshold_bps)
+            || update
+                .liquidation_bonus_bps
+                .is_some_and(|bps| bps != self.liquidation_bonus_bps)
+            || update.max_ltv_bps.is_some_and(|bps| bps > self.max_ltv_bps)
+            || update.oracle.is_some_and(|oracle| oracle != self.oracle)
+            || update
+                .emode_category
+                .is_some_and(|category| category != self.emode_category)
+            || (update.is_active == Some(true) && !self.is_active)
+        {
+            return Err(LegasiError::RequiresTimelock);
+        }
+
+        if let Some(max_ltv_bps) = update.max_ltv_bps.filter(|&bps| bps < self.max_ltv_bps) {
+            self.set_risk_params(
+                max_ltv_bps,
+                self.liquidation_threshold_bps,
+                self.liquidation_bonus_bps,
+                now,
+            )?;
+        }
+        if update.is_active == Some(false) {
+            self.is_active = false;
+        }
+        Ok(())
+    }
+}
+
+impl Borrowable {
+    /// Apply a direct update: deactivation only
+    /// Fields set to their current value are ignored; any other change
+    /// must be queued behind the timelock
+    pub fn apply_config_update(
+        &mut self,
+        update: &BorrowableConfigUpdate,
+    ) -> std::result::Result<(), LegasiError> {
+        if update
+            .interest_rate_bps
+            .is_some_and(|bps| bps != self.interest_rate_bps)
+            || update.oracle.is_some_and(|oracle| oracle != self.oracle)
+            || update
+                .emode_category
+                .is_some_and(|category| category != self.emode_category)
+            || (update.is_active == Some(true) && !self.is_active)
+        {
+            return Err(LegasiError::RequiresTimelock);
+        }
+
+        if update.is_active == Some(false) {
+            self.is_active = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AssetKind, EModeCategory};
+
+    fn collateral() -> Collateral {
+        Collateral {
+            mint: Pubkey::default(),
+            oracle: Pubkey::new_unique(),
+            max_ltv_bps: 7500,
+            liquidation_threshold_bps: 8000,
+            liquidation_bonus_bps: 500,
+            decimals: 9,
+            is_active: true,
+            total_deposited: 0,
+            kind: AssetKind::Native,
+            emode_category: EModeCategory::SolCorrelated,
+            pause_flags: 0,
+            ltv_ramp_start_bps: 7500,
+            ltv_ramp_start_time: 0,
+            ltv_ramp_end_time: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn test_validate_asset_metadata() {
+        let metadata = |kind| AssetMetadata {
+            decimals: 9,
+            kind,
+            emode_category: EModeCategory::SolCorrelated,
+        };
+        let lst_mint = Pubkey::new_unique();
+        assert!(
+            validate_asset_metadata(&NATIVE_SOL_MINT, &metadata(AssetKind::Native), false).is_ok()
+        );
+        assert!(
+            validate_asset_metadata(&lst_mint, &metadata(AssetKind::LiquidStaking), true).is_ok()
+        );
+        // Native kind is reserved for the wrapped SOL mint, and vice versa
+        assert!(validate_asset_metadata(&lst_mint, &metadata(AssetKind::Native), false).is_err());
+        assert!(
+            validate_asset_metadata(&NATIVE_SOL_MINT, &metadata(AssetKind::Volatile), false)
+                .is_err()
+        );
+        // Native SOL can't be borrowed
+        assert!(
+            validate_asset_metadata(&NATIVE_SOL_MINT, &metadata(AssetKind::Native), true).is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_collateral_risk() {
+        assert!(validate_collateral_risk(7500, 8000, 500).is_ok());
+        // LTV must stay below the liquidation threshold
+        assert!(validate_collateral_risk(8000, 8000, 500).is_err());
+        assert!(validate_collateral_risk(0, 8000, 500).is_err());
+        assert!(validate_collateral_risk(7500, 10001, 0).is_err());
+        assert!(validate_collateral_risk(7500, 8000, MAX_LIQUIDATION_BONUS_BPS + 1).is_err());
+        // 95% threshold with a 10% bonus would pay out more than the collateral
+        assert!(validate_collateral_risk(9000, 9500, 1000).is_err());
+    }
+
+    #[test]
+    fn test_ltv_decrease_ramps_linearly() {
+        let mut collateral = collateral();
+        collateral.set_risk_params(6500, 8000, 500, 1_000).unwrap();
+
+        assert_eq!(collateral.max_ltv_bps, 6500);
+        assert_eq!(collateral.effective_max_ltv_bps(1_000), 7500);
+        assert_eq!(
+            collateral.effective_max_ltv_bps(1_000 + LTV_RAMP_DURATION / 2),
+            7000
+        );
+        assert_eq!(
+            collateral.effective_max_ltv_bps(1_000 + LTV_RAMP_DURATION),
+            6500
+        );
+    }
+
+    #[test]
+    fn test_max_borrow_follows_config_and_ramp() {
+        let collateral_usd = 1_000_000_000; // $1,000
+        let mut collateral = collateral();
+        assert_eq!(
+            collateral.max_borrow_usd(collateral_usd, 0, 0).unwrap(),
+            750_000_000
+        );
+        // Reputation bonus on top
+        assert_eq!(
+            collateral.max_borrow_usd(collateral_usd, 100, 0).unwrap(),
+            760_000_000
+        );
+
+        // A decrease shrinks capacity along the ramp
+        collateral
+            .apply_config_update(
+                &CollateralConfigUpdate {
+                    max_ltv_bps: Some(6500),
+                    ..Default::default()
+                },
+                1_000,
+            )
+            .unwrap();
+        let mid_ramp = 1_000 + LTV_RAMP_DURATION / 2;
+        assert_eq!(
+            collateral
+                .max_borrow_usd(collateral_usd, 0, mid_ramp)
+                .unwrap(),
+            700_000_000
+        );
+        assert_eq!(
+            collateral
+                .max_borrow_usd(collateral_usd, 0, 1_000 + LTV_RAMP_DURATION)
+                .unwrap(),
+            650_000_000
+        );
+
+        // An increase applies at once
+        collateral
+            .set_risk_params(8000, 8500, 500, mid_ramp)
+            .unwrap();
+        assert_eq!(
+            collateral
+                .max_borrow_usd(collateral_usd, 0, mid_ramp)
+                .unwrap(),
+            800_000_000
+        );
+    }
+
+    #[test]
+    fn test_ltv_increase_is_immediate_and_ends_ramp() {
+        let mut collateral = collateral();
+        collateral.set_risk_params(6500, 8000, 500, 0).unwrap();
+        collateral
+            .set_risk_params(7000, 8000, 500, LTV_RAMP_DURATION / 2)
+            .unwrap();
+        assert_eq!(
+            collateral.effective_max_ltv_bps(LTV_RAMP_DURATION / 2),
+            7000
+        );
+    }
+
+    #[test]
+    fn test_threshold_must_exceed_ltv_in_force() {
+        let mut collateral = collateral();
+        // 7500 is still in force when the ramp starts
+        assert!(matches!(
+            collateral.set_risk_params(6000, 7000, 500, 0),
+            Err(LegasiError::InvalidRiskParameters)
+        ));
+    }
+
+    #[test]
+    fn test_config_update_keeps_unset_fields() {
+        let mut collateral = collateral();
+        let oracle = collateral.oracle;
+        collateral
+            .apply_config_update(
+                &CollateralConfigUpdate {
+                    is_active: Some(false),
+                    max_ltv_bps: Some(7000),
+                    liquidation_bonus_bps: Some(500),
+                    ..Default::default()
+                },
+                0,
+            )
+            .unwrap();
+        assert!(!collateral.is_active);
+        assert_eq!(collateral.max_ltv_bps, 7000);
+        assert_eq!(collateral.effective_max_ltv_bps(0), 7500);
+        assert_eq!(collateral.liquidation_bonus_bps, 500);
+        assert_eq!(collateral.oracle, oracle);
+    }
+
+    #[test]
+    fn test_direct_update_only_reduces_risk() {
+        let risky = [
+            CollateralConfigUpdate {
+                max_ltv_bps: Some(7600),
+                ..Default::default()
+            },
+            CollateralConfigUpdate {
+                liquidation_threshold_bps: Some(8500),
+                ..Default::default()
+            },
+            CollateralConfigUpdate {
+                liquidation_bonus_bps: Some(700),
+                ..Default::default()
+            },
+            CollateralConfigUpdate {
+                oracle: Some(Pubkey::new_unique()),
+                ..Default::default()
+            },
+            CollateralConfigUpdate {
+                emode_category: Some(EModeCategory::None),
+                ..Default::default()
+            },
+        ];
+        for update in risky {
+            let mut collateral = collateral();
+            assert!(matches!(
+                collateral.apply_config_update(&update, 0),
+                Err(LegasiError::RequiresTimelock)
+            ));
+        }
+
+        // Reactivation is timelocked too
+        let mut collateral = collateral();
+        collateral.is_active = false;
+        let reactivate = CollateralConfigUpdate {
+            is_active: Some(true),
+            ..Default::default()
+        };
+        assert!(matches!(
+            collateral.apply_config_update(&reactivate, 0),
+            Err(LegasiError::RequiresTimelock)
+        ));
+
+        let mut borrowable = Borrowable {
+            mint: Pubkey::default(),
+            oracle: Pubkey::new_unique(),
+            interest_rate_bps: 500,
+            decimals: 6,
+            is_active: true,
+            total_borrowed: 0,
+            total_available: 0,
+            kind: AssetKind::Stablecoin,
+            emode_category: EModeCategory::Stablecoins,
+            pause_flags: 0,
+            bump: 0,
+        };
+        let rate = BorrowableConfigUpdate {
+            interest_rate_bps: Some(400),
+            ..Default::default()
+        };
+        assert!(matches!(
+            borrowable.apply_config_update(&rate),
+            Err(LegasiError::RequiresTimelock)
+        ));
+        let deactivate = BorrowableConfigUpdate {
+            interest_rate_bps: Some(500),
+            is_active: Some(false),
+            ..Default::default()
+        };
+        borrowable.apply_config_update(&deactivate).unwrap();
+        assert!(!borrowable.is_active);
+    }
+}
@@ -7,12 +7,11 @@ pub const USD_MULTIPLIER: u64 = 1_000_000;
 
 /// SOL decimals (9)
 pub const SOL_DECIMALS: u8 = 9;
-pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
 
-/// Default max LTV for collateral assets (basis points)
-/// Accepted collaterals: SOL, cbBTC
-pub const DEFAULT_SOL_MAX_LTV_BPS: u16 = 7500; // 75%
-pub const DEFAULT_BTC_MAX_LTV_BPS: u16 = 7500; // 75%
+/// Wrapped SOL mint - registry key for native SOL collateral and its price feed
+pub const NATIVE_SOL_MINT: anchor_lang::prelude::Pubkey =
+    anchor_spl::token::spl_token::native_mint::ID;
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
 
 /// GAD thresholds (basis points above max LTV)
 pub const GAD_SOFT_THRESHOLD_BPS: u16 = 500; // 5% above max LTV = soft deleverage
@@ -44,11 +43,92 @@ pub const CRANKER_REWARD_BPS: u64 = 50; // 0.5%
 /// Price feed staleness threshold (seconds)
 pub const PRICE_STALENESS_THRESHOLD: i64 = 300; // 5 minutes
 
+/// TWAP lookback window (seconds)
+pub const TWAP_WINDOW_SECONDS: i64 = 1800; // 30 minutes
+
+/// Minimum spacing between TWAP observations (seconds); updates closer to
+/// the latest observation are merged into it
+pub const PRICE_OBSERVATION_INTERVAL: i64 = 60;
+
+/// Price observations kept per feed for TWAP: at the minimum spacing a full
+/// buffer spans the whole window
+pub const PRICE_OBSERVATION_CAPACITY: usize =
+    (TWAP_WINDOW_SECONDS / PRICE_OBSERVATION_INTERVAL) as usize + 1;
+
+/// EMA smoothing period (seconds) - a full period moves the EMA onto the new price
+pub const EMA_PERIOD_SECONDS: i64 = 900; // 15 minutes
+
+/// Max oracle sources aggregated per price feed
+pub const MAX_ORACLE_SOURCES: usize = 4;
+
+/// Default max deviation of a source from the median (basis points)
+pub const DEFAULT_MAX_ORACLE_DEVIATION_BPS: u16 = 200; // 2%
+
+/// Default circuit breaker: max price move per interval (basis points)
+pub const DEFAULT_MAX_PRICE_MOVE_BPS: u16 = 2000; // 20%
+
+/// Default circuit breaker interval (seconds)
+pub const DEFAULT_PRICE_MOVE_INTERVAL: i64 = 3600; // 1 hour
+
+/// Default confidence multiplier k for conservative pricing (bps, 10000 = 1x)
+pub const DEFAULT_CONFIDENCE_MULTIPLIER_BPS: u16 = 10000;
+
+/// Max confidence multiplier k (bps)
+pub const MAX_CONFIDENCE_MULTIPLIER_BPS: u16 = 50000; // 5x
+
+/// Liquidation bonus upper bound (basis points)
+pub const MAX_LIQUIDATION_BONUS_BPS: u16 = 2000; // 20%
+
+/// Duration over which a max LTV decrease is phased in (seconds)
+pub const LTV_RAMP_DURATION: i64 = 7 * 86400; // 7 days
+
+/// Borrowable interest rate upper bound (basis points)
+pub const MAX_INTEREST_RATE_BPS: u16 = 10000; // 100% APR
+
+/// Default delay before a queued parameter change can execute (seconds)
+pub const DEFAULT_TIMELOCK_DELAY: i64 = 2 * 86400; // 48 hours
+
+/// Timelock delay bounds (seconds)
+pub const MIN_TIMELOCK_DELAY: i64 = 3600; // 1 hour
+pub const MAX_TIMELOCK_DELAY: i64 = 30 * 86400; // 30 days
+
+/// Window after the ETA in which a queued change can still execute (seconds)
+pub const TIMELOCK_GRACE_PERIOD: i64 = 14 * 86400; // 14 days
+
+/// Pause flags (protocol-level on `Protocol`, per-asset on `Collateral`/`Borrowable`)
+pub const PAUSE_DEPOSIT: u16 = 1 << 0;
+pub const PAUSE_BORROW: u16 = 1 << 1;
+pub const PAUSE_REPAY: u16 = 1 << 2;
+pub const PAUSE_WITHDRAW: u16 = 1 << 3;
+pub const PAUSE_GAD: u16 = 1 << 4;
+pub const PAUSE_FLASH: u16 = 1 << 5;
+pub const PAUSE_LP_DEPOSIT: u16 = 1 << 6;
+pub const PAUSE_LP_WITHDRAW: u16 = 1 << 7;
+pub const PAUSE_ALL: u16 = (1 << 8) - 1;
+/// Emergency pause: everything except repaying and depositing collateral
+pub const PAUSE_EMERGENCY: u16 = PAUSE_ALL & !(PAUSE_DEPOSIT | PAUSE_REPAY);
+
+/// Delegate permissions (`Delegate::permissions`)
+pub const DELEGATE_BORROW: u8 = 1 << 0;
+/// x402 payments from the agent balance (auto-borrowing also needs `DELEGATE_BORROW`)
+pub const DELEGATE_X402: u8 = 1 << 1;
+pub const DELEGATE_REPAY: u8 = 1 << 2;
+pub const DELEGATE_ALL: u8 = (1 << 3) - 1;
+
+/// Length of a daily borrow limit period (seconds)
+pub const BORROW_PERIOD_SECONDS: i64 = 86400;
+
+/// Max publishers per push oracle
+pub const MAX_PUSH_PUBLISHERS: usize = 16;
+
+/// Push oracle round duration: submissions older than this are discarded (seconds)
+pub const PUSH_ROUND_DURATION: i64 = 60;
+
 /// Max collateral types per position
-pub const MAX_COLLATERAL_TYPES: usize = 8;
+pub const MAX_COLLATERAL_TYPES: usize = 16;
 
 /// Max borrow types per position
-pub const MAX_BORROW_TYPES: usize = 4;
+pub const MAX_BORROW_TYPES: usize = 8;
 
 // ========== TOKEN MINTS (Devnet) ==========
 
@@ -76,4 +76,100 @@ pub enum LegasiError {
 
     #[msg("Invalid slot provided")]
     InvalidSlot,
+
+    #[msg("Oracle account is not owned by a supported oracle program")]
+    InvalidOracleOwner,
+
+    #[msg("Oracle feed does not match the asset configuration")]
+    OracleFeedMismatch,
+
+    #[msg("Oracle price is not trading")]
+    OracleNotTrading,
+
+    #[msg("Oracle sources deviate beyond the allowed range")]
+    OracleDeviationExceeded,
+
+    #[msg("Oracle source is not configured for this price feed")]
+    OracleSourceNotConfigured,
+
+    #[msg("Price circuit breaker is tripped for this asset")]
+    CircuitBreakerTripped,
+
+    #[msg("Missing price feed for a borrowed asset")]
+    DebtPriceFeedMissing,
+
+    #[msg("Signer is not a publisher of this push oracle")]
+    UnknownPublisher,
+
+    #[msg("Publisher already submitted a price this round")]
+    DuplicatePriceSubmission,
+
+    #[msg("No admin transfer is pending for this signer")]
+    NoPendingAdmin,
+
+    #[msg("Invalid risk parameters")]
+    InvalidRiskParameters,
+
+    #[msg("Timelock has not elapsed")]
+    TimelockNotElapsed,
+
+    #[msg("Queued change has expired")]
+    TimelockExpired,
+
+    #[msg("Queued change is not pending")]
+    ChangeNotQueued,
+
+    #[msg("Change target does not match the provided account")]
+    ChangeTargetMismatch,
+
+    #[msg("Action is paused for this asset")]
+    AssetPaused,
+
+    #[msg("Mint has an unsupported Token-2022 extension")]
+    UnsupportedMintExtension,
+
+    #[msg("Decimals do not match the mint")]
+    MintDecimalsMismatch,
+
+    #[msg("Every registered asset must be provided exactly once")]
+    IncompleteAssetList,
+
+    #[msg("Account layout is unknown or newer than this program")]
+    UnknownAccountLayout,
+
+    #[msg("Isolated positions hold one collateral and one borrow asset")]
+    IsolatedMarginPair,
+
+    #[msg("Position still has collateral, debt, delegates or an agent config")]
+    PositionNotEmpty,
+
+    #[msg("Account is still active")]
+    AccountStillActive,
+
+    #[msg("Delegate has expired")]
+    DelegateExpired,
+
+    #[msg("Price history does not cover the TWAP window yet")]
+    InsufficientPriceHistory,
+
+    #[msg("Price update is not newer than the stored reading")]
+    OutdatedPriceUpdate,
+
+    #[msg("Change must be queued behind the timelock")]
+    RequiresTimelock,
+
+    #[msg("Delegate permissions are empty or unknown")]
+    InvalidDelegatePermissions,
+
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+
+    #[msg("Daily borrow limit exceeded")]
+    DailyLimitExceeded,
+
+    #[msg("Status cannot move from its current value to the requested one")]
+    InvalidStatusTransition,
+
+    #[msg("Swap sells more collateral than the GAD schedule allows")]
+    GadLimitExceeded,
 }
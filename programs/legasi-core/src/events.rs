@@ -1,4 +1,12 @@
-use crate::state::AssetType;
+//! Protocol events
+//!
+//! Emitted with `emit_cpi!` so indexers read them from inner instruction data
+//! instead of logs, which can be truncated. Position events carry the
+//! position's `seq` (`PositionCreated` is 0, then +1 per event) and the
+//! post-state balance of the affected mint, so positions can be rebuilt from
+//! events alone.
+
+use crate::state::{AssetKind, EModeCategory, MarginMode, ParameterChange};
 use anchor_lang::prelude::*;
 
 #[event]
@@ -10,14 +18,16 @@ pub struct ProtocolInitialized {
 #[event]
 pub struct CollateralRegistered {
     pub mint: Pubkey,
-    pub asset_type: AssetType,
+    pub kind: AssetKind,
+    pub emode_category: EModeCategory,
     pub max_ltv_bps: u16,
 }
 
 #[event]
 pub struct BorrowableRegistered {
     pub mint: Pubkey,
-    pub asset_type: AssetType,
+    pub kind: AssetKind,
+    pub emode_category: EModeCategory,
     pub interest_rate_bps: u16,
 }
 
@@ -25,46 +35,97 @@ pub struct BorrowableRegistered {
 pub struct PositionCreated {
     pub owner: Pubkey,
     pub position: Pubkey,
+    pub position_id: u8,
+    pub seq: u64,
+}
+
+#[event]
+pub struct PositionClosed {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub position_id: u8,
+    /// Lamports swept from the SOL vault to the owner
+    pub dust_swept: u64,
+    pub seq: u64,
+}
+
+#[event]
+pub struct MarginModeChanged {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub margin_mode: MarginMode,
+    pub seq: u64,
 }
 
 #[event]
 pub struct CollateralDeposited {
     pub position: Pubkey,
     pub owner: Pubkey,
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub amount: u64,
-    pub total_collateral_usd: u64,
+    /// Position's deposit of `mint` after the deposit
+    pub collateral_amount: u64,
+    pub seq: u64,
 }
 
 #[event]
 pub struct CollateralWithdrawn {
     pub position: Pubkey,
     pub owner: Pubkey,
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub amount: u64,
+    /// Position's deposit of `mint` after the withdrawal
+    pub collateral_amount: u64,
+    pub new_ltv_bps: u64,
+    pub seq: u64,
 }
 
 #[event]
 pub struct Borrowed {
     pub position: Pubkey,
     pub owner: Pubkey,
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub amount: u64,
-    pub new_ltv_bps: u64,
+    /// Position's debt in `mint` (principal + interest) after the borrow
+    pub debt_amount: u64,
+    /// `None` when the instruction doesn't value the whole position (x402 auto-borrow)
+    pub new_ltv_bps: Option<u64>,
+    pub seq: u64,
 }
 
 #[event]
 pub struct Repaid {
     pub position: Pubkey,
     pub owner: Pubkey,
-    pub asset_type: AssetType,
+    /// Whose tokens paid: the owner, or anyone repaying through `repay_for`
+    pub payer: Pubkey,
+    pub mint: Pubkey,
+    /// Debt cleared, including `interest_paid`
     pub amount: u64,
     pub interest_paid: u64,
+    /// Position's debt in `mint` after the repayment
+    pub debt_amount: u64,
+    pub seq: u64,
+}
+
+#[event]
+pub struct InterestAccrued {
+    pub position: Pubkey,
+    pub mint: Pubkey,
+    pub interest: u64,
+    /// Position's debt in `mint` after accrual
+    pub debt_amount: u64,
+    pub seq: u64,
 }
 
 #[event]
 pub struct GadExecuted {
     pub position: Pubkey,
+    pub collateral_mint: Pubkey,
+    /// Collateral removed from the position, cranker reward included
+    pub collateral_seized: u64,
+    /// Position's deposit of `collateral_mint` afterwards
+    pub collateral_amount: u64,
     pub collateral_liquidated_usd: u64,
     pub debt_reduced_usd: u64,
     pub ltv_before_bps: u64,
@@ -72,6 +133,7 @@ pub struct GadExecuted {
     pub gad_rate_bps: u64,
     pub cranker: Pubkey,
     pub cranker_reward: u64,
+    pub seq: u64,
 }
 
 #[event]
@@ -93,7 +155,7 @@ pub struct LpWithdrawn {
 #[event]
 pub struct FlashLoanInitiated {
     pub borrower: Pubkey,
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub amount: u64,
     pub fee: u64,
 }
@@ -101,7 +163,7 @@ pub struct FlashLoanInitiated {
 #[event]
 pub struct FlashLoanRepaid {
     pub borrower: Pubkey,
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub amount: u64,
     pub fee: u64,
 }
@@ -110,25 +172,34 @@ pub struct FlashLoanRepaid {
 pub struct LeverageOpened {
     pub position: Pubkey,
     pub owner: Pubkey,
-    pub collateral_type: AssetType,
-    pub borrow_type: AssetType,
+    pub collateral_mint: Pubkey,
+    pub borrow_mint: Pubkey,
     pub initial_collateral: u64,
     pub total_collateral: u64,
     pub total_borrowed: u64,
     pub leverage_multiplier: u8,
+    /// Position's deposit of `collateral_mint` afterwards
+    pub collateral_amount: u64,
+    /// Position's debt in `borrow_mint` afterwards
+    pub debt_amount: u64,
+    pub seq: u64,
 }
 
 #[event]
 pub struct LeverageClosed {
     pub position: Pubkey,
     pub owner: Pubkey,
+    pub borrow_mint: Pubkey,
+    /// Debt cleared in `borrow_mint` (principal + interest)
+    pub debt_repaid: u64,
     pub collateral_returned: u64,
     pub pnl_usd: i64,
+    pub seq: u64,
 }
 
 #[event]
 pub struct PriceUpdated {
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub price_usd_6dec: u64,
     pub timestamp: i64,
 }
@@ -139,8 +210,8 @@ pub struct PriceUpdated {
 pub struct MarketCreated {
     pub market_id: u16,
     pub name: String,
-    pub collateral_asset: AssetType,
-    pub borrow_asset: AssetType,
+    pub collateral_mint: Pubkey,
+    pub borrow_mint: Pubkey,
     pub base_max_ltv_bps: u16,
     pub emode_max_ltv_bps: u16,
 }
@@ -169,3 +240,57 @@ pub struct EModeSet {
     pub old_category: u8,
     pub new_category: u8,
 }
+
+#[event]
+pub struct ParameterChangeQueued {
+    pub id: u64,
+    pub target: Pubkey,
+    pub change: ParameterChange,
+    pub proposer: Pubkey,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ParameterChangeExecuted {
+    pub id: u64,
+    pub target: Pubkey,
+    pub change: ParameterChange,
+    pub executor: Pubkey,
+}
+
+#[event]
+pub struct ParameterChangeCancelled {
+    pub id: u64,
+    pub target: Pubkey,
+    pub cancelled_by: Pubkey,
+}
+
+#[event]
+pub struct CollateralUpdated {
+    pub mint: Pubkey,
+    pub kind: AssetKind,
+    pub emode_category: EModeCategory,
+    pub oracle: Pubkey,
+    pub max_ltv_bps: u16,
+    pub liquidation_threshold_bps: u16,
+    pub liquidation_bonus_bps: u16,
+    pub is_active: bool,
+    pub ltv_ramp_end_time: i64,
+}
+
+#[event]
+pub struct BorrowableUpdated {
+    pub mint: Pubkey,
+    pub kind: AssetKind,
+    pub emode_category: EModeCategory,
+    pub oracle: Pubkey,
+    pub interest_rate_bps: u16,
+    pub is_active: bool,
+}
+
+#[event]
+pub struct ProtocolStatsRefreshed {
+    pub total_collateral_usd: u64,
+    pub total_borrowed_usd: u64,
+    pub timestamp: i64,
+}
@@ -1,20 +1,35 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
 
 declare_id!("4FW9iFaerNuX1GstRKSsWo9UfnTbjtqch3fEHkWMF1Uy");
 
+pub mod accounting;
+pub mod config;
 pub mod constants;
 pub mod errors;
 pub mod events;
 pub mod interest;
+pub mod oracle;
+pub mod pause;
+pub mod push_oracle;
 pub mod pyth;
+pub mod roles;
 pub mod state;
+pub mod switchboard;
+pub mod timelock;
+pub mod tokens;
+pub mod valuation;
 
+pub use accounting::*;
+pub use config::*;
 pub use constants::*;
 pub use errors::*;
 pub use events::*;
 pub use interest::*;
 pub use pyth::*;
 pub use state::*;
+pub use switchboard::*;
+pub use valuation::*;
 
 #[program]
 pub mod legasi_core {
@@ -23,103 +38,353 @@ pub mod legasi_core {
     /// Initialize the protocol state
     pub fn initialize_protocol(ctx: Context<InitializeProtocol>, treasury: Pubkey) -> Result<()> {
         let protocol = &mut ctx.accounts.protocol;
-        protocol.admin = ctx.accounts.admin.key();
+        protocol.init_roles(ctx.accounts.admin.key());
         protocol.treasury = treasury;
+        protocol.timelock_delay = DEFAULT_TIMELOCK_DELAY;
+        protocol.next_change_id = 0;
         protocol.insurance_fund = 0;
         protocol.total_collateral_usd = 0;
         protocol.total_borrowed_usd = 0;
         protocol.paused = false;
+        protocol.pause_flags = 0;
+        protocol.collateral_count = 0;
+        protocol.borrowable_count = 0;
+        protocol.stats_updated_at = 0;
         protocol.bump = ctx.bumps.protocol;
 
+        emit_cpi!(ProtocolInitialized {
+            admin: protocol.admin,
+            treasury,
+        });
+
         msg!("Protocol initialized with admin: {}", protocol.admin);
         Ok(())
     }
 
-    /// Register a collateral asset (SOL, cbBTC)
+    /// Register a collateral asset, keyed by mint (SOL, cbBTC, JitoSOL, wETH)
+    /// Native SOL is registered under NATIVE_SOL_MINT with AssetKind::Native
     pub fn register_collateral(
         ctx: Context<RegisterCollateral>,
         oracle: Pubkey,
         max_ltv_bps: u16,
         liquidation_threshold_bps: u16,
         liquidation_bonus_bps: u16,
-        decimals: u8,
-        asset_type: AssetType,
+        metadata: AssetMetadata,
     ) -> Result<()> {
+        validate_asset_metadata(&ctx.accounts.mint.key(), &metadata, false)?;
+        require!(
+            metadata.decimals == ctx.accounts.mint.decimals,
+            LegasiError::MintDecimalsMismatch
+        );
+        tokens::validate_mint(&ctx.accounts.mint)?;
+        validate_collateral_risk(
+            max_ltv_bps,
+            liquidation_threshold_bps,
+            liquidation_bonus_bps,
+        )?;
         let collateral = &mut ctx.accounts.collateral;
         collateral.mint = ctx.accounts.mint.key();
         collateral.oracle = oracle;
         collateral.max_ltv_bps = max_ltv_bps;
         collateral.liquidation_threshold_bps = liquidation_threshold_bps;
         collateral.liquidation_bonus_bps = liquidation_bonus_bps;
-        collateral.decimals = decimals;
+        collateral.decimals = metadata.decimals;
         collateral.is_active = true;
         collateral.total_deposited = 0;
-        collateral.asset_type = asset_type;
+        collateral.kind = metadata.kind;
+        collateral.emode_category = metadata.emode_category;
+        collateral.pause_flags = 0;
+        collateral.ltv_ramp_start_bps = max_ltv_bps;
+        collateral.ltv_ramp_start_time = 0;
+        collateral.ltv_ramp_end_time = 0;
         collateral.bump = ctx.bumps.collateral;
 
-        msg!("Collateral registered: {:?}", asset_type);
+        let protocol = &mut ctx.accounts.protocol;
+        protocol.collateral_count = protocol
+            .collateral_count
+            .checked_add(1)
+            .ok_or(LegasiError::MathOverflow)?;
+
+        emit_cpi!(CollateralRegistered {
+            mint: collateral.mint,
+            kind: metadata.kind,
+            emode_category: metadata.emode_category,
+            max_ltv_bps,
+        });
         Ok(())
     }
 
-    /// Register a borrowable asset (USDC, USDT, EURC)
+    /// Register a borrowable asset, keyed by mint (USDC, USDT, EURC, PYUSD)
     pub fn register_borrowable(
         ctx: Context<RegisterBorrowable>,
         oracle: Pubkey,
         interest_rate_bps: u16,
-        decimals: u8,
-        asset_type: AssetType,
+        metadata: AssetMetadata,
     ) -> Result<()> {
+        validate_asset_metadata(&ctx.accounts.mint.key(), &metadata, true)?;
+        require!(
+            metadata.decimals == ctx.accounts.mint.decimals,
+            LegasiError::MintDecimalsMismatch
+        );
+        tokens::validate_mint(&ctx.accounts.mint)?;
+        validate_interest_rate(interest_rate_bps)?;
         let borrowable = &mut ctx.accounts.borrowable;
         borrowable.mint = ctx.accounts.mint.key();
         borrowable.oracle = oracle;
         borrowable.interest_rate_bps = interest_rate_bps;
-        borrowable.decimals = decimals;
+        borrowable.decimals = metadata.decimals;
         borrowable.is_active = true;
         borrowable.total_borrowed = 0;
         borrowable.total_available = 0;
-        borrowable.asset_type = asset_type;
+        borrowable.kind = metadata.kind;
+        borrowable.emode_category = metadata.emode_category;
+        borrowable.pause_flags = 0;
         borrowable.bump = ctx.bumps.borrowable;
 
-        msg!("Borrowable registered: {:?}", asset_type);
+        let protocol = &mut ctx.accounts.protocol;
+        protocol.borrowable_count = protocol
+            .borrowable_count
+            .checked_add(1)
+            .ok_or(LegasiError::MathOverflow)?;
+
+        emit_cpi!(BorrowableRegistered {
+            mint: borrowable.mint,
+            kind: metadata.kind,
+            emode_category: metadata.emode_category,
+            interest_rate_bps,
+        });
+        Ok(())
+    }
+
+    /// Lower a collateral's max LTV or deactivate it (risk admin only)
+    /// Max LTV decreases ramp in over LTV_RAMP_DURATION. Other risk, oracle
+    /// and e-mode changes go through queue_parameter_change.
+    pub fn update_collateral_config(
+        ctx: Context<UpdateCollateralConfig>,
+        update: CollateralConfigUpdate,
+    ) -> Result<()> {
+        let admin = ctx.accounts.admin.key();
+        let protocol = &ctx.accounts.protocol;
+        let risk_update = update.max_ltv_bps.is_some()
+            || update.liquidation_threshold_bps.is_some()
+            || update.liquidation_bonus_bps.is_some()
+            || update.emode_category.is_some()
+            || update.is_active.is_some();
+        require!(
+            !risk_update || protocol.has_role(ProtocolRole::Risk, &admin),
+            LegasiError::Unauthorized
+        );
+        require!(
+            update.oracle.is_none() || protocol.has_role(ProtocolRole::Oracle, &admin),
+            LegasiError::Unauthorized
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let collateral = &mut ctx.accounts.collateral;
+        collateral.apply_config_update(&update, now)?;
+
+        emit_cpi!(CollateralUpdated {
+            mint: collateral.mint,
+            kind: collateral.kind,
+            emode_category: collateral.emode_category,
+            oracle: collateral.oracle,
+            max_ltv_bps: collateral.max_ltv_bps,
+            liquidation_threshold_bps: collateral.liquidation_threshold_bps,
+            liquidation_bonus_bps: collateral.liquidation_bonus_bps,
+            is_active: collateral.is_active,
+            ltv_ramp_end_time: collateral.ltv_ramp_end_time,
+        });
+        Ok(())
+    }
+
+    /// Deactivate a borrowable (risk admin only)
+    /// Rate, oracle and e-mode changes go through queue_parameter_change
+    pub fn update_borrowable_config(
+        ctx: Context<UpdateBorrowableConfig>,
+        update: BorrowableConfigUpdate,
+    ) -> Result<()> {
+        let admin = ctx.accounts.admin.key();
+        let protocol = &ctx.accounts.protocol;
+        let risk_update = update.interest_rate_bps.is_some()
+            || update.emode_category.is_some()
+            || update.is_active.is_some();
+        require!(
+            !risk_update || protocol.has_role(ProtocolRole::Risk, &admin),
+            LegasiError::Unauthorized
+        );
+        require!(
+            update.oracle.is_none() || protocol.has_role(ProtocolRole::Oracle, &admin),
+            LegasiError::Unauthorized
+        );
+
+        let borrowable = &mut ctx.accounts.borrowable;
+        borrowable.apply_config_update(&update)?;
+
+        emit_cpi!(BorrowableUpdated {
+            mint: borrowable.mint,
+            kind: borrowable.kind,
+            emode_category: borrowable.emode_category,
+            oracle: borrowable.oracle,
+            interest_rate_bps: borrowable.interest_rate_bps,
+            is_active: borrowable.is_active,
+        });
         Ok(())
     }
 
     /// Initialize a price feed for a token (keyed by mint)
     pub fn initialize_price_feed(
         ctx: Context<InitializePriceFeed>,
-        asset_type: AssetType,
         initial_price_usd: u64,
     ) -> Result<()> {
         let price_feed = &mut ctx.accounts.price_feed;
-        price_feed.asset_type = asset_type;
-        price_feed.price_usd_6dec = initial_price_usd;
-        price_feed.last_update = Clock::get()?.unix_timestamp;
+        price_feed.mint = ctx.accounts.mint.key();
         price_feed.confidence = 0;
+        price_feed.observation_index = 0;
+        price_feed.observation_count = 0;
+        price_feed.observations = [PriceObservation::default(); PRICE_OBSERVATION_CAPACITY];
+        // Start with a single admin push source; more can be added with configure_price_sources
+        price_feed.configure_sources(
+            &[OracleSourceConfig {
+                kind: OracleSourceKind::Push,
+                address: Pubkey::default(),
+            }],
+            DEFAULT_MAX_ORACLE_DEVIATION_BPS,
+            1,
+        )?;
+        price_feed
+            .configure_circuit_breaker(DEFAULT_MAX_PRICE_MOVE_BPS, DEFAULT_PRICE_MOVE_INTERVAL)?;
+        price_feed.confidence_multiplier_bps = DEFAULT_CONFIDENCE_MULTIPLIER_BPS;
+        price_feed.reference_price_usd_6dec = 0;
+        price_feed.circuit_breaker_tripped = false;
+        price_feed.tripped_at = 0;
+        let now = Clock::get()?.unix_timestamp;
+        price_feed.set_source_reading(
+            OracleSourceKind::Push,
+            &Pubkey::default(),
+            initial_price_usd,
+            0,
+            now,
+        )?;
+        price_feed.record_price(initial_price_usd, now);
         price_feed.bump = ctx.bumps.price_feed;
+        let updated = PriceUpdated {
+            mint: price_feed.mint,
+            price_usd_6dec: price_feed.price_usd_6dec,
+            timestamp: price_feed.last_update,
+        };
 
         msg!(
-            "Price feed initialized: {:?} = ${}",
-            asset_type,
+            "Price feed initialized: {} = ${}",
+            price_feed.mint,
             initial_price_usd as f64 / 1_000_000.0
         );
+        emit_cpi!(updated);
+        Ok(())
+    }
+
+    /// Configure the oracle sources aggregated by a price feed (oracle admin only)
+    /// Order doesn't matter: any `min_sources` fresh, agreeing sources
+    /// publish their median, so the others act as fallbacks
+    pub fn configure_price_sources(
+        ctx: Context<UpdatePrice>,
+        sources: Vec<OracleSourceConfig>,
+        max_deviation_bps: u16,
+        min_sources: u8,
+    ) -> Result<()> {
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.configure_sources(&sources, max_deviation_bps, min_sources)?;
+
+        msg!(
+            "Price feed sources configured: {} sources, min {}, max deviation {} bps",
+            sources.len(),
+            min_sources,
+            max_deviation_bps
+        );
         Ok(())
     }
 
-    /// Update price (admin only - for testing/fallback)
+    /// Configure the price circuit breaker (oracle admin only)
+    /// A move above `max_move_bps` within `interval_seconds` blocks borrows and withdrawals
+    pub fn configure_circuit_breaker(
+        ctx: Context<UpdatePrice>,
+        max_move_bps: u16,
+        interval_seconds: i64,
+    ) -> Result<()> {
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.configure_circuit_breaker(max_move_bps, interval_seconds)?;
+
+        msg!(
+            "Circuit breaker configured: {} bps per {}s",
+            max_move_bps,
+            interval_seconds
+        );
+        Ok(())
+    }
+
+    /// Set the confidence multiplier k used for conservative valuations (oracle admin only)
+    /// Collateral is valued at price - k*conf and debt at price + k*conf
+    pub fn configure_confidence_multiplier(
+        ctx: Context<UpdatePrice>,
+        multiplier_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts
+            .price_feed
+            .configure_confidence_multiplier(multiplier_bps)?;
+
+        msg!("Confidence multiplier set to {} bps", multiplier_bps);
+        Ok(())
+    }
+
+    /// Clear a tripped circuit breaker after review (oracle admin only)
+    pub fn clear_circuit_breaker(ctx: Context<UpdatePrice>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.price_feed.clear_circuit_breaker(now);
+
+        msg!("Circuit breaker cleared");
+        Ok(())
+    }
+
+    /// Push a price from the admin source (oracle admin only - for testing/fallback)
+    /// The feed only moves if the push agrees with the other sources. Once a
+    /// push oracle is initialized for the mint it owns the push source, and
+    /// admin pushes are rejected unless an admin source is configured again
     pub fn update_price(ctx: Context<UpdatePrice>, price_usd: u64) -> Result<()> {
+        require!(price_usd > 0, LegasiError::InvalidAmount);
+        let now = Clock::get()?.unix_timestamp;
+
         let price_feed = &mut ctx.accounts.price_feed;
-        price_feed.price_usd_6dec = price_usd;
-        price_feed.last_update = Clock::get()?.unix_timestamp;
+        price_feed.set_source_reading(
+            OracleSourceKind::Push,
+            &Pubkey::default(),
+            price_usd,
+            0,
+            now,
+        )?;
+        price_feed.refresh_from_sources(now)?;
+        let updated = PriceUpdated {
+            mint: price_feed.mint,
+            price_usd_6dec: price_feed.price_usd_6dec,
+            timestamp: price_feed.last_update,
+        };
 
-        msg!("Price updated to ${}", price_usd as f64 / 1_000_000.0);
+        msg!(
+            "Price updated to ${}",
+            price_feed.price_usd_6dec as f64 / 1_000_000.0
+        );
+        emit_cpi!(updated);
         Ok(())
     }
 
     /// Sync price from Pyth oracle (permissionless)
+    /// The Pyth account must match the oracle configured on the asset
     pub fn sync_pyth_price(ctx: Context<SyncPythPrice>) -> Result<()> {
-        let pyth_data = ctx.accounts.pyth_price_account.try_borrow_data()?;
+        let expected_oracle = match (&ctx.accounts.collateral, &ctx.accounts.borrowable) {
+            (Some(collateral), _) => collateral.oracle,
+            (None, Some(borrowable)) => borrowable.oracle,
+            (None, None) => return err!(LegasiError::AssetNotSupported),
+        };
 
-        let pyth_price = parse_pyth_price(&pyth_data).ok_or(LegasiError::InvalidOracle)?;
+        let pyth_price = load_pyth_price(&ctx.accounts.pyth_price_account, &expected_oracle)?;
 
         let now = Clock::get()?.unix_timestamp;
 
@@ -135,29 +400,414 @@ pub mod legasi_core {
             LegasiError::InvalidOracle
         );
 
-        // Update our price feed
+        let price_usd = pyth_price.to_usd_6dec().ok_or(LegasiError::InvalidOracle)?;
+        let confidence = pyth_price
+            .conf_to_usd_6dec()
+            .ok_or(LegasiError::InvalidOracle)?;
+
+        // Update the Pyth source and re-aggregate
         let price_feed = &mut ctx.accounts.price_feed;
-        price_feed.price_usd_6dec = pyth_price.to_usd_6dec();
-        price_feed.confidence = pyth_price.conf;
-        price_feed.last_update = now;
+        price_feed.set_source_reading(
+            OracleSourceKind::Pyth,
+            &expected_oracle,
+            price_usd,
+            confidence,
+            pyth_price.publish_time,
+        )?;
+        price_feed.refresh_from_sources(now)?;
+        let updated = PriceUpdated {
+            mint: price_feed.mint,
+            price_usd_6dec: price_feed.price_usd_6dec,
+            timestamp: price_feed.last_update,
+        };
 
         msg!(
             "Synced Pyth price: ${}",
             price_feed.price_usd_6dec as f64 / 1_000_000.0
         );
+        emit_cpi!(updated);
         Ok(())
     }
 
-    /// Pause/unpause protocol (admin only)
-    pub fn set_paused(ctx: Context<AdminOnly>, paused: bool) -> Result<()> {
-        ctx.accounts.protocol.paused = paused;
+    /// Sync price from a Switchboard On-Demand pull feed (permissionless)
+    /// The feed account must be configured as a source of the price feed
+    pub fn sync_switchboard_price(ctx: Context<SyncSwitchboardPrice>) -> Result<()> {
+        let switchboard_price = load_switchboard_price(&ctx.accounts.switchboard_feed)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now.saturating_sub(switchboard_price.publish_time) <= MAX_PRICE_AGE,
+            LegasiError::StalePriceFeed
+        );
+
+        let price_usd = switchboard_price
+            .to_usd_6dec()
+            .ok_or(LegasiError::InvalidOracle)?;
+        let confidence = switchboard_price
+            .std_dev_to_usd_6dec()
+            .ok_or(LegasiError::InvalidOracle)?;
+        require!(
+            (confidence as u128) * (BPS_DENOMINATOR as u128)
+                <= (price_usd as u128) * (MAX_CONFIDENCE_BPS as u128),
+            LegasiError::InvalidOracle
+        );
+
+        let feed_key = ctx.accounts.switchboard_feed.key();
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.set_source_reading(
+            OracleSourceKind::Switchboard,
+            &feed_key,
+            price_usd,
+            confidence,
+            switchboard_price.publish_time,
+        )?;
+        price_feed.refresh_from_sources(now)?;
+        let updated = PriceUpdated {
+            mint: price_feed.mint,
+            price_usd_6dec: price_feed.price_usd_6dec,
+            timestamp: price_feed.last_update,
+        };
+
+        msg!(
+            "Synced Switchboard price: ${}",
+            price_feed.price_usd_6dec as f64 / 1_000_000.0
+        );
+        emit_cpi!(updated);
+        Ok(())
+    }
+
+    /// Create a push oracle for a price feed (oracle admin only)
+    /// Its published medians feed a push source keyed by the PushOracle
+    /// address, which replaces the admin push source if the feed has one
+    pub fn initialize_push_oracle(
+        ctx: Context<InitializePushOracle>,
+        publishers: Vec<Pubkey>,
+        quorum: u8,
+    ) -> Result<()> {
+        let push_oracle_key = ctx.accounts.push_oracle.key();
+        ctx.accounts.price_feed.assign_push_source(&push_oracle_key);
+
+        let push_oracle = &mut ctx.accounts.push_oracle;
+        push_oracle.price_feed = ctx.accounts.price_feed.key();
+        push_oracle.round = 0;
+        push_oracle.configure(&publishers, quorum)?;
+        push_oracle.bump = ctx.bumps.push_oracle;
+
+        msg!(
+            "Push oracle initialized: {} publishers, quorum {}",
+            publishers.len(),
+            quorum
+        );
+        Ok(())
+    }
+
+    /// Replace the publisher set and quorum of a push oracle (oracle admin only)
+    pub fn update_push_publishers(
+        ctx: Context<UpdatePushPublishers>,
+        publishers: Vec<Pubkey>,
+        quorum: u8,
+    ) -> Result<()> {
+        ctx.accounts.push_oracle.configure(&publishers, quorum)?;
+
+        msg!(
+            "Push publishers updated: {} publishers, quorum {}",
+            publishers.len(),
+            quorum
+        );
+        Ok(())
+    }
+
+    /// Submit a price for the current round (publishers only)
+    /// The feed updates to the round median once quorum is reached
+    pub fn submit_push_price(ctx: Context<SubmitPushPrice>, price_usd: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let publisher = ctx.accounts.publisher.key();
+        let round = ctx.accounts.push_oracle.round;
+
+        let Some((price_usd, confidence)) = ctx
+            .accounts
+            .push_oracle
+            .submit(&publisher, price_usd, now)?
+        else {
+            msg!("Push price submitted for round {}", round);
+            return Ok(());
+        };
+
+        let push_oracle_key = ctx.accounts.push_oracle.key();
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.set_source_reading(
+            OracleSourceKind::Push,
+            &push_oracle_key,
+            price_usd,
+            confidence,
+            now,
+        )?;
+        price_feed.refresh_from_sources(now)?;
+        let updated = PriceUpdated {
+            mint: price_feed.mint,
+            price_usd_6dec: price_feed.price_usd_6dec,
+            timestamp: price_feed.last_update,
+        };
+
+        msg!(
+            "Push round {} published: ${}",
+            round,
+            price_feed.price_usd_6dec as f64 / 1_000_000.0
+        );
+        emit_cpi!(updated);
+        Ok(())
+    }
+
+    /// Pause/unpause protocol (pause guardian only)
+    /// Pausing is an emergency pause: repay and collateral deposits stay available
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let flags = if paused { PAUSE_EMERGENCY } else { 0 };
+        ctx.accounts.protocol.set_pause_flags(flags)?;
         msg!("Protocol paused: {}", paused);
         Ok(())
     }
+
+    /// Pause individual actions protocol-wide (pause guardian only)
+    pub fn set_pause_flags(ctx: Context<SetPaused>, flags: u16) -> Result<()> {
+        ctx.accounts.protocol.set_pause_flags(flags)?;
+        msg!("Protocol pause flags: {:#06x}", flags);
+        Ok(())
+    }
+
+    /// Pause individual actions for one collateral (pause guardian only)
+    pub fn set_collateral_pause_flags(ctx: Context<SetCollateralPause>, flags: u16) -> Result<()> {
+        ctx.accounts.collateral.set_pause_flags(flags)?;
+        msg!(
+            "Collateral {} pause flags: {:#06x}",
+            ctx.accounts.collateral.mint,
+            flags
+        );
+        Ok(())
+    }
+
+    /// Pause individual actions for one borrowable (pause guardian only)
+    pub fn set_borrowable_pause_flags(ctx: Context<SetBorrowablePause>, flags: u16) -> Result<()> {
+        ctx.accounts.borrowable.set_pause_flags(flags)?;
+        msg!(
+            "Borrowable {} pause flags: {:#06x}",
+            ctx.accounts.borrowable.mint,
+            flags
+        );
+        Ok(())
+    }
+
+    /// Assign a protocol role (super admin only)
+    pub fn set_role(ctx: Context<AdminOnly>, role: ProtocolRole, holder: Pubkey) -> Result<()> {
+        ctx.accounts.protocol.set_role(role, holder)?;
+        msg!("Role {:?} assigned to {}", role, holder);
+        Ok(())
+    }
+
+    /// Propose a new super admin (super admin only)
+    /// Takes effect once the proposed key calls accept_admin
+    pub fn propose_admin(ctx: Context<AdminOnly>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.protocol.pending_admin = new_admin;
+        msg!("Admin transfer proposed to {}", new_admin);
+        Ok(())
+    }
+
+    /// Accept a pending super admin transfer (proposed admin only)
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let new_admin = ctx.accounts.new_admin.key();
+        ctx.accounts.protocol.accept_admin(&new_admin)?;
+        msg!("Admin transferred to {}", new_admin);
+        Ok(())
+    }
+
+    /// Queue a parameter change behind the timelock
+    /// Risk changes need the risk admin, oracle changes the oracle admin,
+    /// timelock delay changes the super admin
+    pub fn queue_parameter_change(
+        ctx: Context<QueueParameterChange>,
+        target: Pubkey,
+        change: ParameterChange,
+    ) -> Result<()> {
+        change.validate()?;
+        let proposer = ctx.accounts.proposer.key();
+        let protocol = &mut ctx.accounts.protocol;
+        require!(
+            protocol.can_queue_change(&change, &proposer),
+            LegasiError::Unauthorized
+        );
+        if matches!(change, ParameterChange::TimelockDelay { .. }) {
+            require_keys_eq!(target, protocol.key(), LegasiError::ChangeTargetMismatch);
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let queued_change = &mut ctx.accounts.queued_change;
+        queued_change.id = protocol.next_change_id;
+        queued_change.target = target;
+        queued_change.change = change;
+        queued_change.proposer = proposer;
+        queued_change.queued_at = now;
+        queued_change.eta = now
+            .checked_add(protocol.timelock_delay)
+            .ok_or(LegasiError::MathOverflow)?;
+        queued_change.status = ChangeStatus::Queued;
+        queued_change.bump = ctx.bumps.queued_change;
+        protocol.next_change_id = protocol
+            .next_change_id
+            .checked_add(1)
+            .ok_or(LegasiError::MathOverflow)?;
+
+        emit_cpi!(ParameterChangeQueued {
+            id: queued_change.id,
+            target,
+            change,
+            proposer,
+            eta: queued_change.eta,
+        });
+        Ok(())
+    }
+
+    /// Execute a queued change once its timelock has elapsed (permissionless)
+    /// Pass the target `collateral` or `borrowable` account
+    pub fn execute_parameter_change(ctx: Context<ExecuteParameterChange>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let queued_change = &mut ctx.accounts.queued_change;
+        queued_change.check_executable(now)?;
+        let change = queued_change.change;
+        let target = queued_change.target;
+
+        match change {
+            ParameterChange::TimelockDelay { delay_seconds } => {
+                let protocol = &mut ctx.accounts.protocol;
+                require_keys_eq!(target, protocol.key(), LegasiError::ChangeTargetMismatch);
+                protocol.timelock_delay = delay_seconds;
+            }
+            ParameterChange::CollateralRisk { .. }
+            | ParameterChange::CollateralOracle { .. }
+            | ParameterChange::CollateralStatus { .. } => {
+                let collateral = ctx
+                    .accounts
+                    .collateral
+                    .as_mut()
+                    .ok_or(LegasiError::ChangeTargetMismatch)?;
+                require_keys_eq!(target, collateral.key(), LegasiError::ChangeTargetMismatch);
+                collateral.apply_change(&change, now)?;
+            }
+            ParameterChange::BorrowableInterestRate { .. }
+            | ParameterChange::BorrowableOracle { .. }
+            | ParameterChange::BorrowableStatus { .. } => {
+                let borrowable = ctx
+                    .accounts
+                    .borrowable
+                    .as_mut()
+                    .ok_or(LegasiError::ChangeTargetMismatch)?;
+                require_keys_eq!(target, borrowable.key(), LegasiError::ChangeTargetMismatch);
+                borrowable.apply_change(&change)?;
+            }
+        }
+        queued_change.status = ChangeStatus::Executed;
+
+        emit_cpi!(ParameterChangeExecuted {
+            id: queued_change.id,
+            target,
+            change,
+            executor: ctx.accounts.executor.key(),
+        });
+        Ok(())
+    }
+
+    /// Cancel a queued change (queuing role or super admin)
+    pub fn cancel_parameter_change(ctx: Context<CancelParameterChange>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let queued_change = &mut ctx.accounts.queued_change;
+        require!(
+            queued_change.status == ChangeStatus::Queued,
+            LegasiError::ChangeNotQueued
+        );
+        require!(
+            ctx.accounts
+                .protocol
+                .can_cancel_change(&queued_change.change, &authority),
+            LegasiError::Unauthorized
+        );
+        queued_change.status = ChangeStatus::Cancelled;
+
+        emit_cpi!(ParameterChangeCancelled {
+            id: queued_change.id,
+            target: queued_change.target,
+            cancelled_by: authority,
+        });
+        Ok(())
+    }
+
+    /// Set the treasury account (treasury manager only)
+    pub fn set_treasury(ctx: Context<SetTreasury>, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.protocol.treasury = treasury;
+        msg!("Treasury set to {}", treasury);
+        Ok(())
+    }
+
+    /// Record a change to a collateral's deposits
+    /// CPI only, signed by the source program's accounting PDA
+    pub fn record_collateral_change(
+        ctx: Context<RecordCollateralChange>,
+        source: AccountingSource,
+        change: CollateralChange,
+    ) -> Result<()> {
+        source.require_authority(&ctx.accounts.authority.key())?;
+        ctx.accounts.collateral.record_change(change)?;
+        Ok(())
+    }
+
+    /// Record a change to a borrowable's liquidity or debt
+    /// CPI only, signed by the source program's accounting PDA
+    pub fn record_borrowable_change(
+        ctx: Context<RecordBorrowableChange>,
+        source: AccountingSource,
+        change: BorrowableChange,
+    ) -> Result<()> {
+        source.require_authority(&ctx.accounts.authority.key())?;
+        ctx.accounts.borrowable.record_change(change)?;
+        Ok(())
+    }
+
+    /// Credit the insurance fund with its share of interest or fees
+    /// CPI only, signed by the source program's accounting PDA
+    pub fn record_insurance_fee(
+        ctx: Context<RecordInsuranceFee>,
+        source: AccountingSource,
+        amount: u64,
+    ) -> Result<()> {
+        source.require_authority(&ctx.accounts.authority.key())?;
+        let protocol = &mut ctx.accounts.protocol;
+        protocol.insurance_fund = protocol
+            .insurance_fund
+            .checked_add(amount)
+            .ok_or(LegasiError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Recompute protocol USD totals from price feeds (permissionless)
+    /// remaining_accounts: (Collateral or Borrowable, PriceFeed) pair for every registered asset
+    pub fn refresh_protocol_stats(ctx: Context<RefreshProtocolStats>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let stats = load_protocol_stats(ctx.remaining_accounts, &ctx.accounts.protocol, now)?;
+
+        let protocol = &mut ctx.accounts.protocol;
+        protocol.total_collateral_usd = stats.total_collateral_usd;
+        protocol.total_borrowed_usd = stats.total_borrowed_usd;
+        protocol.stats_updated_at = now;
+
+        emit_cpi!(ProtocolStatsRefreshed {
+            total_collateral_usd: stats.total_collateral_usd,
+            total_borrowed_usd: stats.total_borrowed_usd,
+            timestamp: now,
+        });
+        Ok(())
+    }
 }
 
 // ========== ACCOUNTS ==========
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct InitializeProtocol<'info> {
     #[account(
@@ -173,9 +823,15 @@ pub struct InitializeProtocol<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct RegisterCollateral<'info> {
-    #[account(seeds = [b"protocol"], bump = protocol.bump, has_one = admin)]
+    #[account(
+        mut,
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        constraint = protocol.has_role(ProtocolRole::Risk, &admin.key()) @ LegasiError::Unauthorized
+    )]
     pub protocol: Account<'info, Protocol>,
     #[account(
         init,
@@ -185,16 +841,22 @@ pub struct RegisterCollateral<'info> {
         bump
     )]
     pub collateral: Account<'info, Collateral>,
-    /// CHECK: Token mint
-    pub mint: UncheckedAccount<'info>,
+    /// SPL Token or Token-2022 mint
+    pub mint: InterfaceAccount<'info, Mint>,
     #[account(mut)]
     pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct RegisterBorrowable<'info> {
-    #[account(seeds = [b"protocol"], bump = protocol.bump, has_one = admin)]
+    #[account(
+        mut,
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        constraint = protocol.has_role(ProtocolRole::Risk, &admin.key()) @ LegasiError::Unauthorized
+    )]
     pub protocol: Account<'info, Protocol>,
     #[account(
         init,
@@ -204,16 +866,43 @@ pub struct RegisterBorrowable<'info> {
         bump
     )]
     pub borrowable: Account<'info, Borrowable>,
-    /// CHECK: Token mint
-    pub mint: UncheckedAccount<'info>,
+    /// SPL Token or Token-2022 mint
+    pub mint: InterfaceAccount<'info, Mint>,
     #[account(mut)]
     pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateCollateralConfig<'info> {
+    #[account(seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+    #[account(mut, seeds = [b"collateral", collateral.mint.as_ref()], bump = collateral.bump)]
+    pub collateral: Account<'info, Collateral>,
+    /// Risk and/or oracle admin depending on the fields updated
+    pub admin: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateBorrowableConfig<'info> {
+    #[account(seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+    #[account(mut, seeds = [b"borrowable", borrowable.mint.as_ref()], bump = borrowable.bump)]
+    pub borrowable: Account<'info, Borrowable>,
+    /// Risk and/or oracle admin depending on the fields updated
+    pub admin: Signer<'info>,
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct InitializePriceFeed<'info> {
-    #[account(seeds = [b"protocol"], bump = protocol.bump, has_one = admin)]
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        constraint = protocol.has_role(ProtocolRole::Oracle, &admin.key()) @ LegasiError::Unauthorized
+    )]
     pub protocol: Account<'info, Protocol>,
     #[account(
         init,
@@ -230,9 +919,14 @@ pub struct InitializePriceFeed<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct UpdatePrice<'info> {
-    #[account(seeds = [b"protocol"], bump = protocol.bump, has_one = admin)]
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        constraint = protocol.has_role(ProtocolRole::Oracle, &admin.key()) @ LegasiError::Unauthorized
+    )]
     pub protocol: Account<'info, Protocol>,
     #[account(
         mut,
@@ -252,7 +946,131 @@ pub struct AdminOnly<'info> {
     pub admin: Signer<'info>,
 }
 
+#[event_cpi]
+#[derive(Accounts)]
+pub struct QueueParameterChange<'info> {
+    #[account(mut, seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + QueuedChange::INIT_SPACE,
+        seeds = [b"timelock", protocol.next_change_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub queued_change: Account<'info, QueuedChange>,
+    /// Role holder for the change (checked in the instruction)
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ExecuteParameterChange<'info> {
+    #[account(mut, seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+    #[account(
+        mut,
+        seeds = [b"timelock", queued_change.id.to_le_bytes().as_ref()],
+        bump = queued_change.bump
+    )]
+    pub queued_change: Account<'info, QueuedChange>,
+    #[account(mut, seeds = [b"collateral", collateral.mint.as_ref()], bump = collateral.bump)]
+    pub collateral: Option<Account<'info, Collateral>>,
+    #[account(mut, seeds = [b"borrowable", borrowable.mint.as_ref()], bump = borrowable.bump)]
+    pub borrowable: Option<Account<'info, Borrowable>>,
+    pub executor: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelParameterChange<'info> {
+    #[account(seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+    #[account(
+        mut,
+        seeds = [b"timelock", queued_change.id.to_le_bytes().as_ref()],
+        bump = queued_change.bump
+    )]
+    pub queued_change: Account<'info, QueuedChange>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(mut, seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+    /// Must match `protocol.pending_admin`
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(mut, seeds = [b"protocol"], bump = protocol.bump, has_one = pause_guardian)]
+    pub protocol: Account<'info, Protocol>,
+    pub pause_guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollateralPause<'info> {
+    #[account(seeds = [b"protocol"], bump = protocol.bump, has_one = pause_guardian)]
+    pub protocol: Account<'info, Protocol>,
+    #[account(mut, seeds = [b"collateral", collateral.mint.as_ref()], bump = collateral.bump)]
+    pub collateral: Account<'info, Collateral>,
+    pub pause_guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowablePause<'info> {
+    #[account(seeds = [b"protocol"], bump = protocol.bump, has_one = pause_guardian)]
+    pub protocol: Account<'info, Protocol>,
+    #[account(mut, seeds = [b"borrowable", borrowable.mint.as_ref()], bump = borrowable.bump)]
+    pub borrowable: Account<'info, Borrowable>,
+    pub pause_guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    #[account(mut, seeds = [b"protocol"], bump = protocol.bump, has_one = treasury_manager)]
+    pub protocol: Account<'info, Protocol>,
+    pub treasury_manager: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordCollateralChange<'info> {
+    /// Accounting PDA of the calling program
+    pub authority: Signer<'info>,
+    #[account(mut, seeds = [b"collateral", collateral.mint.as_ref()], bump = collateral.bump)]
+    pub collateral: Account<'info, Collateral>,
+}
+
+#[derive(Accounts)]
+pub struct RecordBorrowableChange<'info> {
+    /// Accounting PDA of the calling program
+    pub authority: Signer<'info>,
+    #[account(mut, seeds = [b"borrowable", borrowable.mint.as_ref()], bump = borrowable.bump)]
+    pub borrowable: Account<'info, Borrowable>,
+}
+
+#[derive(Accounts)]
+pub struct RecordInsuranceFee<'info> {
+    /// Accounting PDA of the calling program
+    pub authority: Signer<'info>,
+    #[account(mut, seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+}
+
+/// Refresh protocol USD totals (permissionless)
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RefreshProtocolStats<'info> {
+    #[account(mut, seeds = [b"protocol"], bump = protocol.bump)]
+    pub protocol: Account<'info, Protocol>,
+}
+
 /// Sync price from Pyth oracle (permissionless - anyone can update)
+#[event_cpi]
 #[derive(Accounts)]
 pub struct SyncPythPrice<'info> {
     #[account(
@@ -263,6 +1081,96 @@ pub struct SyncPythPrice<'info> {
     pub price_feed: Account<'info, PriceFeed>,
     /// CHECK: Token mint for this price feed
     pub mint: UncheckedAccount<'info>,
-    /// CHECK: Pyth price account - verified by parsing
+    /// Collateral config for the mint (provide this or `borrowable`)
+    #[account(seeds = [b"collateral", mint.key().as_ref()], bump = collateral.bump)]
+    pub collateral: Option<Account<'info, Collateral>>,
+    /// Borrowable config for the mint
+    #[account(seeds = [b"borrowable", mint.key().as_ref()], bump = borrowable.bump)]
+    pub borrowable: Option<Account<'info, Borrowable>>,
+    /// CHECK: Pyth price account - owner, layout and feed verified in load_pyth_price
     pub pyth_price_account: UncheckedAccount<'info>,
 }
+
+/// Create a push oracle (oracle admin only)
+#[derive(Accounts)]
+pub struct InitializePushOracle<'info> {
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        constraint = protocol.has_role(ProtocolRole::Oracle, &admin.key()) @ LegasiError::Unauthorized
+    )]
+    pub protocol: Account<'info, Protocol>,
+    #[account(mut, seeds = [b"price", mint.key().as_ref()], bump = price_feed.bump)]
+    pub price_feed: Account<'info, PriceFeed>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PushOracle::INIT_SPACE,
+        seeds = [b"push_oracle", mint.key().as_ref()],
+        bump
+    )]
+    pub push_oracle: Account<'info, PushOracle>,
+    /// CHECK: Token mint for this price feed
+    pub mint: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePushPublishers<'info> {
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        constraint = protocol.has_role(ProtocolRole::Oracle, &admin.key()) @ LegasiError::Unauthorized
+    )]
+    pub protocol: Account<'info, Protocol>,
+    #[account(
+        mut,
+        seeds = [b"push_oracle", mint.key().as_ref()],
+        bump = push_oracle.bump
+    )]
+    pub push_oracle: Account<'info, PushOracle>,
+    /// CHECK: Token mint for this price feed
+    pub mint: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SubmitPushPrice<'info> {
+    #[account(
+        mut,
+        seeds = [b"push_oracle", mint.key().as_ref()],
+        bump = push_oracle.bump,
+        has_one = price_feed
+    )]
+    pub push_oracle: Account<'info, PushOracle>,
+    #[account(
+        mut,
+        seeds = [b"price", mint.key().as_ref()],
+        bump = price_feed.bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+    /// CHECK: Token mint for this price feed
+    pub mint: UncheckedAccount<'info>,
+    /// Publisher - must be in the push oracle's publisher set
+    pub publisher: Signer<'info>,
+}
+
+/// Sync price from Switchboard (permissionless - anyone can update)
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SyncSwitchboardPrice<'info> {
+    #[account(
+        mut,
+        seeds = [b"price", mint.key().as_ref()],
+        bump = price_feed.bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+    /// CHECK: Token mint for this price feed
+    pub mint: UncheckedAccount<'info>,
+    /// CHECK: Switchboard pull feed - owner and layout verified in load_switchboard_price,
+    /// address must be a configured source of the price feed
+    pub switchboard_feed: UncheckedAccount<'info>,
+}
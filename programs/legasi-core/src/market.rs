@@ -30,32 +30,7 @@
 //! - Each market has independent liquidation parameters
 
 use anchor_lang::prelude::*;
-use crate::state::AssetType;
-
-// ========== EMODE CATEGORIES ==========
-
-/// Efficiency Mode categories for correlated assets
-/// Higher LTV allowed when collateral and borrow are in same category
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
-#[repr(u8)]
-pub enum EModeCategory {
-    /// No eMode (standard parameters)
-    None = 0,
-    /// Stablecoins (USDC, USDT, EURC) - highly correlated
-    Stablecoins = 1,
-    /// ETH ecosystem (ETH, stETH, wstETH, cbETH)
-    EthCorrelated = 2,
-    /// SOL ecosystem (SOL)
-    SolCorrelated = 3,
-    /// BTC ecosystem (BTC, wBTC, cbBTC)
-    BtcCorrelated = 4,
-}
-
-impl Default for EModeCategory {
-    fn default() -> Self {
-        EModeCategory::None
-    }
-}
+use crate::state::EModeCategory;
 
 // ========== MARKET CONFIG ==========
 
@@ -73,13 +48,9 @@ pub struct Market {
     
     // === Asset Configuration ===
     
-    /// Collateral asset type
-    pub collateral_asset: AssetType,
     /// Collateral token mint
     pub collateral_mint: Pubkey,
     
-    /// Borrowable asset type
-    pub borrow_asset: AssetType,
     /// Borrowable token mint
     pub borrow_mint: Pubkey,
     
@@ -0,0 +1,897 @@
+//! Price feed history and derived prices
+//!
+//! Spot updates are sampled into a ring buffer on the `PriceFeed`, at most
+//! one observation per `PRICE_OBSERVATION_INTERVAL`, so the buffer always
+//! spans the TWAP window and a burst of updates cannot flush it. From it we
+//! derive a time-weighted average price (TWAP) and keep an exponential moving
+//! average (EMA), so a single bad update cannot move risk decisions on its own.
+//!
+//! A feed can also aggregate several oracle sources (Pyth, Switchboard,
+//! admin/keeper push). The published price is the median of fresh sources
+//! that agree with each other; see `aggregate_sources`.
+
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::LegasiError;
+use crate::state::{
+    OracleSource, OracleSourceConfig, OracleSourceKind, PriceFeed, PriceObservation,
+};
+
+impl PriceFeed {
+    /// Record a new spot price: updates spot, EMA and the observation buffer
+    pub fn record_price(&mut self, price_usd_6dec: u64, timestamp: i64) {
+        self.ema_price_usd_6dec = if self.observation_count == 0 || self.ema_price_usd_6dec == 0 {
+            price_usd_6dec
+        } else {
+            let elapsed = timestamp
+                .saturating_sub(self.last_update)
+                .clamp(0, EMA_PERIOD_SECONDS);
+            let ema = self.ema_price_usd_6dec as i128;
+            let delta =
+                (price_usd_6dec as i128 - ema) * elapsed as i128 / EMA_PERIOD_SECONDS as i128;
+            (ema + delta) as u64
+        };
+
+        let latest_index = (self.observation_index as usize + PRICE_OBSERVATION_CAPACITY - 1)
+            % PRICE_OBSERVATION_CAPACITY;
+        let latest = &mut self.observations[latest_index];
+        if self.observation_count > 0
+            && timestamp.saturating_sub(latest.timestamp) < PRICE_OBSERVATION_INTERVAL
+        {
+            // Too close to the latest observation: it takes the new price
+            latest.price_usd_6dec = price_usd_6dec;
+        } else {
+            let index = self.observation_index as usize % PRICE_OBSERVATION_CAPACITY;
+            self.observations[index] = PriceObservation {
+                price_usd_6dec,
+                timestamp,
+            };
+            self.observation_index = ((index + 1) % PRICE_OBSERVATION_CAPACITY) as u8;
+            if (self.observation_count as usize) < PRICE_OBSERVATION_CAPACITY {
+                self.observation_count += 1;
+            }
+        }
+
+        self.update_circuit_breaker(price_usd_6dec, timestamp);
+
+        self.price_usd_6dec = price_usd_6dec;
+        self.last_update = timestamp;
+    }
+
+    /// Trip the breaker when the price moves more than `max_move_bps` within
+    /// an interval. It clears once a full interval passes without such a move.
+    fn update_circuit_breaker(&mut self, price_usd_6dec: u64, timestamp: i64) {
+        if self.max_move_bps == 0 || self.reference_price_usd_6dec == 0 {
+            self.reference_price_usd_6dec = price_usd_6dec;
+            self.reference_timestamp = timestamp;
+            return;
+        }
+
+        let within_bounds = deviation_bps(price_usd_6dec, self.reference_price_usd_6dec)
+            <= self.max_move_bps as u64;
+        if !within_bounds {
+            self.circuit_breaker_tripped = true;
+            self.tripped_at = timestamp;
+        }
+
+        if timestamp.saturating_sub(self.reference_timestamp) >= self.move_interval_seconds {
+            if self.circuit_breaker_tripped
+                && within_bounds
+                && timestamp.saturating_sub(self.tripped_at) >= self.move_interval_seconds
+            {
+                self.circuit_breaker_tripped = false;
+            }
+            self.reference_price_usd_6dec = price_usd_6dec;
+            self.reference_timestamp = timestamp;
+        }
+    }
+
+    /// Configure the circuit breaker (0 bps disables it)
+    pub fn configure_circuit_breaker(
+        &mut self,
+        max_move_bps: u16,
+        interval_seconds: i64,
+    ) -> Result<()> {
+        require!(
+            max_move_bps as u64 <= BPS_DENOMINATOR && interval_seconds > 0,
+            LegasiError::InvalidOracle
+        );
+        self.max_move_bps = max_move_bps;
+        self.move_interval_seconds = interval_seconds;
+        Ok(())
+    }
+
+    /// Manually reset the breaker around the current price (admin)
+    pub fn clear_circuit_breaker(&mut self, now: i64) {
+        self.circuit_breaker_tripped = false;
+        self.reference_price_usd_6dec = self.price_usd_6dec;
+        self.reference_timestamp = now;
+    }
+
+    /// Reject risk-increasing actions while the breaker is tripped
+    pub fn require_circuit_breaker_clear(&self) -> Result<()> {
+        require!(
+            !self.circuit_breaker_tripped,
+            LegasiError::CircuitBreakerTripped
+        );
+        Ok(())
+    }
+
+    /// Time-weighted average price over the last `window` seconds, or `None`
+    /// while the history doesn't reach back to the start of the window
+    pub fn twap(&self, now: i64, window: i64) -> Option<u64> {
+        let count = self.observation_count as usize;
+        let window_start = now.saturating_sub(window);
+        let mut weighted_sum: u128 = 0;
+        let mut total_time: u128 = 0;
+        let mut segment_end = now;
+        let mut covered = false;
+
+        // Walk from newest to oldest observation
+        for i in 0..count {
+            let index = (self.observation_index as usize + PRICE_OBSERVATION_CAPACITY - 1 - i)
+                % PRICE_OBSERVATION_CAPACITY;
+            let observation = &self.observations[index];
+
+            let segment_start = std::cmp::max(observation.timestamp, window_start);
+            if segment_end > segment_start {
+                let duration = (segment_end - segment_start) as u128;
+                weighted_sum += observation.price_usd_6dec as u128 * duration;
+                total_time += duration;
+            }
+
+            if observation.timestamp <= window_start {
+                covered = true;
+                break;
+            }
+            segment_end = observation.timestamp;
+        }
+
+        if !covered || total_time == 0 {
+            return None;
+        }
+        Some((weighted_sum / total_time) as u64)
+    }
+
+    /// TWAP over the protocol default window
+    pub fn twap_price(&self, now: i64) -> std::result::Result<u64, LegasiError> {
+        self.twap(now, TWAP_WINDOW_SECONDS)
+            .ok_or(LegasiError::InsufficientPriceHistory)
+    }
+
+    /// Lowest and highest of spot and every recorded observation
+    fn observed_range(&self) -> (u64, u64) {
+        self.observations[..self.observation_count as usize]
+            .iter()
+            .fold(
+                (self.price_usd_6dec, self.price_usd_6dec),
+                |(low, high), o| (low.min(o.price_usd_6dec), high.max(o.price_usd_6dec)),
+            )
+    }
+
+    /// Spot price, for reporting and stats
+    /// Rejects zero prices and feeds not updated within `PRICE_STALENESS_THRESHOLD`
+    pub fn fresh_price(&self, now: i64) -> std::result::Result<u64, LegasiError> {
+        if self.price_usd_6dec == 0
+            || now.saturating_sub(self.last_update) > PRICE_STALENESS_THRESHOLD
+        {
+            return Err(LegasiError::StalePriceFeed);
+        }
+        Ok(self.price_usd_6dec)
+    }
+
+    /// Mid TWAP of a fresh feed, for actions that must not react to a
+    /// single update (GAD, mid-mode debt valuation)
+    pub fn fresh_twap_price(&self, now: i64) -> std::result::Result<u64, LegasiError> {
+        self.fresh_price(now)?;
+        self.twap_price(now)
+    }
+
+    /// `collateral_price_for_borrow` of a fresh feed with its circuit breaker clear
+    pub fn fresh_collateral_price_for_borrow(
+        &self,
+        now: i64,
+    ) -> std::result::Result<u64, LegasiError> {
+        self.require_usable_for_borrow(now)?;
+        Ok(self.collateral_price_for_borrow(now))
+    }
+
+    /// `debt_price_for_borrow` of a fresh feed with its circuit breaker clear
+    pub fn fresh_debt_price_for_borrow(&self, now: i64) -> std::result::Result<u64, LegasiError> {
+        self.require_usable_for_borrow(now)?;
+        Ok(self.debt_price_for_borrow(now))
+    }
+
+    fn require_usable_for_borrow(&self, now: i64) -> std::result::Result<(), LegasiError> {
+        self.fresh_price(now)?;
+        if self.circuit_breaker_tripped {
+            return Err(LegasiError::CircuitBreakerTripped);
+        }
+        Ok(())
+    }
+
+    /// Confidence band used for conservative valuations: k * confidence
+    pub fn confidence_adjustment(&self) -> u64 {
+        ((self.confidence as u128) * (self.confidence_multiplier_bps as u128)
+            / (BPS_DENOMINATOR as u128))
+            .min(u64::MAX as u128) as u64
+    }
+
+    /// Price used to value collateral for borrows and withdrawals:
+    /// the lower of spot and TWAP, minus k * confidence. Until the history
+    /// covers the window, the lowest recorded price stands in for the TWAP
+    pub fn collateral_price_for_borrow(&self, now: i64) -> u64 {
+        let reference = self
+            .twap(now, TWAP_WINDOW_SECONDS)
+            .unwrap_or(self.observed_range().0);
+        std::cmp::min(self.price_usd_6dec, reference).saturating_sub(self.confidence_adjustment())
+    }
+
+    /// Price used to value debt for borrows and withdrawals:
+    /// the higher of spot and TWAP, plus k * confidence. Until the history
+    /// covers the window, the highest recorded price stands in for the TWAP
+    pub fn debt_price_for_borrow(&self, now: i64) -> u64 {
+        let reference = self
+            .twap(now, TWAP_WINDOW_SECONDS)
+            .unwrap_or(self.observed_range().1);
+        std::cmp::max(self.price_usd_6dec, reference).saturating_add(self.confidence_adjustment())
+    }
+
+    /// Set the confidence multiplier k (bps, 10000 = 1x)
+    pub fn configure_confidence_multiplier(&mut self, multiplier_bps: u16) -> Result<()> {
+        require!(
+            multiplier_bps <= MAX_CONFIDENCE_MULTIPLIER_BPS,
+            LegasiError::InvalidOracle
+        );
+        self.confidence_multiplier_bps = multiplier_bps;
+        Ok(())
+    }
+}
+
+impl PriceFeed {
+    /// Replace the source configuration (readings are reset)
+    pub fn configure_sources(
+        &mut self,
+        sources: &[OracleSourceConfig],
+        max_deviation_bps: u16,
+        min_sources: u8,
+    ) -> Result<()> {
+        require!(
+            !sources.is_empty() && sources.len() <= MAX_ORACLE_SOURCES,
+            LegasiError::InvalidOracle
+        );
+        require!(
+            min_sources >= 1 && min_sources as usize <= sources.len(),
+            LegasiError::InvalidOracle
+        );
+        require!(
+            max_deviation_bps > 0 && max_deviation_bps as u64 <= BPS_DENOMINATOR,
+            LegasiError::InvalidOracle
+        );
+        for (i, source) in sources.iter().enumerate() {
+            require!(
+                source.kind != OracleSourceKind::None,
+                LegasiError::InvalidOracle
+            );
+            // Each source may appear only once
+            require!(
+                !sources[..i]
+                    .iter()
+                    .any(|other| other.kind == source.kind && other.address == source.address),
+                LegasiError::InvalidOracle
+            );
+        }
+
+        self.sources = [OracleSource::default(); MAX_ORACLE_SOURCES];
+        for (slot, source) in self.sources.iter_mut().zip(sources) {
+            slot.kind = source.kind;
+            slot.address = source.address;
+        }
+        self.max_deviation_bps = max_deviation_bps;
+        self.min_sources = min_sources;
+        Ok(())
+    }
+
+    /// Index of the configured source matching `kind` and `address`
+    /// (the admin push source has the default address, a push oracle's
+    /// source the PushOracle account address)
+    pub fn find_source(&self, kind: OracleSourceKind, address: &Pubkey) -> Option<usize> {
+        self.sources
+            .iter()
+            .position(|source| source.kind == kind && source.address == *address)
+    }
+
+    /// Hand the admin push source over to a push oracle: its reading is
+    /// cleared and admin pushes no longer have a source to write
+    pub fn assign_push_source(&mut self, push_oracle: &Pubkey) {
+        if let Some(index) = self.find_source(OracleSourceKind::Push, &Pubkey::default()) {
+            self.sources[index] = OracleSource {
+                kind: OracleSourceKind::Push,
+                address: *push_oracle,
+                ..OracleSource::default()
+            };
+        }
+    }
+
+    /// Store the latest reading of a configured source
+    ///
+    /// Pyth and Switchboard readings carry the oracle's publish time and must
+    /// be strictly newer than the stored one, so an older verified update
+    /// can't be replayed to roll the price back. Push readings are stamped
+    /// with the clock and only must not go backwards
+    pub fn set_source_reading(
+        &mut self,
+        kind: OracleSourceKind,
+        address: &Pubkey,
+        price_usd_6dec: u64,
+        confidence: u64,
+        timestamp: i64,
+    ) -> Result<()> {
+        let index = self
+            .find_source(kind, address)
+            .ok_or(LegasiError::OracleSourceNotConfigured)?;
+        let source = &mut self.sources[index];
+        let newer = match kind {
+            OracleSourceKind::Push => timestamp >= source.last_update,
+            _ => timestamp > source.last_update,
+        };
+        require!(newer, LegasiError::OutdatedPriceUpdate);
+        source.price_usd_6dec = price_usd_6dec;
+        source.confidence = confidence;
+        source.last_update = timestamp;
+        Ok(())
+    }
+
+    /// Aggregate fresh sources into (price, confidence, timestamp), the
+    /// timestamp being the oldest reading that went into the price
+    ///
+    /// - Sources older than `PRICE_STALENESS_THRESHOLD` are ignored
+    /// - Sources further than `max_deviation_bps` from the median are dropped
+    /// - With at least `min_sources` agreeing sources, their median is used
+    /// - Otherwise the update is rejected: as stale if too few sources are
+    ///   fresh, as a deviation if fresh sources disagree. A lone source never
+    ///   sets the price unless `min_sources` is 1
+    pub fn aggregate_sources(&self, now: i64) -> std::result::Result<(u64, u64, i64), LegasiError> {
+        let fresh: Vec<&OracleSource> = self
+            .sources
+            .iter()
+            .filter(|s| {
+                s.kind != OracleSourceKind::None
+                    && s.price_usd_6dec > 0
+                    && now.saturating_sub(s.last_update) <= PRICE_STALENESS_THRESHOLD
+            })
+            .collect();
+        if fresh.is_empty() {
+            return Err(LegasiError::StalePriceFeed);
+        }
+
+        let fresh_median = median(fresh.iter().map(|s| s.price_usd_6dec).collect());
+        let agreeing: Vec<&OracleSource> = fresh
+            .iter()
+            .copied()
+            .filter(|s| {
+                deviation_bps(s.price_usd_6dec, fresh_median) <= self.max_deviation_bps as u64
+            })
+            .collect();
+
+        if agreeing.len() >= self.min_sources as usize {
+            let price = median(agreeing.iter().map(|s| s.price_usd_6dec).collect());
+            let confidence = agreeing.iter().map(|s| s.confidence).max().unwrap_or(0);
+            let timestamp = agreeing.iter().map(|s| s.last_update).min().unwrap_or(now);
+            return Ok((price, confidence, timestamp));
+        }
+
+        if agreeing.len() == fresh.len() {
+            return Err(LegasiError::StalePriceFeed);
+        }
+        Err(LegasiError::OracleDeviationExceeded)
+    }
+
+    /// Re-aggregate sources and record the result as the new spot price, at
+    /// the time of the readings it was built from. A source rejoining with an
+    /// older reading can't move the feed (or the TWAP history) back in time
+    pub fn refresh_from_sources(&mut self, now: i64) -> Result<()> {
+        let (price, confidence, timestamp) = self.aggregate_sources(now)?;
+        self.record_price(price, timestamp.max(self.last_update));
+        self.confidence = confidence;
+        Ok(())
+    }
+}
+
+/// Median of a non-empty list (average of the middle pair for even lengths)
+pub(crate) fn median(mut values: Vec<u64>) -> u64 {
+    values.sort_unstable();
+    let lower = values[(values.len() - 1) / 2];
+    let upper = values[values.len() / 2];
+    ((lower as u128 + upper as u128) / 2) as u64
+}
+
+/// Absolute deviation of `price` from `reference` in basis points
+fn deviation_bps(price: u64, reference: u64) -> u64 {
+    if reference == 0 {
+        return u64::MAX;
+    }
+    (price.abs_diff(reference) as u128 * BPS_DENOMINATOR as u128 / reference as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_feed() -> PriceFeed {
+        PriceFeed {
+            mint: Pubkey::default(),
+            price_usd_6dec: 0,
+            last_update: 0,
+            confidence: 0,
+            ema_price_usd_6dec: 0,
+            observation_index: 0,
+            observation_count: 0,
+            observations: [PriceObservation::default(); PRICE_OBSERVATION_CAPACITY],
+            sources: [OracleSource::default(); MAX_ORACLE_SOURCES],
+            max_deviation_bps: DEFAULT_MAX_ORACLE_DEVIATION_BPS,
+            min_sources: 1,
+            max_move_bps: 0,
+            move_interval_seconds: DEFAULT_PRICE_MOVE_INTERVAL,
+            reference_price_usd_6dec: 0,
+            reference_timestamp: 0,
+            circuit_breaker_tripped: false,
+            tripped_at: 0,
+            confidence_multiplier_bps: DEFAULT_CONFIDENCE_MULTIPLIER_BPS,
+            bump: 0,
+        }
+    }
+
+    fn multi_source_feed(min_sources: u8) -> PriceFeed {
+        let mut feed = empty_feed();
+        let configs = [
+            OracleSourceConfig {
+                kind: OracleSourceKind::Pyth,
+                address: Pubkey::new_from_array([1; 32]),
+            },
+            OracleSourceConfig {
+                kind: OracleSourceKind::Switchboard,
+                address: Pubkey::new_from_array([2; 32]),
+            },
+            OracleSourceConfig {
+                kind: OracleSourceKind::Push,
+                address: Pubkey::default(),
+            },
+        ];
+        feed.configure_sources(&configs, DEFAULT_MAX_ORACLE_DEVIATION_BPS, min_sources)
+            .unwrap();
+        feed
+    }
+
+    fn set(feed: &mut PriceFeed, index: usize, price: u64, timestamp: i64) {
+        let source = feed.sources[index];
+        feed.set_source_reading(source.kind, &source.address, price, 10_000, timestamp)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_twap_constant_price() {
+        let mut feed = empty_feed();
+        for i in 0..5 {
+            feed.record_price(150_000_000, i * 300);
+        }
+        assert_eq!(feed.twap_price(1800).unwrap(), 150_000_000);
+        assert_eq!(feed.ema_price_usd_6dec, 150_000_000);
+    }
+
+    #[test]
+    fn test_single_spike_barely_moves_twap() {
+        let mut feed = empty_feed();
+        feed.record_price(100_000_000, 0);
+        feed.record_price(100_000_000, 1740);
+        // Manipulated update 60 seconds before "now"
+        feed.record_price(200_000_000, 1740);
+
+        let now = 1800;
+        let twap = feed.twap_price(now).unwrap();
+        // 1740s at $100 and 60s at $200 -> ~$103.33
+        assert_eq!(twap, 103_333_333);
+        assert_eq!(feed.collateral_price_for_borrow(now), twap);
+    }
+
+    #[test]
+    fn test_ema_moves_with_elapsed_time() {
+        let mut feed = empty_feed();
+        feed.record_price(100_000_000, 0);
+        // A third of the EMA period towards $130
+        feed.record_price(130_000_000, EMA_PERIOD_SECONDS / 3);
+        assert_eq!(feed.ema_price_usd_6dec, 110_000_000);
+
+        // Beyond a full period the EMA catches up completely
+        feed.record_price(130_000_000, EMA_PERIOD_SECONDS * 3);
+        assert_eq!(feed.ema_price_usd_6dec, 130_000_000);
+    }
+
+    #[test]
+    fn test_ring_buffer_wraps() {
+        let mut feed = empty_feed();
+        for i in 0..(PRICE_OBSERVATION_CAPACITY as i64 + 4) {
+            feed.record_price(1_000_000 + i as u64, i * 60);
+        }
+        assert_eq!(feed.observation_count as usize, PRICE_OBSERVATION_CAPACITY);
+        assert_eq!(feed.observation_index, 4);
+
+        // Only the last observation falls inside a 30s window
+        let last = PRICE_OBSERVATION_CAPACITY as i64 + 3;
+        assert_eq!(feed.twap(last * 60 + 30, 30), Some(1_000_000 + last as u64));
+
+        // At the minimum spacing a full buffer spans the default window
+        assert!(feed.twap_price(last * 60).is_ok());
+    }
+
+    #[test]
+    fn test_burst_of_updates_cannot_flush_history() {
+        let mut feed = empty_feed();
+        for i in 0..PRICE_OBSERVATION_CAPACITY as i64 {
+            feed.record_price(100_000_000, i * PRICE_OBSERVATION_INTERVAL);
+        }
+        let now = (PRICE_OBSERVATION_CAPACITY as i64 - 1) * PRICE_OBSERVATION_INTERVAL;
+
+        // Far more same-second syncs than the buffer holds
+        for _ in 0..4 * PRICE_OBSERVATION_CAPACITY {
+            feed.record_price(200_000_000, now);
+        }
+        assert_eq!(feed.price_usd_6dec, 200_000_000);
+        assert_eq!(feed.observation_count as usize, PRICE_OBSERVATION_CAPACITY);
+
+        // Only the newest observation took the new price; the rest of the
+        // window is still at $100
+        let twap = feed.twap_price(now + 1).unwrap();
+        assert!(twap < 100_200_000, "twap {twap}");
+        assert_eq!(feed.collateral_price_for_borrow(now + 1), twap);
+    }
+
+    #[test]
+    fn test_short_history_is_not_treated_as_spot() {
+        let mut feed = empty_feed();
+        feed.record_price(100_000_000, 0);
+        feed.record_price(300_000_000, 600);
+
+        // 600s of history don't cover the 1800s window
+        let now = 700;
+        assert!(matches!(
+            feed.twap_price(now),
+            Err(LegasiError::InsufficientPriceHistory)
+        ));
+        // Borrows value collateral at the lowest and debt at the highest price seen
+        assert_eq!(feed.collateral_price_for_borrow(now), 100_000_000);
+        assert_eq!(feed.debt_price_for_borrow(now), 300_000_000);
+
+        // Once the window is covered, the TWAP is used
+        assert_eq!(feed.twap_price(1800).unwrap(), 233_333_333);
+    }
+
+    #[test]
+    fn test_median_of_agreeing_sources() {
+        let mut feed = multi_source_feed(2);
+        set(&mut feed, 0, 150_000_000, 1000);
+        set(&mut feed, 1, 151_000_000, 1000);
+        set(&mut feed, 2, 149_000_000, 1000);
+
+        feed.refresh_from_sources(1010).unwrap();
+        assert_eq!(feed.price_usd_6dec, 150_000_000);
+        // Stamped with the readings' time, so staleness counts from there
+        assert_eq!(feed.last_update, 1000);
+    }
+
+    #[test]
+    fn test_rejoining_source_cannot_move_feed_back() {
+        let mut feed = multi_source_feed(2);
+        set(&mut feed, 0, 150_000_000, 100); // Stale Pyth
+        set(&mut feed, 1, 150_000_000, 1000);
+        set(&mut feed, 2, 150_000_000, 1000);
+        feed.refresh_from_sources(1000).unwrap();
+        assert_eq!(feed.last_update, 1000);
+
+        // Pyth comes back with a reading older than the feed
+        set(&mut feed, 0, 151_000_000, 900);
+        feed.refresh_from_sources(1000).unwrap();
+        assert_eq!(feed.last_update, 1000);
+
+        set(&mut feed, 1, 152_000_000, 1200);
+        set(&mut feed, 2, 152_000_000, 1200);
+        feed.refresh_from_sources(1200).unwrap();
+        let count = feed.observation_count as usize;
+        let timestamps: Vec<i64> = (0..count)
+            .map(|i| {
+                let index = (feed.observation_index as usize + PRICE_OBSERVATION_CAPACITY - count
+                    + i)
+                    % PRICE_OBSERVATION_CAPACITY;
+                feed.observations[index].timestamp
+            })
+            .collect();
+        assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(feed.last_update, 1000);
+    }
+
+    #[test]
+    fn test_broken_source_is_ignored() {
+        let mut feed = multi_source_feed(2);
+        set(&mut feed, 0, 150_000_000, 1000);
+        set(&mut feed, 1, 1_000_000, 1000); // Broken oracle
+        set(&mut feed, 2, 150_500_000, 1000);
+
+        let (price, _, _) = feed.aggregate_sources(1000).unwrap();
+        assert_eq!(price, 150_250_000);
+    }
+
+    #[test]
+    fn test_disagreement_without_quorum_is_rejected() {
+        let mut feed = multi_source_feed(2);
+        set(&mut feed, 0, 150_000_000, 1000);
+        set(&mut feed, 1, 180_000_000, 1000);
+
+        assert!(matches!(
+            feed.aggregate_sources(1000),
+            Err(LegasiError::OracleDeviationExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_quorum_required_when_others_stale() {
+        let mut feed = multi_source_feed(2);
+        set(&mut feed, 0, 150_000_000, 500); // Stale Pyth
+        set(&mut feed, 1, 152_000_000, 1000);
+        set(&mut feed, 2, 151_000_000, 1000);
+
+        let now = 500 + PRICE_STALENESS_THRESHOLD + 1;
+        let (price, _, _) = feed.aggregate_sources(now).unwrap();
+        assert_eq!(price, 151_500_000);
+
+        // Only the push source is fresh: one source can't publish alone
+        let now = 1000 + PRICE_STALENESS_THRESHOLD + 1;
+        set(&mut feed, 2, 151_000_000, now);
+        assert!(matches!(
+            feed.aggregate_sources(now),
+            Err(LegasiError::StalePriceFeed)
+        ));
+        assert!(feed.refresh_from_sources(now).is_err());
+
+        // Unless the feed is configured to accept a single source
+        feed.min_sources = 1;
+        let (price, _, _) = feed.aggregate_sources(now).unwrap();
+        assert_eq!(price, 151_000_000);
+
+        // Nothing fresh at all
+        assert!(matches!(
+            feed.aggregate_sources(now + PRICE_STALENESS_THRESHOLD + 1),
+            Err(LegasiError::StalePriceFeed)
+        ));
+    }
+
+    #[test]
+    fn test_pull_readings_must_be_newer() {
+        let mut feed = multi_source_feed(1);
+        set(&mut feed, 0, 150_000_000, 1000);
+
+        // Replaying an older (or the same) Pyth update is rejected
+        let pyth = feed.sources[0];
+        for timestamp in [999, 1000] {
+            assert!(feed
+                .set_source_reading(pyth.kind, &pyth.address, 100_000_000, 0, timestamp)
+                .is_err());
+        }
+        assert_eq!(feed.sources[0].price_usd_6dec, 150_000_000);
+        set(&mut feed, 0, 151_000_000, 1001);
+
+        // Push readings may repeat within a second, but not go backwards
+        set(&mut feed, 2, 150_000_000, 1000);
+        set(&mut feed, 2, 150_500_000, 1000);
+        let push = feed.sources[2];
+        assert!(feed
+            .set_source_reading(push.kind, &push.address, 150_000_000, 0, 999)
+            .is_err());
+    }
+
+    #[test]
+    fn test_push_oracle_takes_over_admin_push_source() {
+        let mut feed = multi_source_feed(2);
+        let push_oracle = Pubkey::new_from_array([3; 32]);
+        set(&mut feed, 2, 150_000_000, 1000);
+
+        feed.assign_push_source(&push_oracle);
+        assert_eq!(feed.sources[2].address, push_oracle);
+        assert_eq!(feed.sources[2].last_update, 0);
+
+        // The admin push can no longer write the quorum's slot
+        assert!(feed
+            .set_source_reading(
+                OracleSourceKind::Push,
+                &Pubkey::default(),
+                1_000_000,
+                0,
+                1001
+            )
+            .is_err());
+        set(&mut feed, 2, 151_000_000, 1001);
+        assert_eq!(feed.sources[2].price_usd_6dec, 151_000_000);
+
+        // Both kinds of push source can be configured side by side
+        let mut feed = multi_source_feed(2);
+        let configs = [
+            OracleSourceConfig {
+                kind: OracleSourceKind::Push,
+                address: Pubkey::default(),
+            },
+            OracleSourceConfig {
+                kind: OracleSourceKind::Push,
+                address: push_oracle,
+            },
+        ];
+        feed.configure_sources(&configs, DEFAULT_MAX_ORACLE_DEVIATION_BPS, 2)
+            .unwrap();
+        set(&mut feed, 0, 1_000_000, 1000);
+        set(&mut feed, 1, 150_000_000, 1000);
+        assert_eq!(feed.sources[1].price_usd_6dec, 150_000_000);
+        assert!(feed.aggregate_sources(1000).is_err());
+    }
+
+    #[test]
+    fn test_configure_sources_validation() {
+        let mut feed = empty_feed();
+        let pyth = OracleSourceConfig {
+            kind: OracleSourceKind::Pyth,
+            address: Pubkey::new_from_array([1; 32]),
+        };
+        assert!(feed.configure_sources(&[], 200, 1).is_err());
+        assert!(feed.configure_sources(&[pyth], 200, 2).is_err());
+        assert!(feed.configure_sources(&[pyth, pyth], 200, 1).is_err());
+        assert!(feed.configure_sources(&[pyth], 0, 1).is_err());
+        assert!(feed.configure_sources(&[pyth], 200, 1).is_ok());
+        assert!(feed
+            .find_source(OracleSourceKind::Push, &Pubkey::default())
+            .is_none());
+    }
+
+    #[test]
+    fn test_circuit_breaker_trips_on_abnormal_move() {
+        let mut feed = empty_feed();
+        feed.configure_circuit_breaker(1000, 600).unwrap();
+        feed.record_price(100_000_000, 0);
+
+        // 5% move is fine
+        feed.record_price(105_000_000, 60);
+        assert!(!feed.circuit_breaker_tripped);
+        assert!(feed.require_circuit_breaker_clear().is_ok());
+
+        // 15% from the interval reference trips the breaker
+        feed.record_price(115_000_000, 120);
+        assert!(feed.circuit_breaker_tripped);
+        assert!(feed.require_circuit_breaker_clear().is_err());
+    }
+
+    #[test]
+    fn test_circuit_breaker_clears_after_stable_interval() {
+        let mut feed = empty_feed();
+        feed.configure_circuit_breaker(1000, 600).unwrap();
+        feed.record_price(100_000_000, 0);
+        feed.record_price(150_000_000, 100);
+        assert!(feed.circuit_breaker_tripped);
+
+        // New level persists: still abnormal against the old reference
+        feed.record_price(150_000_000, 700);
+        assert!(feed.circuit_breaker_tripped);
+
+        // A full interval at the new level clears it
+        feed.record_price(151_000_000, 1000);
+        assert!(feed.circuit_breaker_tripped);
+        feed.record_price(151_000_000, 1300);
+        assert!(!feed.circuit_breaker_tripped);
+    }
+
+    #[test]
+    fn test_circuit_breaker_admin_clear_and_disable() {
+        let mut feed = empty_feed();
+        feed.configure_circuit_breaker(1000, 600).unwrap();
+        feed.record_price(100_000_000, 0);
+        feed.record_price(50_000_000, 10);
+        assert!(feed.circuit_breaker_tripped);
+
+        feed.clear_circuit_breaker(20);
+        assert!(!feed.circuit_breaker_tripped);
+        assert_eq!(feed.reference_price_usd_6dec, 50_000_000);
+
+        feed.configure_circuit_breaker(0, 600).unwrap();
+        feed.record_price(500_000_000, 30);
+        assert!(!feed.circuit_breaker_tripped);
+    }
+
+    #[test]
+    fn test_confidence_widens_collateral_and_debt_prices() {
+        let mut feed = empty_feed();
+        feed.record_price(100_000_000, 0);
+        feed.confidence = 500_000; // $0.50
+        let now = TWAP_WINDOW_SECONDS;
+
+        assert_eq!(feed.collateral_price_for_borrow(now), 99_500_000);
+        assert_eq!(feed.debt_price_for_borrow(now), 100_500_000);
+
+        // k = 3 triples the band; mid TWAP is unaffected
+        feed.configure_confidence_multiplier(30000).unwrap();
+        assert_eq!(feed.collateral_price_for_borrow(now), 98_500_000);
+        assert_eq!(feed.debt_price_for_borrow(now), 101_500_000);
+        assert_eq!(feed.twap_price(now).unwrap(), 100_000_000);
+
+        assert!(feed
+            .configure_confidence_multiplier(MAX_CONFIDENCE_MULTIPLIER_BPS + 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_fresh_price_rejects_stale_and_zero() {
+        let mut feed = empty_feed();
+        assert!(matches!(
+            feed.fresh_price(0),
+            Err(LegasiError::StalePriceFeed)
+        ));
+
+        feed.record_price(100_000_000, 1_000);
+        assert_eq!(feed.fresh_price(1_000).unwrap(), 100_000_000);
+        assert_eq!(
+            feed.fresh_price(1_000 + PRICE_STALENESS_THRESHOLD).unwrap(),
+            100_000_000
+        );
+
+        // Clock advances past the threshold without an update
+        assert!(matches!(
+            feed.fresh_price(1_000 + PRICE_STALENESS_THRESHOLD + 1),
+            Err(LegasiError::StalePriceFeed)
+        ));
+
+        // A new update makes it usable again
+        feed.record_price(101_000_000, 2_000);
+        assert_eq!(feed.fresh_price(2_100).unwrap(), 101_000_000);
+    }
+
+    #[test]
+    fn test_fresh_helpers_return_the_checked_price() {
+        let mut feed = empty_feed();
+        feed.record_price(100_000_000, 0);
+        feed.record_price(90_000_000, TWAP_WINDOW_SECONDS);
+        feed.confidence = 500_000;
+        let now = TWAP_WINDOW_SECONDS;
+
+        assert_eq!(
+            feed.fresh_collateral_price_for_borrow(now).unwrap(),
+            feed.collateral_price_for_borrow(now)
+        );
+        assert_eq!(
+            feed.fresh_debt_price_for_borrow(now).unwrap(),
+            feed.debt_price_for_borrow(now)
+        );
+        assert_eq!(
+            feed.fresh_twap_price(now).unwrap(),
+            feed.twap_price(now).unwrap()
+        );
+
+        // A tripped breaker blocks borrow pricing but not the mid TWAP
+        feed.circuit_breaker_tripped = true;
+        assert!(matches!(
+            feed.fresh_collateral_price_for_borrow(now),
+            Err(LegasiError::CircuitBreakerTripped)
+        ));
+        assert!(matches!(
+            feed.fresh_debt_price_for_borrow(now),
+            Err(LegasiError::CircuitBreakerTripped)
+        ));
+        assert!(feed.fresh_twap_price(now).is_ok());
+
+        // Staleness blocks all of them
+        let later = now + PRICE_STALENESS_THRESHOLD + 1;
+        feed.circuit_breaker_tripped = false;
+        assert!(matches!(
+            feed.fresh_collateral_price_for_borrow(later),
+            Err(LegasiError::StalePriceFeed)
+        ));
+        assert!(matches!(
+            feed.fresh_twap_price(later),
+            Err(LegasiError::StalePriceFeed)
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+//! Pause controls
+//!
+//! Actions are paused with `PAUSE_*` bit flags, protocol-wide on `Protocol`
+//! and per asset on `Collateral` / `Borrowable`. The emergency pause leaves
+//! repaying and depositing collateral available so users can always de-risk.
+
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::LegasiError;
+use crate::state::{Borrowable, Collateral, Protocol};
+
+fn validate_flags(flags: u16) -> std::result::Result<(), LegasiError> {
+    if flags & !PAUSE_ALL != 0 {
+        return Err(LegasiError::InvalidAmount);
+    }
+    Ok(())
+}
+
+impl Protocol {
+    pub fn set_pause_flags(&mut self, flags: u16) -> std::result::Result<(), LegasiError> {
+        validate_flags(flags)?;
+        self.pause_flags = flags;
+        self.paused = flags != 0;
+        Ok(())
+    }
+
+    pub fn require_not_paused(&self, action: u16) -> Result<()> {
+        require!(self.pause_flags & action == 0, LegasiError::ProtocolPaused);
+        Ok(())
+    }
+}
+
+impl Collateral {
+    pub fn set_pause_flags(&mut self, flags: u16) -> std::result::Result<(), LegasiError> {
+        validate_flags(flags)?;
+        self.pause_flags = flags;
+        Ok(())
+    }
+
+    pub fn require_not_paused(&self, action: u16) -> Result<()> {
+        require!(self.pause_flags & action == 0, LegasiError::AssetPaused);
+        Ok(())
+    }
+}
+
+impl Borrowable {
+    pub fn set_pause_flags(&mut self, flags: u16) -> std::result::Result<(), LegasiError> {
+        validate_flags(flags)?;
+        self.pause_flags = flags;
+        Ok(())
+    }
+
+    pub fn require_not_paused(&self, action: u16) -> Result<()> {
+        require!(self.pause_flags & action == 0, LegasiError::AssetPaused);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol() -> Protocol {
+        Protocol {
+            admin: Pubkey::default(),
+            treasury: Pubkey::default(),
+            insurance_fund: 0,
+            total_collateral_usd: 0,
+            total_borrowed_usd: 0,
+            paused: false,
+            pending_admin: Pubkey::default(),
+            risk_admin: Pubkey::default(),
+            oracle_admin: Pubkey::default(),
+            pause_guardian: Pubkey::default(),
+            treasury_manager: Pubkey::default(),
+            timelock_delay: 0,
+            next_change_id: 0,
+            pause_flags: 0,
+            collateral_count: 0,
+            borrowable_count: 0,
+            stats_updated_at: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn test_emergency_pause_keeps_repay_and_deposit() {
+        let mut protocol = protocol();
+        protocol.set_pause_flags(PAUSE_EMERGENCY).unwrap();
+        assert!(protocol.paused);
+
+        assert!(protocol.require_not_paused(PAUSE_REPAY).is_ok());
+        assert!(protocol.require_not_paused(PAUSE_DEPOSIT).is_ok());
+        for action in [
+            PAUSE_BORROW,
+            PAUSE_WITHDRAW,
+            PAUSE_GAD,
+            PAUSE_FLASH,
+            PAUSE_LP_DEPOSIT,
+            PAUSE_LP_WITHDRAW,
+        ] {
+            assert!(protocol.require_not_paused(action).is_err());
+        }
+
+        protocol.set_pause_flags(0).unwrap();
+        assert!(!protocol.paused);
+        assert!(protocol.require_not_paused(PAUSE_BORROW).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_unknown_flags() {
+        let mut protocol = protocol();
+        assert!(protocol.set_pause_flags(1 << 15).is_err());
+        assert_eq!(protocol.pause_flags, 0);
+    }
+}
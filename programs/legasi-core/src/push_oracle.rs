@@ -0,0 +1,188 @@
+//! Push oracle with publisher quorum
+//!
+//! A configured set of publisher keys each submit a price per round. Once
+//! `quorum` publishers have submitted, the round's median is written to the
+//! price feed's push source and a new round starts. Rounds that don't reach
+//! quorum within `PUSH_ROUND_DURATION` are discarded.
+
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::LegasiError;
+use crate::oracle::median;
+use crate::state::PushOracle;
+
+impl PushOracle {
+    /// Replace the publisher set and quorum (the current round is discarded)
+    pub fn configure(&mut self, publishers: &[Pubkey], quorum: u8) -> Result<()> {
+        require!(
+            !publishers.is_empty() && publishers.len() <= MAX_PUSH_PUBLISHERS,
+            LegasiError::InvalidOracle
+        );
+        require!(
+            quorum > 0 && quorum as usize <= publishers.len(),
+            LegasiError::InvalidOracle
+        );
+        for (i, publisher) in publishers.iter().enumerate() {
+            require!(
+                *publisher != Pubkey::default() && !publishers[..i].contains(publisher),
+                LegasiError::InvalidOracle
+            );
+        }
+
+        self.publishers = [Pubkey::default(); MAX_PUSH_PUBLISHERS];
+        self.publishers[..publishers.len()].copy_from_slice(publishers);
+        self.publisher_count = publishers.len() as u8;
+        self.quorum = quorum;
+        self.start_round(self.round.saturating_add(1), 0);
+        Ok(())
+    }
+
+    fn start_round(&mut self, round: u64, now: i64) {
+        self.round = round;
+        self.round_started_at = now;
+        self.submitted_mask = 0;
+        self.submissions = [0; MAX_PUSH_PUBLISHERS];
+    }
+
+    /// Record a publisher's price for the current round
+    /// Returns `(median, confidence)` when the submission completes the quorum,
+    /// where confidence is half the spread of the round's submissions
+    pub fn submit(
+        &mut self,
+        publisher: &Pubkey,
+        price_usd_6dec: u64,
+        now: i64,
+    ) -> std::result::Result<Option<(u64, u64)>, LegasiError> {
+        if price_usd_6dec == 0 {
+            return Err(LegasiError::InvalidAmount);
+        }
+        let index = self.publishers[..self.publisher_count as usize]
+            .iter()
+            .position(|p| p == publisher)
+            .ok_or(LegasiError::UnknownPublisher)?;
+
+        if self.submitted_mask != 0
+            && now.saturating_sub(self.round_started_at) > PUSH_ROUND_DURATION
+        {
+            self.start_round(self.round.saturating_add(1), now);
+        }
+        if self.submitted_mask == 0 {
+            self.round_started_at = now;
+        }
+
+        let bit = 1u16 << index;
+        if self.submitted_mask & bit != 0 {
+            return Err(LegasiError::DuplicatePriceSubmission);
+        }
+        self.submitted_mask |= bit;
+        self.submissions[index] = price_usd_6dec;
+
+        if self.submitted_mask.count_ones() < self.quorum as u32 {
+            return Ok(None);
+        }
+
+        let prices: Vec<u64> = (0..self.publisher_count as usize)
+            .filter(|i| self.submitted_mask & (1u16 << i) != 0)
+            .map(|i| self.submissions[i])
+            .collect();
+        let min = *prices.iter().min().unwrap_or(&0);
+        let max = *prices.iter().max().unwrap_or(&0);
+        let price = median(prices);
+
+        self.start_round(self.round.saturating_add(1), now);
+        Ok(Some((price, (max - min) / 2)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oracle(publishers: &[Pubkey], quorum: u8) -> PushOracle {
+        let mut oracle = PushOracle {
+            price_feed: Pubkey::default(),
+            publishers: [Pubkey::default(); MAX_PUSH_PUBLISHERS],
+            publisher_count: 0,
+            quorum: 0,
+            round: 0,
+            round_started_at: 0,
+            submitted_mask: 0,
+            submissions: [0; MAX_PUSH_PUBLISHERS],
+            bump: 0,
+        };
+        oracle.configure(publishers, quorum).unwrap();
+        oracle
+    }
+
+    #[test]
+    fn test_round_publishes_median_at_quorum() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut oracle = oracle(&keys, 2);
+        let round = oracle.round;
+
+        assert_eq!(oracle.submit(&keys[0], 100_000_000, 10).unwrap(), None);
+        assert_eq!(
+            oracle.submit(&keys[2], 102_000_000, 12).unwrap(),
+            Some((101_000_000, 1_000_000))
+        );
+        assert_eq!(oracle.round, round + 1);
+        assert_eq!(oracle.submitted_mask, 0);
+    }
+
+    #[test]
+    fn test_rejects_unknown_and_duplicate_publishers() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut oracle = oracle(&keys, 3);
+
+        assert!(matches!(
+            oracle.submit(&Pubkey::new_unique(), 100_000_000, 0),
+            Err(LegasiError::UnknownPublisher)
+        ));
+        oracle.submit(&keys[1], 100_000_000, 0).unwrap();
+        assert!(matches!(
+            oracle.submit(&keys[1], 101_000_000, 1),
+            Err(LegasiError::DuplicatePriceSubmission)
+        ));
+        assert!(matches!(
+            oracle.submit(&keys[0], 0, 1),
+            Err(LegasiError::InvalidAmount)
+        ));
+    }
+
+    #[test]
+    fn test_expired_round_is_discarded() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut oracle = oracle(&keys, 2);
+        let round = oracle.round;
+
+        oracle.submit(&keys[0], 50_000_000, 0).unwrap();
+        // Too late to pair with the first submission
+        assert_eq!(
+            oracle
+                .submit(&keys[1], 100_000_000, PUSH_ROUND_DURATION + 1)
+                .unwrap(),
+            None
+        );
+        assert_eq!(oracle.round, round + 1);
+        assert_eq!(
+            oracle
+                .submit(&keys[0], 100_000_000, PUSH_ROUND_DURATION + 2)
+                .unwrap(),
+            Some((100_000_000, 0))
+        );
+    }
+
+    #[test]
+    fn test_configure_validates_publishers() {
+        let key = Pubkey::new_unique();
+        let mut oracle = oracle(&[key], 1);
+        assert!(oracle.configure(&[], 1).is_err());
+        assert!(oracle.configure(&[key], 2).is_err());
+        assert!(oracle.configure(&[key, key], 1).is_err());
+        assert!(oracle.configure(&[Pubkey::default()], 1).is_err());
+        assert!(oracle
+            .configure(&[key; MAX_PUSH_PUBLISHERS + 1], 1)
+            .is_err());
+    }
+}
@@ -1,7 +1,17 @@
-/// Pyth Oracle Integration
-///
-/// Pyth provides real-time price feeds for crypto assets on Solana.
-/// We use Pyth for SOL/USD and BTC/USD prices.
+//! Pyth Oracle Integration
+//!
+//! Pyth provides real-time price feeds for crypto assets on Solana.
+//! We use Pyth for SOL/USD and BTC/USD prices.
+//!
+//! Two account formats are supported:
+//! - Legacy push oracle price accounts (owned by the Pyth oracle program)
+//! - Pull oracle `PriceUpdateV2` accounts (owned by the Pyth receiver program)
+//!
+//! Both are validated (owner, layout, feed, status) before any price is used.
+
+use anchor_lang::prelude::*;
+
+use crate::errors::LegasiError;
 
 /// Pyth Price Feed IDs (Mainnet)
 /// See: https://pyth.network/developers/price-feed-ids
@@ -34,8 +44,60 @@ pub mod price_feed_ids {
     }
 }
 
-/// Pyth price data structure (simplified)
-/// In production, use the official pyth-solana-receiver-sdk
+/// Programs allowed to own Pyth accounts we read
+pub mod program_ids {
+    use super::*;
+
+    /// Legacy Pyth oracle program (mainnet)
+    pub const PYTH_ORACLE_MAINNET: Pubkey = pubkey!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+
+    /// Legacy Pyth oracle program (devnet)
+    pub const PYTH_ORACLE_DEVNET: Pubkey = pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+
+    /// Pyth Solana receiver program (pull oracle, all clusters)
+    pub const PYTH_RECEIVER: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+}
+
+/// Legacy price account layout
+pub mod legacy_layout {
+    /// Magic number at the start of every Pyth account
+    pub const MAGIC: u32 = 0xa1b2c3d4;
+    /// Supported account version
+    pub const VERSION_2: u32 = 2;
+    /// Account type for price accounts
+    pub const ACCOUNT_TYPE_PRICE: u32 = 3;
+    /// Aggregate price status: trading
+    pub const STATUS_TRADING: u32 = 1;
+
+    pub const MAGIC_OFFSET: usize = 0;
+    pub const VERSION_OFFSET: usize = 4;
+    pub const ACCOUNT_TYPE_OFFSET: usize = 8;
+    pub const EXPO_OFFSET: usize = 20;
+    pub const TIMESTAMP_OFFSET: usize = 96;
+    pub const AGG_PRICE_OFFSET: usize = 208;
+    pub const AGG_CONF_OFFSET: usize = 216;
+    pub const AGG_STATUS_OFFSET: usize = 224;
+    /// Minimum account size covering the aggregate price info
+    pub const MIN_SIZE: usize = 240;
+}
+
+/// Pull oracle `PriceUpdateV2` account layout (Borsh, Anchor account)
+pub mod price_update_v2_layout {
+    /// sha256("account:PriceUpdateV2")[..8]
+    pub const DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+    /// VerificationLevel::Partial variant (followed by a u8 signature count)
+    pub const VERIFICATION_PARTIAL: u8 = 0;
+    /// VerificationLevel::Full variant
+    pub const VERIFICATION_FULL: u8 = 1;
+
+    pub const WRITE_AUTHORITY_OFFSET: usize = 8;
+    pub const VERIFICATION_LEVEL_OFFSET: usize = 40;
+    /// Price message size: feed_id + price + conf + exponent + publish_time
+    /// + prev_publish_time + ema_price + ema_conf
+    pub const PRICE_MESSAGE_SIZE: usize = 32 + 8 + 8 + 4 + 8 + 8 + 8 + 8;
+}
+
+/// Pyth price data structure
 #[derive(Clone, Copy, Debug)]
 pub struct PythPrice {
     /// Price in USD (scaled by 10^expo)
@@ -50,32 +112,17 @@ pub struct PythPrice {
 
 impl PythPrice {
     /// Convert Pyth price to our standard 6-decimal USD format
-    pub fn to_usd_6dec(&self) -> u64 {
+    /// Returns None for non-positive prices or values that don't fit
+    pub fn to_usd_6dec(&self) -> Option<u64> {
         if self.price <= 0 {
-            return 0;
+            return None;
         }
+        scale_to_usd_6dec(self.price as u128, self.expo).filter(|p| *p > 0)
+    }
 
-        let price = self.price as u128;
-
-        // Convert to 6 decimals
-        // If expo is -8, we need to divide by 10^2 to get 6 decimals
-        // If expo is -6, price is already in 6 decimals
-        let target_decimals: i32 = 6;
-        let adjustment = target_decimals - (-self.expo);
-
-        let result = if adjustment > 0 {
-            price
-                .checked_mul(10u128.pow(adjustment as u32))
-                .unwrap_or(0)
-        } else if adjustment < 0 {
-            price
-                .checked_div(10u128.pow((-adjustment) as u32))
-                .unwrap_or(0)
-        } else {
-            price
-        };
-
-        result as u64
+    /// Convert the confidence interval to 6-decimal USD
+    pub fn conf_to_usd_6dec(&self) -> Option<u64> {
+        scale_to_usd_6dec(self.conf as u128, self.expo)
     }
 
     /// Check if price is stale (older than max_age seconds)
@@ -93,30 +140,141 @@ impl PythPrice {
     }
 }
 
-/// Parse Pyth price account data
-/// This is a simplified version - in production use pyth-solana-receiver-sdk
-pub fn parse_pyth_price(data: &[u8]) -> Option<PythPrice> {
-    // Pyth price account layout (simplified):
-    // Skip magic number and version (8 bytes)
-    // Price at offset 208, conf at 216, expo at 224, publish_time at 232
+/// Rescale `value * 10^expo` to 6 decimals
+/// Returns None on overflow instead of truncating
+fn scale_to_usd_6dec(value: u128, expo: i32) -> Option<u64> {
+    let target_decimals: i32 = 6;
+    let adjustment = target_decimals.checked_add(expo)?;
 
-    if data.len() < 240 {
-        return None;
-    }
+    let result = if adjustment >= 0 {
+        value.checked_mul(10u128.checked_pow(adjustment as u32)?)?
+    } else {
+        match 10u128.checked_pow(adjustment.unsigned_abs()) {
+            Some(divisor) => value / divisor,
+            // Divisor beyond u128 range: value rounds down to zero
+            None => 0,
+        }
+    };
+
+    u64::try_from(result).ok()
+}
 
-    let price = i64::from_le_bytes(data[208..216].try_into().ok()?);
-    let conf = u64::from_le_bytes(data[216..224].try_into().ok()?);
-    let expo = i32::from_le_bytes(data[224..228].try_into().ok()?);
-    let publish_time = i64::from_le_bytes(data[232..240].try_into().ok()?);
+fn read_u32(data: &[u8], offset: usize) -> std::result::Result<u32, LegasiError> {
+    data.get(offset..offset + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or(LegasiError::InvalidOracle)
+}
+
+fn read_i32(data: &[u8], offset: usize) -> std::result::Result<i32, LegasiError> {
+    read_u32(data, offset).map(|v| v as i32)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> std::result::Result<u64, LegasiError> {
+    data.get(offset..offset + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(LegasiError::InvalidOracle)
+}
+
+fn read_i64(data: &[u8], offset: usize) -> std::result::Result<i64, LegasiError> {
+    read_u64(data, offset).map(|v| v as i64)
+}
+
+/// Parse a legacy Pyth price account
+/// Validates magic, version, account type and aggregate trading status
+pub fn parse_legacy_price_account(data: &[u8]) -> std::result::Result<PythPrice, LegasiError> {
+    use legacy_layout::*;
+
+    if data.len() < MIN_SIZE {
+        return Err(LegasiError::InvalidOracle);
+    }
+    if read_u32(data, MAGIC_OFFSET)? != MAGIC
+        || read_u32(data, VERSION_OFFSET)? != VERSION_2
+        || read_u32(data, ACCOUNT_TYPE_OFFSET)? != ACCOUNT_TYPE_PRICE
+    {
+        return Err(LegasiError::InvalidOracle);
+    }
+    if read_u32(data, AGG_STATUS_OFFSET)? != STATUS_TRADING {
+        return Err(LegasiError::OracleNotTrading);
+    }
 
-    Some(PythPrice {
-        price,
-        conf,
-        expo,
-        publish_time,
+    Ok(PythPrice {
+        price: read_i64(data, AGG_PRICE_OFFSET)?,
+        conf: read_u64(data, AGG_CONF_OFFSET)?,
+        expo: read_i32(data, EXPO_OFFSET)?,
+        publish_time: read_i64(data, TIMESTAMP_OFFSET)?,
     })
 }
 
+/// Parse a Pyth pull oracle `PriceUpdateV2` account
+/// Returns the feed id alongside the price; only fully verified updates are accepted
+pub fn parse_price_update_v2(
+    data: &[u8],
+) -> std::result::Result<([u8; 32], PythPrice), LegasiError> {
+    use price_update_v2_layout::*;
+
+    if data.get(..8) != Some(&DISCRIMINATOR[..]) {
+        return Err(LegasiError::InvalidOracle);
+    }
+
+    let message_offset = match data.get(VERIFICATION_LEVEL_OFFSET) {
+        Some(&VERIFICATION_FULL) => VERIFICATION_LEVEL_OFFSET + 1,
+        // Partially verified updates are not trusted for risk decisions
+        Some(&VERIFICATION_PARTIAL) => return Err(LegasiError::OracleNotTrading),
+        _ => return Err(LegasiError::InvalidOracle),
+    };
+    if data.len() < message_offset + PRICE_MESSAGE_SIZE {
+        return Err(LegasiError::InvalidOracle);
+    }
+
+    let mut feed_id = [0u8; 32];
+    feed_id.copy_from_slice(&data[message_offset..message_offset + 32]);
+    let price = read_i64(data, message_offset + 32)?;
+    let conf = read_u64(data, message_offset + 40)?;
+    let expo = read_i32(data, message_offset + 48)?;
+    let publish_time = read_i64(data, message_offset + 52)?;
+
+    Ok((
+        feed_id,
+        PythPrice {
+            price,
+            conf,
+            expo,
+            publish_time,
+        },
+    ))
+}
+
+/// Load and validate a Pyth price from an account
+///
+/// `expected_oracle` is the asset's configured oracle (`Collateral.oracle` /
+/// `Borrowable.oracle`): the price account address for legacy feeds, or the
+/// 32-byte feed id for pull feeds.
+pub fn load_pyth_price(account: &AccountInfo, expected_oracle: &Pubkey) -> Result<PythPrice> {
+    let data = account.try_borrow_data()?;
+
+    if *account.owner == program_ids::PYTH_RECEIVER {
+        let (feed_id, price) = parse_price_update_v2(&data)?;
+        require!(
+            feed_id == expected_oracle.to_bytes(),
+            LegasiError::OracleFeedMismatch
+        );
+        Ok(price)
+    } else if *account.owner == program_ids::PYTH_ORACLE_MAINNET
+        || *account.owner == program_ids::PYTH_ORACLE_DEVNET
+    {
+        require_keys_eq!(
+            account.key(),
+            *expected_oracle,
+            LegasiError::OracleFeedMismatch
+        );
+        Ok(parse_legacy_price_account(&data)?)
+    } else {
+        err!(LegasiError::InvalidOracleOwner)
+    }
+}
+
 /// Maximum price age before considered stale (seconds)
 pub const MAX_PRICE_AGE: i64 = 60; // 1 minute
 
@@ -127,6 +285,42 @@ pub const MAX_CONFIDENCE_BPS: u64 = 500; // 5%
 mod tests {
     use super::*;
 
+    /// Build a legacy price account fixture
+    fn legacy_fixture(price: i64, conf: u64, expo: i32, status: u32, timestamp: i64) -> Vec<u8> {
+        use legacy_layout::*;
+
+        let mut data = vec![0u8; 3312]; // Real price account size
+        data[MAGIC_OFFSET..MAGIC_OFFSET + 4].copy_from_slice(&MAGIC.to_le_bytes());
+        data[VERSION_OFFSET..VERSION_OFFSET + 4].copy_from_slice(&VERSION_2.to_le_bytes());
+        data[ACCOUNT_TYPE_OFFSET..ACCOUNT_TYPE_OFFSET + 4]
+            .copy_from_slice(&ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[EXPO_OFFSET..EXPO_OFFSET + 4].copy_from_slice(&expo.to_le_bytes());
+        data[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + 8].copy_from_slice(&timestamp.to_le_bytes());
+        data[AGG_PRICE_OFFSET..AGG_PRICE_OFFSET + 8].copy_from_slice(&price.to_le_bytes());
+        data[AGG_CONF_OFFSET..AGG_CONF_OFFSET + 8].copy_from_slice(&conf.to_le_bytes());
+        data[AGG_STATUS_OFFSET..AGG_STATUS_OFFSET + 4].copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    /// Build a fully verified PriceUpdateV2 account fixture
+    fn price_update_fixture(feed_id: [u8; 32], price: i64, conf: u64, expo: i32) -> Vec<u8> {
+        use price_update_v2_layout::*;
+
+        let mut data = DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[7u8; 32]); // write authority
+        data.push(VERIFICATION_FULL);
+        data.extend_from_slice(&feed_id);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&expo.to_le_bytes());
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // publish_time
+        data.extend_from_slice(&1_699_999_999i64.to_le_bytes()); // prev_publish_time
+        data.extend_from_slice(&price.to_le_bytes()); // ema_price
+        data.extend_from_slice(&conf.to_le_bytes()); // ema_conf
+        data.extend_from_slice(&300_000_000u64.to_le_bytes()); // posted_slot
+        data
+    }
+
     #[test]
     fn test_price_conversion() {
         // SOL at $150.00 with expo -8
@@ -138,7 +332,38 @@ mod tests {
         };
 
         // Should convert to 150_000_000 (150 * 10^6)
-        assert_eq!(price.to_usd_6dec(), 150_000_000);
+        assert_eq!(price.to_usd_6dec(), Some(150_000_000));
+        assert_eq!(price.conf_to_usd_6dec(), Some(500_000));
+    }
+
+    #[test]
+    fn test_price_conversion_wide_exponents() {
+        let mut price = PythPrice {
+            price: 15,
+            conf: 0,
+            expo: 1, // $150
+            publish_time: 0,
+        };
+        assert_eq!(price.to_usd_6dec(), Some(150_000_000));
+
+        // -12 decimals: $1.000000000001 truncates to $1.000000
+        price.price = 1_000_000_000_001;
+        price.expo = -12;
+        assert_eq!(price.to_usd_6dec(), Some(1_000_000));
+
+        // Overflow is reported, not silently zeroed
+        price.price = i64::MAX;
+        price.expo = 20;
+        assert_eq!(price.to_usd_6dec(), None);
+
+        // Price that rounds to zero is rejected
+        price.price = 5;
+        price.expo = -50;
+        assert_eq!(price.to_usd_6dec(), None);
+
+        price.price = -1;
+        price.expo = -8;
+        assert_eq!(price.to_usd_6dec(), None);
     }
 
     #[test]
@@ -152,4 +377,149 @@ mod tests {
 
         assert_eq!(price.confidence_bps(), 100); // 1% = 100 bps
     }
+
+    #[test]
+    fn test_parse_legacy_price_account() {
+        let data = legacy_fixture(15_000_000_000, 50_000_000, -8, 1, 1_700_000_000);
+        let price = parse_legacy_price_account(&data).unwrap();
+        assert_eq!(price.price, 15_000_000_000);
+        assert_eq!(price.conf, 50_000_000);
+        assert_eq!(price.expo, -8);
+        assert_eq!(price.publish_time, 1_700_000_000);
+        assert_eq!(price.to_usd_6dec(), Some(150_000_000));
+    }
+
+    #[test]
+    fn test_parse_legacy_rejects_bad_accounts() {
+        let good = legacy_fixture(15_000_000_000, 50_000_000, -8, 1, 1_700_000_000);
+
+        let mut bad_magic = good.clone();
+        bad_magic[0] = 0;
+        assert!(matches!(
+            parse_legacy_price_account(&bad_magic),
+            Err(LegasiError::InvalidOracle)
+        ));
+
+        let mut bad_version = good.clone();
+        bad_version[legacy_layout::VERSION_OFFSET] = 1;
+        assert!(matches!(
+            parse_legacy_price_account(&bad_version),
+            Err(LegasiError::InvalidOracle)
+        ));
+
+        // Product account (type 2) instead of a price account
+        let mut bad_type = good.clone();
+        bad_type[legacy_layout::ACCOUNT_TYPE_OFFSET] = 2;
+        assert!(matches!(
+            parse_legacy_price_account(&bad_type),
+            Err(LegasiError::InvalidOracle)
+        ));
+
+        assert!(matches!(
+            parse_legacy_price_account(&good[..200]),
+            Err(LegasiError::InvalidOracle)
+        ));
+
+        // Halted feed (status 2)
+        let halted = legacy_fixture(15_000_000_000, 50_000_000, -8, 2, 1_700_000_000);
+        assert!(matches!(
+            parse_legacy_price_account(&halted),
+            Err(LegasiError::OracleNotTrading)
+        ));
+    }
+
+    #[test]
+    fn test_parse_price_update_v2() {
+        let feed_id = [0xef; 32];
+        let data = price_update_fixture(feed_id, 6_500_000_000_000, 3_000_000_000, -8);
+        let (parsed_feed, price) = parse_price_update_v2(&data).unwrap();
+        assert_eq!(parsed_feed, feed_id);
+        assert_eq!(price.publish_time, 1_700_000_000);
+        assert_eq!(price.to_usd_6dec(), Some(65_000_000_000)); // $65,000
+        assert_eq!(price.conf_to_usd_6dec(), Some(30_000_000)); // $30
+    }
+
+    #[test]
+    fn test_parse_price_update_v2_rejects_bad_accounts() {
+        let good = price_update_fixture([1; 32], 100_000_000, 10_000, -8);
+
+        let mut bad_discriminator = good.clone();
+        bad_discriminator[0] ^= 0xff;
+        assert!(matches!(
+            parse_price_update_v2(&bad_discriminator),
+            Err(LegasiError::InvalidOracle)
+        ));
+
+        // Partially verified update: extra signature-count byte, not trusted
+        let mut partial = good[..41].to_vec();
+        partial[price_update_v2_layout::VERIFICATION_LEVEL_OFFSET] =
+            price_update_v2_layout::VERIFICATION_PARTIAL;
+        partial.push(3);
+        partial.extend_from_slice(&good[41..]);
+        assert!(matches!(
+            parse_price_update_v2(&partial),
+            Err(LegasiError::OracleNotTrading)
+        ));
+
+        assert!(matches!(
+            parse_price_update_v2(&good[..60]),
+            Err(LegasiError::InvalidOracle)
+        ));
+    }
+
+    #[test]
+    fn test_load_pyth_price_validates_owner_and_feed() {
+        let feed_id = [9u8; 32];
+        let expected = Pubkey::new_from_array(feed_id);
+        let key = Pubkey::new_unique();
+        let mut lamports = 0u64;
+
+        let mut data = price_update_fixture(feed_id, 100_000_000, 10_000, -8);
+        let receiver = program_ids::PYTH_RECEIVER;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &receiver,
+            false,
+            0,
+        );
+        assert!(load_pyth_price(&account, &expected).is_ok());
+        assert!(load_pyth_price(&account, &Pubkey::new_unique()).is_err());
+
+        // Same bytes owned by an arbitrary program are rejected
+        let mut lamports = 0u64;
+        let mut data = price_update_fixture(feed_id, 100_000_000, 10_000, -8);
+        let spoofer = Pubkey::new_unique();
+        let spoofed = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &spoofer,
+            false,
+            0,
+        );
+        assert!(load_pyth_price(&spoofed, &expected).is_err());
+
+        // Legacy accounts are matched by address
+        let mut lamports = 0u64;
+        let mut data = legacy_fixture(15_000_000_000, 50_000_000, -8, 1, 1_700_000_000);
+        let legacy_owner = program_ids::PYTH_ORACLE_MAINNET;
+        let legacy = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &legacy_owner,
+            false,
+            0,
+        );
+        assert!(load_pyth_price(&legacy, &key).is_ok());
+        assert!(load_pyth_price(&legacy, &expected).is_err());
+    }
 }
@@ -0,0 +1,126 @@
+//! Protocol roles
+//!
+//! The super admin (`Protocol.admin`) assigns the narrower roles and can be
+//! rotated with a two-step `propose_admin` / `accept_admin` transfer.
+//! Instructions check the narrowest role that covers them.
+
+use anchor_lang::prelude::*;
+
+use crate::errors::LegasiError;
+use crate::state::{Protocol, ProtocolRole};
+
+impl Protocol {
+    /// Key currently holding `role`
+    pub fn role_holder(&self, role: ProtocolRole) -> Pubkey {
+        match role {
+            ProtocolRole::Risk => self.risk_admin,
+            ProtocolRole::Oracle => self.oracle_admin,
+            ProtocolRole::PauseGuardian => self.pause_guardian,
+            ProtocolRole::TreasuryManager => self.treasury_manager,
+        }
+    }
+
+    /// Whether `key` holds `role`
+    pub fn has_role(&self, role: ProtocolRole, key: &Pubkey) -> bool {
+        self.role_holder(role) == *key
+    }
+
+    /// Assign `role` to `key`
+    pub fn set_role(&mut self, role: ProtocolRole, key: Pubkey) -> Result<()> {
+        require!(key != Pubkey::default(), LegasiError::Unauthorized);
+        match role {
+            ProtocolRole::Risk => self.risk_admin = key,
+            ProtocolRole::Oracle => self.oracle_admin = key,
+            ProtocolRole::PauseGuardian => self.pause_guardian = key,
+            ProtocolRole::TreasuryManager => self.treasury_manager = key,
+        }
+        Ok(())
+    }
+
+    /// Give every role to the initial admin
+    pub fn init_roles(&mut self, admin: Pubkey) {
+        self.admin = admin;
+        self.pending_admin = Pubkey::default();
+        self.risk_admin = admin;
+        self.oracle_admin = admin;
+        self.pause_guardian = admin;
+        self.treasury_manager = admin;
+    }
+
+    /// Complete a pending admin transfer
+    pub fn accept_admin(&mut self, signer: &Pubkey) -> Result<()> {
+        require!(
+            self.pending_admin != Pubkey::default() && self.pending_admin == *signer,
+            LegasiError::NoPendingAdmin
+        );
+        self.admin = self.pending_admin;
+        self.pending_admin = Pubkey::default();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol(admin: Pubkey) -> Protocol {
+        let mut protocol = Protocol {
+            admin: Pubkey::default(),
+            treasury: Pubkey::default(),
+            insurance_fund: 0,
+            total_collateral_usd: 0,
+            total_borrowed_usd: 0,
+            paused: false,
+            pending_admin: Pubkey::default(),
+            risk_admin: Pubkey::default(),
+            oracle_admin: Pubkey::default(),
+            pause_guardian: Pubkey::default(),
+            treasury_manager: Pubkey::default(),
+            timelock_delay: 0,
+            next_change_id: 0,
+            pause_flags: 0,
+            collateral_count: 0,
+            borrowable_count: 0,
+            stats_updated_at: 0,
+            bump: 0,
+        };
+        protocol.init_roles(admin);
+        protocol
+    }
+
+    #[test]
+    fn test_roles_are_independent() {
+        let admin = Pubkey::new_unique();
+        let guardian = Pubkey::new_unique();
+        let mut protocol = protocol(admin);
+        assert!(protocol.has_role(ProtocolRole::PauseGuardian, &admin));
+
+        protocol
+            .set_role(ProtocolRole::PauseGuardian, guardian)
+            .unwrap();
+        assert!(protocol.has_role(ProtocolRole::PauseGuardian, &guardian));
+        assert!(!protocol.has_role(ProtocolRole::PauseGuardian, &admin));
+        assert!(!protocol.has_role(ProtocolRole::Oracle, &guardian));
+        assert!(protocol
+            .set_role(ProtocolRole::Risk, Pubkey::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_two_step_admin_transfer() {
+        let admin = Pubkey::new_unique();
+        let next = Pubkey::new_unique();
+        let mut protocol = protocol(admin);
+
+        // Nothing pending
+        assert!(protocol.accept_admin(&next).is_err());
+
+        protocol.pending_admin = next;
+        assert!(protocol.accept_admin(&Pubkey::new_unique()).is_err());
+        assert_eq!(protocol.admin, admin);
+
+        protocol.accept_admin(&next).unwrap();
+        assert_eq!(protocol.admin, next);
+        assert_eq!(protocol.pending_admin, Pubkey::default());
+    }
+}
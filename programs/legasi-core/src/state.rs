@@ -1,29 +1,163 @@
 use anchor_lang::prelude::*;
 
-/// Supported asset types
-/// Collaterals: SOL, cbBTC
-/// Borrowables: USDC, EURC
+use crate::constants::{MAX_ORACLE_SOURCES, MAX_PUSH_PUBLISHERS, PRICE_OBSERVATION_CAPACITY};
+
+/// Kind of a registered asset
+/// Assets are identified by mint; the kind only drives asset-class behaviour
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
 #[repr(u8)]
-pub enum AssetType {
-    // Collaterals
-    SOL = 0,   // Native SOL
-    CbBTC = 1, // Coinbase wrapped BTC
-    // Borrowables
-    USDC = 2, // USD Coin
-    EURC = 3, // Euro Coin
+pub enum AssetKind {
+    /// Native SOL, held as lamports in the position's SOL vault
+    Native = 0,
+    /// Volatile SPL token (cbBTC, wETH)
+    Volatile = 1,
+    /// Liquid staking token (JitoSOL, mSOL)
+    LiquidStaking = 2,
+    /// Fiat stablecoin (USDC, USDT, EURC, PYUSD)
+    Stablecoin = 3,
+}
+
+/// Efficiency Mode categories for correlated assets
+/// Higher LTV allowed when collateral and borrow are in same category
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace, Default,
+)]
+#[repr(u8)]
+pub enum EModeCategory {
+    /// No eMode (standard parameters)
+    #[default]
+    None = 0,
+    /// Stablecoins (USDC, USDT, EURC) - highly correlated
+    Stablecoins = 1,
+    /// ETH ecosystem (ETH, stETH, wstETH, cbETH)
+    EthCorrelated = 2,
+    /// SOL ecosystem (SOL, JitoSOL, mSOL)
+    SolCorrelated = 3,
+    /// BTC ecosystem (BTC, wBTC, cbBTC)
+    BtcCorrelated = 4,
+}
+
+/// How a position's collateral backs its debt
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace, Default,
+)]
+#[repr(u8)]
+pub enum MarginMode {
+    /// Every collateral backs every borrow
+    #[default]
+    Cross = 0,
+    /// A single collateral backs a single borrow asset. Further pairs go in
+    /// other sub-accounts, so their risk and GAD stay separate
+    Isolated = 1,
+}
+
+/// Registry metadata for a listed asset (instruction argument)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct AssetMetadata {
+    pub decimals: u8,
+    pub kind: AssetKind,
+    pub emode_category: EModeCategory,
 }
 
 /// Protocol global state
 #[account]
 #[derive(InitSpace)]
 pub struct Protocol {
+    /// Super admin: assigns roles and transfers itself via propose/accept
     pub admin: Pubkey,
     pub treasury: Pubkey,
     pub insurance_fund: u64,
+    /// USD value of all collateral as of `stats_updated_at` (refresh_protocol_stats)
     pub total_collateral_usd: u64,
+    /// USD value of all borrowed principal as of `stats_updated_at`
     pub total_borrowed_usd: u64,
+    /// Set while any action is paused (see `pause_flags`)
     pub paused: bool,
+    /// Proposed super admin (default when no transfer is pending)
+    pub pending_admin: Pubkey,
+    /// Registers assets and manages lending/LP configuration
+    pub risk_admin: Pubkey,
+    /// Manages price feeds and oracle sources
+    pub oracle_admin: Pubkey,
+    /// Pauses and unpauses the protocol
+    pub pause_guardian: Pubkey,
+    /// Sets the treasury account
+    pub treasury_manager: Pubkey,
+    /// Delay before a queued parameter change can execute (seconds)
+    pub timelock_delay: i64,
+    /// Id of the next queued parameter change
+    pub next_change_id: u64,
+    /// Paused actions protocol-wide (`PAUSE_*` flags)
+    pub pause_flags: u16,
+    /// Registered collateral assets
+    pub collateral_count: u16,
+    /// Registered borrowable assets
+    pub borrowable_count: u16,
+    /// Last refresh of the USD totals
+    pub stats_updated_at: i64,
+    pub bump: u8,
+}
+
+/// Protocol roles assignable by the super admin
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProtocolRole {
+    Risk,
+    Oracle,
+    PauseGuardian,
+    TreasuryManager,
+}
+
+/// Timelocked parameter change
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum ParameterChange {
+    CollateralRisk {
+        max_ltv_bps: u16,
+        liquidation_threshold_bps: u16,
+        liquidation_bonus_bps: u16,
+    },
+    CollateralOracle {
+        oracle: Pubkey,
+    },
+    BorrowableInterestRate {
+        interest_rate_bps: u16,
+    },
+    BorrowableOracle {
+        oracle: Pubkey,
+    },
+    /// E-mode category and active flag
+    CollateralStatus {
+        emode_category: EModeCategory,
+        is_active: bool,
+    },
+    BorrowableStatus {
+        emode_category: EModeCategory,
+        is_active: bool,
+    },
+    TimelockDelay {
+        delay_seconds: i64,
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum ChangeStatus {
+    Queued,
+    Executed,
+    Cancelled,
+}
+
+/// Parameter change waiting out the timelock
+#[account]
+#[derive(InitSpace)]
+pub struct QueuedChange {
+    pub id: u64,
+    /// Collateral or Borrowable account the change applies to (Protocol for delay changes)
+    pub target: Pubkey,
+    pub change: ParameterChange,
+    pub proposer: Pubkey,
+    pub queued_at: i64,
+    /// Earliest execution time
+    pub eta: i64,
+    pub status: ChangeStatus,
     pub bump: u8,
 }
 
@@ -33,16 +167,79 @@ pub struct Protocol {
 pub struct Collateral {
     pub mint: Pubkey,
     pub oracle: Pubkey,
+    /// Target max LTV (reached at `ltv_ramp_end_time` while a decrease is ramping)
     pub max_ltv_bps: u16,
     pub liquidation_threshold_bps: u16,
     pub liquidation_bonus_bps: u16,
     pub decimals: u8,
     pub is_active: bool,
+    /// Deposited across all positions (native units)
     pub total_deposited: u64,
-    pub asset_type: AssetType,
+    pub kind: AssetKind,
+    pub emode_category: EModeCategory,
+    /// Paused actions for this asset (`PAUSE_*` flags)
+    pub pause_flags: u16,
+    /// Max LTV when the current decrease started
+    pub ltv_ramp_start_bps: u16,
+    pub ltv_ramp_start_time: i64,
+    pub ltv_ramp_end_time: i64,
     pub bump: u8,
 }
 
+/// Collateral config update (instruction argument, `None` keeps the current value)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct CollateralConfigUpdate {
+    pub oracle: Option<Pubkey>,
+    pub max_ltv_bps: Option<u16>,
+    pub liquidation_threshold_bps: Option<u16>,
+    pub liquidation_bonus_bps: Option<u16>,
+    pub emode_category: Option<EModeCategory>,
+    pub is_active: Option<bool>,
+}
+
+/// Borrowable config update (instruction argument, `None` keeps the current value)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct BorrowableConfigUpdate {
+    pub oracle: Option<Pubkey>,
+    pub interest_rate_bps: Option<u16>,
+    pub emode_category: Option<EModeCategory>,
+    pub is_active: Option<bool>,
+}
+
+/// Program updating asset aggregates through core (see `accounting`)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccountingSource {
+    Lending,
+    Lp,
+    Gad,
+    Leverage,
+    Flash,
+}
+
+/// Change to a collateral's deposits (instruction argument)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollateralChange {
+    Deposited(u64),
+    /// Withdrawn by the owner or liquidated
+    Withdrawn(u64),
+}
+
+/// Change to a borrowable's liquidity or debt (instruction argument)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BorrowableChange {
+    /// Liquidity supplied by an LP
+    Supplied(u64),
+    /// Liquidity withdrawn by an LP
+    Withdrawn(u64),
+    Borrowed(u64),
+    /// `principal` of debt cleared, `received` tokens returned to the vault
+    /// (interest included; zero when debt is written down by GAD)
+    Repaid {
+        principal: u64,
+        received: u64,
+    },
+}
+
 /// Borrowable asset configuration
 #[account]
 #[derive(InitSpace)]
@@ -52,9 +249,14 @@ pub struct Borrowable {
     pub interest_rate_bps: u16,
     pub decimals: u8,
     pub is_active: bool,
+    /// Outstanding borrowed principal across all positions (native units)
     pub total_borrowed: u64,
+    /// Liquidity supplied and not lent out (native units)
     pub total_available: u64,
-    pub asset_type: AssetType,
+    pub kind: AssetKind,
+    pub emode_category: EModeCategory,
+    /// Paused actions for this asset (`PAUSE_*` flags)
+    pub pause_flags: u16,
     pub bump: u8,
 }
 
@@ -62,132 +264,95 @@ pub struct Borrowable {
 #[account]
 #[derive(InitSpace)]
 pub struct PriceFeed {
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub price_usd_6dec: u64,
     pub last_update: i64,
     pub confidence: u64,
+    /// Exponential moving average of the spot price
+    pub ema_price_usd_6dec: u64,
+    /// Next slot to write in `observations`
+    pub observation_index: u8,
+    /// Number of populated observations
+    pub observation_count: u8,
+    /// Ring buffer of recent spot prices (used for TWAP)
+    pub observations: [PriceObservation; PRICE_OBSERVATION_CAPACITY],
+    /// Configured oracle sources (unordered, see `aggregate_sources`)
+    pub sources: [OracleSource; MAX_ORACLE_SOURCES],
+    /// Max deviation of a source from the median (bps) before it is ignored
+    pub max_deviation_bps: u16,
+    /// Fresh, agreeing sources required to publish a median
+    pub min_sources: u8,
+    /// Circuit breaker: max price move per interval (bps, 0 = disabled)
+    pub max_move_bps: u16,
+    /// Circuit breaker interval (seconds)
+    pub move_interval_seconds: i64,
+    /// Price at the start of the current interval
+    pub reference_price_usd_6dec: u64,
+    pub reference_timestamp: i64,
+    /// Set on an abnormal move - blocks borrows and withdrawals for this asset
+    pub circuit_breaker_tripped: bool,
+    pub tripped_at: i64,
+    /// k applied to `confidence` for conservative valuations (bps, 10000 = 1x)
+    pub confidence_multiplier_bps: u16,
     pub bump: u8,
 }
 
-/// User lending position (multi-collateral, multi-borrow)
-#[account]
-#[derive(InitSpace)]
-pub struct Position {
-    pub owner: Pubkey,
-    #[max_len(8)]
-    pub collaterals: Vec<CollateralDeposit>,
-    #[max_len(4)]
-    pub borrows: Vec<BorrowedAmount>,
-    pub last_update: i64,
-    pub last_gad_crank: i64,
-    pub gad_enabled: bool,
-    pub total_gad_liquidated_usd: u64,
-    pub reputation: Reputation,
-    pub bump: u8,
-}
-
-/// Single collateral deposit entry
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
-pub struct CollateralDeposit {
-    pub asset_type: AssetType,
-    pub amount: u64,
-}
-
-/// Single borrow entry
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
-pub struct BorrowedAmount {
-    pub asset_type: AssetType,
-    pub amount: u64,
-    pub accrued_interest: u64,
+/// Single spot price observation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct PriceObservation {
+    pub price_usd_6dec: u64,
+    pub timestamp: i64,
 }
 
-/// On-chain reputation score
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, Default)]
-pub struct Reputation {
-    pub successful_repayments: u32,
-    pub total_repaid_usd: u64,
-    pub gad_events: u32,
-    pub account_age_days: u32,
+/// Kind of oracle backing a price source
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace, Default,
+)]
+#[repr(u8)]
+pub enum OracleSourceKind {
+    /// Unused slot
+    #[default]
+    None = 0,
+    /// Pyth legacy price account or pull feed id
+    Pyth = 1,
+    /// Switchboard On-Demand pull feed
+    Switchboard = 2,
+    /// Admin/keeper pushed price
+    Push = 3,
 }
 
-impl Reputation {
-    pub fn get_score(&self) -> u32 {
-        let base = std::cmp::min(self.successful_repayments * 50, 500);
-        let age_bonus = std::cmp::min(self.account_age_days / 30 * 10, 100);
-        base.saturating_add(age_bonus)
-            .saturating_sub(self.gad_events * 100)
-    }
-
-    /// Returns LTV bonus in basis points based on reputation
-    pub fn get_ltv_bonus_bps(&self) -> u16 {
-        match self.get_score() {
-            s if s >= 400 => 500, // +5% LTV
-            s if s >= 200 => 300, // +3% LTV
-            s if s >= 100 => 100, // +1% LTV
-            _ => 0,
-        }
-    }
+/// A configured oracle source and its latest reading
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct OracleSource {
+    pub kind: OracleSourceKind,
+    /// Pyth price account / feed id or Switchboard feed (unused for Push)
+    pub address: Pubkey,
+    pub price_usd_6dec: u64,
+    pub confidence: u64,
+    pub last_update: i64,
 }
 
-/// LP pool for a borrowable asset
-#[account]
-#[derive(InitSpace)]
-pub struct LpPool {
-    pub borrowable_mint: Pubkey,
-    pub lp_token_mint: Pubkey,
-    pub total_deposits: u64,
-    pub total_shares: u64,
-    pub total_borrowed: u64,
-    pub interest_earned: u64,
-    pub bump: u8,
+/// Oracle source configuration (instruction argument)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct OracleSourceConfig {
+    pub kind: OracleSourceKind,
+    pub address: Pubkey,
 }
 
-/// Agent-specific position for x402 and autonomous operations
-/// Extends the base Position with agent-specific features
+/// Push oracle: a quorum of publishers feeds a price feed's push source
 #[account]
 #[derive(InitSpace)]
-pub struct AgentConfig {
-    /// The position this config extends
-    pub position: Pubkey,
-    /// Human operator (can intervene if needed)
-    pub operator: Pubkey,
-    /// Maximum USDC that can be borrowed per day
-    pub daily_borrow_limit: u64,
-    /// Amount borrowed in current period
-    pub daily_borrowed: u64,
-    /// Period reset timestamp
-    pub period_start: i64,
-    /// Auto-repay incoming USDC to reduce debt
-    pub auto_repay_enabled: bool,
-    /// x402 payment endpoint enabled
-    pub x402_enabled: bool,
-    /// Webhook URL for low balance alerts (stored off-chain, this is just a flag)
-    pub alerts_enabled: bool,
-    /// Minimum collateral ratio before alert (in bps)
-    pub alert_threshold_bps: u16,
+pub struct PushOracle {
+    pub price_feed: Pubkey,
+    pub publishers: [Pubkey; MAX_PUSH_PUBLISHERS],
+    pub publisher_count: u8,
+    /// Submissions required to publish a round
+    pub quorum: u8,
+    /// Current round number (increments when a round is published or expires)
+    pub round: u64,
+    pub round_started_at: i64,
+    /// Bit i set when publishers[i] submitted this round
+    pub submitted_mask: u16,
+    pub submissions: [u64; MAX_PUSH_PUBLISHERS],
     pub bump: u8,
 }
-
-impl AgentConfig {
-    /// Check if agent can borrow more today
-    pub fn can_borrow(&self, amount: u64, current_time: i64) -> bool {
-        // Reset daily limit if new period
-        let seconds_per_day: i64 = 86400;
-        if current_time - self.period_start >= seconds_per_day {
-            return amount <= self.daily_borrow_limit;
-        }
-
-        self.daily_borrowed.saturating_add(amount) <= self.daily_borrow_limit
-    }
-
-    /// Record a borrow against daily limit
-    pub fn record_borrow(&mut self, amount: u64, current_time: i64) {
-        let seconds_per_day: i64 = 86400;
-        if current_time - self.period_start >= seconds_per_day {
-            self.period_start = current_time;
-            self.daily_borrowed = amount;
-        } else {
-            self.daily_borrowed = self.daily_borrowed.saturating_add(amount);
-        }
-    }
-}
@@ -0,0 +1,162 @@
+//! Switchboard On-Demand Integration
+//!
+//! Reads the latest aggregated result from a Switchboard On-Demand
+//! `PullFeedAccountData` account. Values are i128 with 18 decimals.
+
+use anchor_lang::prelude::*;
+
+use crate::errors::LegasiError;
+
+/// Switchboard On-Demand program (mainnet and devnet)
+pub const SWITCHBOARD_ON_DEMAND: Pubkey = pubkey!("SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv");
+
+/// `PullFeedAccountData` layout (repr(C), after the 8-byte discriminator)
+pub mod pull_feed_layout {
+    /// sha256("account:PullFeedAccountData")[..8]
+    pub const DISCRIMINATOR: [u8; 8] = [196, 27, 108, 196, 10, 215, 219, 40];
+    /// Decimals of `CurrentResult` values
+    pub const PRECISION: u32 = 18;
+
+    pub const LAST_UPDATE_TIMESTAMP_OFFSET: usize = 8 + 2208;
+    pub const RESULT_VALUE_OFFSET: usize = 8 + 2256;
+    pub const RESULT_STD_DEV_OFFSET: usize = 8 + 2272;
+    pub const RESULT_NUM_SAMPLES_OFFSET: usize = 8 + 2352;
+    /// Minimum account size covering `CurrentResult`
+    pub const MIN_SIZE: usize = 8 + 2384;
+}
+
+/// Latest Switchboard result
+#[derive(Clone, Copy, Debug)]
+pub struct SwitchboardPrice {
+    /// Value with 18 decimals
+    pub value: i128,
+    /// Standard deviation across oracle samples (18 decimals)
+    pub std_dev: i128,
+    /// Number of oracle samples in the result
+    pub num_samples: u8,
+    /// Last update timestamp
+    pub publish_time: i64,
+}
+
+impl SwitchboardPrice {
+    /// Convert the value to our standard 6-decimal USD format
+    pub fn to_usd_6dec(&self) -> Option<u64> {
+        if self.value <= 0 {
+            return None;
+        }
+        to_6dec(self.value).filter(|p| *p > 0)
+    }
+
+    /// Convert the standard deviation to 6-decimal USD (used as confidence)
+    pub fn std_dev_to_usd_6dec(&self) -> Option<u64> {
+        to_6dec(self.std_dev.max(0))
+    }
+}
+
+fn to_6dec(value: i128) -> Option<u64> {
+    let divisor = 10i128.pow(pull_feed_layout::PRECISION - 6);
+    u64::try_from(value / divisor).ok()
+}
+
+fn read_i128(data: &[u8], offset: usize) -> std::result::Result<i128, LegasiError> {
+    data.get(offset..offset + 16)
+        .and_then(|b| b.try_into().ok())
+        .map(i128::from_le_bytes)
+        .ok_or(LegasiError::InvalidOracle)
+}
+
+/// Parse a Switchboard On-Demand pull feed account
+pub fn parse_pull_feed(data: &[u8]) -> std::result::Result<SwitchboardPrice, LegasiError> {
+    use pull_feed_layout::*;
+
+    if data.len() < MIN_SIZE || data[..8] != DISCRIMINATOR {
+        return Err(LegasiError::InvalidOracle);
+    }
+
+    let num_samples = data[RESULT_NUM_SAMPLES_OFFSET];
+    if num_samples == 0 {
+        return Err(LegasiError::OracleNotTrading);
+    }
+
+    let publish_time = i64::from_le_bytes(
+        data[LAST_UPDATE_TIMESTAMP_OFFSET..LAST_UPDATE_TIMESTAMP_OFFSET + 8]
+            .try_into()
+            .map_err(|_| LegasiError::InvalidOracle)?,
+    );
+
+    Ok(SwitchboardPrice {
+        value: read_i128(data, RESULT_VALUE_OFFSET)?,
+        std_dev: read_i128(data, RESULT_STD_DEV_OFFSET)?,
+        num_samples,
+        publish_time,
+    })
+}
+
+/// Load and validate a Switchboard price from an account
+pub fn load_switchboard_price(account: &AccountInfo) -> Result<SwitchboardPrice> {
+    require_keys_eq!(
+        *account.owner,
+        SWITCHBOARD_ON_DEMAND,
+        LegasiError::InvalidOracleOwner
+    );
+    let data = account.try_borrow_data()?;
+    Ok(parse_pull_feed(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pull_feed_layout::*;
+    use super::*;
+
+    fn pull_feed_fixture(value: i128, std_dev: i128, num_samples: u8, timestamp: i64) -> Vec<u8> {
+        let mut data = vec![0u8; 3208]; // Real account size
+        data[..8].copy_from_slice(&DISCRIMINATOR);
+        data[LAST_UPDATE_TIMESTAMP_OFFSET..LAST_UPDATE_TIMESTAMP_OFFSET + 8]
+            .copy_from_slice(&timestamp.to_le_bytes());
+        data[RESULT_VALUE_OFFSET..RESULT_VALUE_OFFSET + 16].copy_from_slice(&value.to_le_bytes());
+        data[RESULT_STD_DEV_OFFSET..RESULT_STD_DEV_OFFSET + 16]
+            .copy_from_slice(&std_dev.to_le_bytes());
+        data[RESULT_NUM_SAMPLES_OFFSET] = num_samples;
+        data
+    }
+
+    #[test]
+    fn test_parse_pull_feed() {
+        // $150.25 +/- $0.10
+        let data = pull_feed_fixture(
+            150_250_000_000_000_000_000,
+            100_000_000_000_000_000,
+            5,
+            1_700_000_000,
+        );
+        let price = parse_pull_feed(&data).unwrap();
+        assert_eq!(price.to_usd_6dec(), Some(150_250_000));
+        assert_eq!(price.std_dev_to_usd_6dec(), Some(100_000));
+        assert_eq!(price.publish_time, 1_700_000_000);
+    }
+
+    #[test]
+    fn test_parse_pull_feed_rejects_bad_accounts() {
+        let good = pull_feed_fixture(1_000_000_000_000_000_000, 0, 3, 0);
+
+        let mut bad_discriminator = good.clone();
+        bad_discriminator[3] ^= 1;
+        assert!(matches!(
+            parse_pull_feed(&bad_discriminator),
+            Err(LegasiError::InvalidOracle)
+        ));
+        assert!(matches!(
+            parse_pull_feed(&good[..MIN_SIZE - 1]),
+            Err(LegasiError::InvalidOracle)
+        ));
+
+        let no_samples = pull_feed_fixture(1_000_000_000_000_000_000, 0, 0, 0);
+        assert!(matches!(
+            parse_pull_feed(&no_samples),
+            Err(LegasiError::OracleNotTrading)
+        ));
+
+        let negative = pull_feed_fixture(-1_000_000_000_000_000_000, 0, 3, 0);
+        assert_eq!(parse_pull_feed(&negative).unwrap().to_usd_6dec(), None);
+    }
+}
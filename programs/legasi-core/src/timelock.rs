@@ -0,0 +1,208 @@
+//! Timelocked parameter changes
+//!
+//! Risk and oracle parameters change through a queue: a role holder queues a
+//! `ParameterChange`, anyone can execute it once `Protocol.timelock_delay` has
+//! elapsed (within `TIMELOCK_GRACE_PERIOD`), and it can be cancelled until
+//! then. Users get the delay to react to risk changes.
+
+use anchor_lang::prelude::*;
+
+use crate::config::{validate_collateral_risk, validate_interest_rate};
+use crate::constants::*;
+use crate::errors::LegasiError;
+use crate::state::{
+    Borrowable, ChangeStatus, Collateral, ParameterChange, Protocol, ProtocolRole, QueuedChange,
+};
+
+impl ParameterChange {
+    pub fn validate(&self) -> std::result::Result<(), LegasiError> {
+        match *self {
+            ParameterChange::CollateralRisk {
+                max_ltv_bps,
+                liquidation_threshold_bps,
+                liquidation_bonus_bps,
+            } => validate_collateral_risk(
+                max_ltv_bps,
+                liquidation_threshold_bps,
+                liquidation_bonus_bps,
+            ),
+            ParameterChange::BorrowableInterestRate { interest_rate_bps } => {
+                validate_interest_rate(interest_rate_bps)
+            }
+            ParameterChange::CollateralOracle { oracle }
+            | ParameterChange::BorrowableOracle { oracle } => {
+                if oracle == Pubkey::default() {
+                    return Err(LegasiError::InvalidOracle);
+                }
+                Ok(())
+            }
+            ParameterChange::CollateralStatus { .. } | ParameterChange::BorrowableStatus { .. } => {
+                Ok(())
+            }
+            ParameterChange::TimelockDelay { delay_seconds } => {
+                if !(MIN_TIMELOCK_DELAY..=MAX_TIMELOCK_DELAY).contains(&delay_seconds) {
+                    return Err(LegasiError::InvalidRiskParameters);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Role that queues this change (`None`: super admin only)
+    pub fn required_role(&self) -> Option<ProtocolRole> {
+        match self {
+            ParameterChange::CollateralRisk { .. }
+            | ParameterChange::BorrowableInterestRate { .. }
+            | ParameterChange::CollateralStatus { .. }
+            | ParameterChange::BorrowableStatus { .. } => Some(ProtocolRole::Risk),
+            ParameterChange::CollateralOracle { .. } | ParameterChange::BorrowableOracle { .. } => {
+                Some(ProtocolRole::Oracle)
+            }
+            ParameterChange::TimelockDelay { .. } => None,
+        }
+    }
+}
+
+impl Protocol {
+    /// Whether `key` may queue `change`
+    pub fn can_queue_change(&self, change: &ParameterChange, key: &Pubkey) -> bool {
+        match change.required_role() {
+            Some(role) => self.has_role(role, key),
+            None => self.admin == *key,
+        }
+    }
+
+    /// Whether `key` may cancel `change` (the queuing role or the super admin)
+    pub fn can_cancel_change(&self, change: &ParameterChange, key: &Pubkey) -> bool {
+        self.admin == *key || self.can_queue_change(change, key)
+    }
+}
+
+impl QueuedChange {
+    /// Queued, past its ETA and within the grace period
+    pub fn check_executable(&self, now: i64) -> std::result::Result<(), LegasiError> {
+        if self.status != ChangeStatus::Queued {
+            return Err(LegasiError::ChangeNotQueued);
+        }
+        if now < self.eta {
+            return Err(LegasiError::TimelockNotElapsed);
+        }
+        if now > self.eta.saturating_add(TIMELOCK_GRACE_PERIOD) {
+            return Err(LegasiError::TimelockExpired);
+        }
+        Ok(())
+    }
+}
+
+impl Collateral {
+    /// LTV decreases ramp in the same way as direct config updates
+    pub fn apply_change(
+        &mut self,
+        change: &ParameterChange,
+        now: i64,
+    ) -> std::result::Result<(), LegasiError> {
+        match *change {
+            ParameterChange::CollateralRisk {
+                max_ltv_bps,
+                liquidation_threshold_bps,
+                liquidation_bonus_bps,
+            } => self.set_risk_params(
+                max_ltv_bps,
+                liquidation_threshold_bps,
+                liquidation_bonus_bps,
+                now,
+            )?,
+            ParameterChange::CollateralOracle { oracle } => self.oracle = oracle,
+            ParameterChange::CollateralStatus {
+                emode_category,
+                is_active,
+            } => {
+                self.emode_category = emode_category;
+                self.is_active = is_active;
+            }
+            _ => return Err(LegasiError::ChangeTargetMismatch),
+        }
+        Ok(())
+    }
+}
+
+impl Borrowable {
+    pub fn apply_change(
+        &mut self,
+        change: &ParameterChange,
+    ) -> std::result::Result<(), LegasiError> {
+        match *change {
+            ParameterChange::BorrowableInterestRate { interest_rate_bps } => {
+                self.interest_rate_bps = interest_rate_bps
+            }
+            ParameterChange::BorrowableOracle { oracle } => self.oracle = oracle,
+            ParameterChange::BorrowableStatus {
+                emode_category,
+                is_active,
+            } => {
+                self.emode_category = emode_category;
+                self.is_active = is_active;
+            }
+            _ => return Err(LegasiError::ChangeTargetMismatch),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(eta: i64) -> QueuedChange {
+        QueuedChange {
+            id: 0,
+            target: Pubkey::default(),
+            change: ParameterChange::BorrowableInterestRate {
+                interest_rate_bps: 500,
+            },
+            proposer: Pubkey::default(),
+            queued_at: 0,
+            eta,
+            status: ChangeStatus::Queued,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn test_executable_window() {
+        let mut change = queued(1_000);
+        assert!(matches!(
+            change.check_executable(999),
+            Err(LegasiError::TimelockNotElapsed)
+        ));
+        assert!(change.check_executable(1_000).is_ok());
+        assert!(matches!(
+            change.check_executable(1_000 + TIMELOCK_GRACE_PERIOD + 1),
+            Err(LegasiError::TimelockExpired)
+        ));
+
+        change.status = ChangeStatus::Cancelled;
+        assert!(matches!(
+            change.check_executable(1_000),
+            Err(LegasiError::ChangeNotQueued)
+        ));
+    }
+
+    #[test]
+    fn test_required_roles() {
+        let risk = ParameterChange::CollateralRisk {
+            max_ltv_bps: 7000,
+            liquidation_threshold_bps: 8000,
+            liquidation_bonus_bps: 500,
+        };
+        let delay = ParameterChange::TimelockDelay {
+            delay_seconds: DEFAULT_TIMELOCK_DELAY,
+        };
+        assert_eq!(risk.required_role(), Some(ProtocolRole::Risk));
+        assert_eq!(delay.required_role(), None);
+        assert!(delay.validate().is_ok());
+        assert!(ParameterChange::TimelockDelay { delay_seconds: 60 }
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! SPL Token / Token-2022 support
+//!
+//! Assets may live under either token program. Programs move tokens with
+//! `transfer_checked` through the token interface, and inbound transfers are
+//! credited with the amount the vault actually received, so Token-2022
+//! transfer fees never inflate deposits or repayments.
+//!
+//! Token-2022 mints are screened at registration: extensions that let a third
+//! party move or lock vault balances (permanent delegate, frozen-by-default
+//! accounts, non-transferable tokens, an active transfer hook) are rejected.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::TransferFeeConfig, transfer_hook::TransferHook, BaseStateWithExtensions,
+        ExtensionType, StateWithExtensions,
+    },
+};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::errors::LegasiError;
+
+/// Mint extensions that don't affect custody of vault balances
+const SUPPORTED_MINT_EXTENSIONS: [ExtensionType; 12] = [
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::ConfidentialTransferMint,
+    ExtensionType::ConfidentialTransferFeeConfig,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+    // Only while no hook program is set (checked separately)
+    ExtensionType::TransferHook,
+];
+
+/// Reject unsupported extensions, and transfer hooks with a program set
+pub fn check_mint_extensions(
+    extensions: &[ExtensionType],
+    transfer_hook_program: Option<Pubkey>,
+) -> std::result::Result<(), LegasiError> {
+    let supported = extensions
+        .iter()
+        .all(|ext| SUPPORTED_MINT_EXTENSIONS.contains(ext));
+    if !supported || transfer_hook_program.is_some() {
+        return Err(LegasiError::UnsupportedMintExtension);
+    }
+    Ok(())
+}
+
+/// Screen a mint being listed (legacy SPL Token mints have no extensions)
+pub fn validate_mint(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let info = mint.to_account_info();
+    if *info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let extensions = state.get_extension_types()?;
+    let transfer_hook_program = state
+        .get_extension::<TransferHook>()
+        .ok()
+        .and_then(|hook| Option::<Pubkey>::from(hook.program_id));
+    check_mint_extensions(&extensions, transfer_hook_program)?;
+    Ok(())
+}
+
+/// Amount to send so the recipient nets `net_amount` after any transfer fee
+pub fn gross_amount_for(mint: &InterfaceAccount<Mint>, net_amount: u64) -> Result<u64> {
+    let info = mint.to_account_info();
+    if *info.owner != spl_token_2022::ID {
+        return Ok(net_amount);
+    }
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let Ok(fee_config) = state.get_extension::<TransferFeeConfig>() else {
+        return Ok(net_amount);
+    };
+    let fee = fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+        .ok_or(LegasiError::MathOverflow)?;
+    Ok(net_amount
+        .checked_add(fee)
+        .ok_or(LegasiError::MathOverflow)?)
+}
+
+/// Transfer `amount` into `vault` and return what it received (net of any transfer fee)
+pub fn transfer_in<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    vault: &mut InterfaceAccount<'info, TokenAccount>,
+    authority: AccountInfo<'info>,
+    amount: u64,
+) -> Result<u64> {
+    let balance_before = vault.amount;
+    token_interface::transfer_checked(
+        CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from,
+                mint: mint.to_account_info(),
+                to: vault.to_account_info(),
+                authority,
+            },
+        ),
+        amount,
+        mint.decimals,
+    )?;
+    vault.reload()?;
+    Ok(vault
+        .amount
+        .checked_sub(balance_before)
+        .ok_or(LegasiError::MathOverflow)?)
+}
+
+/// Transfer `amount` out of a PDA-owned vault
+pub fn transfer_out<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    vault: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vault,
+                mint: mint.to_account_info(),
+                to,
+                authority,
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_mint_extensions() {
+        // Legacy-style mint, and a PYUSD-style fee + metadata mint with an unset hook
+        assert!(check_mint_extensions(&[], None).is_ok());
+        assert!(check_mint_extensions(
+            &[
+                ExtensionType::TransferFeeConfig,
+                ExtensionType::MetadataPointer,
+                ExtensionType::TokenMetadata,
+                ExtensionType::TransferHook,
+            ],
+            None
+        )
+        .is_ok());
+
+        for ext in [
+            ExtensionType::PermanentDelegate,
+            ExtensionType::DefaultAccountState,
+            ExtensionType::NonTransferable,
+        ] {
+            assert!(matches!(
+                check_mint_extensions(&[ExtensionType::TransferFeeConfig, ext], None),
+                Err(LegasiError::UnsupportedMintExtension)
+            ));
+        }
+
+        // A live transfer hook would need extra accounts on every transfer
+        assert!(
+            check_mint_extensions(&[ExtensionType::TransferHook], Some(Pubkey::new_unique()))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Debt valuation
+//!
+//! Borrowed amounts are valued through each borrowable's own `PriceFeed`
+//! (USDC via USDC/USD, EURC via EUR/USD) using the asset's decimals, so a
+//! stablecoin depeg or the EUR/USD rate is reflected in position health.
+//!
+//! Callers pass `(Borrowable, PriceFeed)` account pairs, usually through
+//! `remaining_accounts`. Each feed must be the core PDA `[b"price", mint]`
+//! of the paired borrowable.
+
+use anchor_lang::prelude::*;
+
+use crate::constants::BPS_DENOMINATOR;
+use crate::errors::LegasiError;
+use crate::state::{Borrowable, PriceFeed};
+
+/// How a debt price is derived from its feed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebtPriceMode {
+    /// Higher of spot and TWAP plus k * confidence (borrows, withdrawals)
+    Conservative,
+    /// Mid TWAP (GAD)
+    Mid,
+}
+
+/// USD price of a borrowed asset
+#[derive(Clone, Copy, Debug)]
+pub struct DebtPrice {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub price_usd_6dec: u64,
+}
+
+impl DebtPrice {
+    /// Build from a borrowable and its price feed
+    /// Rejects stale feeds, and tripped circuit breakers in conservative mode
+    pub fn from_feed(
+        borrowable: &Borrowable,
+        price_feed: &PriceFeed,
+        now: i64,
+        mode: DebtPriceMode,
+    ) -> Result<Self> {
+        let price_usd_6dec = match mode {
+            DebtPriceMode::Conservative => price_feed.fresh_debt_price_for_borrow(now)?,
+            DebtPriceMode::Mid => price_feed.fresh_twap_price(now)?,
+        };
+        Ok(Self {
+            mint: borrowable.mint,
+            decimals: borrowable.decimals,
+            price_usd_6dec,
+        })
+    }
+
+    /// USD value (6 decimals) of `amount` base units
+    pub fn value_usd(&self, amount: u64) -> std::result::Result<u64, LegasiError> {
+        value_usd(amount, self.decimals, self.price_usd_6dec)
+    }
+
+    /// Base units worth `usd` (6 decimals), rounded down
+    pub fn amount_for_usd(&self, usd: u64) -> std::result::Result<u64, LegasiError> {
+        let scale = 10u128
+            .checked_pow(self.decimals as u32)
+            .ok_or(LegasiError::MathOverflow)?;
+        let amount = (usd as u128)
+            .checked_mul(scale)
+            .ok_or(LegasiError::MathOverflow)?
+            .checked_div(self.price_usd_6dec as u128)
+            .ok_or(LegasiError::MathOverflow)?;
+        u64::try_from(amount).map_err(|_| LegasiError::MathOverflow)
+    }
+}
+
+/// USD value (6 decimals) of `amount` base units of an asset with `decimals`
+pub fn value_usd(
+    amount: u64,
+    decimals: u8,
+    price_usd_6dec: u64,
+) -> std::result::Result<u64, LegasiError> {
+    let scale = 10u128
+        .checked_pow(decimals as u32)
+        .ok_or(LegasiError::MathOverflow)?;
+    let value = (amount as u128)
+        .checked_mul(price_usd_6dec as u128)
+        .ok_or(LegasiError::MathOverflow)?
+        / scale;
+    u64::try_from(value).map_err(|_| LegasiError::MathOverflow)
+}
+
+/// LTV (bps) of `debt_usd` against `collateral_usd`; `u64::MAX` for debt without collateral
+pub fn ltv_bps(debt_usd: u64, collateral_usd: u64) -> u64 {
+    if debt_usd == 0 {
+        return 0;
+    }
+    if collateral_usd == 0 {
+        return u64::MAX;
+    }
+    let ltv = debt_usd as u128 * BPS_DENOMINATOR as u128 / collateral_usd as u128;
+    u64::try_from(ltv).unwrap_or(u64::MAX)
+}
+
+/// Check `feed_key` is the core price feed PDA for `mint`
+pub fn require_price_feed_address(
+    mint: &Pubkey,
+    price_feed: &PriceFeed,
+    feed_key: &Pubkey,
+) -> Result<()> {
+    let expected =
+        Pubkey::create_program_address(&[b"price", mint.as_ref(), &[price_feed.bump]], &crate::ID)
+            .map_err(|_| LegasiError::InvalidOracle)?;
+    require_keys_eq!(*feed_key, expected, LegasiError::InvalidOracle);
+    Ok(())
+}
+
+/// Load debt prices from `(Borrowable, PriceFeed)` account pairs
+pub fn load_debt_prices(
+    accounts: &[AccountInfo],
+    now: i64,
+    mode: DebtPriceMode,
+) -> Result<Vec<DebtPrice>> {
+    let pairs = accounts.chunks_exact(2);
+    require!(
+        pairs.remainder().is_empty(),
+        LegasiError::DebtPriceFeedMissing
+    );
+
+    let mut prices = Vec::with_capacity(accounts.len() / 2);
+    for pair in pairs {
+        let borrowable: Borrowable = load_core_account(&pair[0])?;
+        let price_feed: PriceFeed = load_core_account(&pair[1])?;
+        require_price_feed_address(&borrowable.mint, &price_feed, pair[1].key)?;
+
+        prices.push(DebtPrice::from_feed(&borrowable, &price_feed, now, mode)?);
+    }
+    Ok(prices)
+}
+
+/// Deserialize an account owned by the core program (owner and discriminator checked)
+pub fn load_core_account<T: AccountDeserialize>(info: &AccountInfo) -> Result<T> {
+    require_keys_eq!(
+        *info.owner,
+        crate::ID,
+        ErrorCode::AccountOwnedByWrongProgram
+    );
+    let data = info.try_borrow_data()?;
+    T::try_deserialize(&mut &data[..])
+}
+
+/// Total USD value (6 decimals) of `(mint, amount)` debts
+pub fn total_debt_usd(
+    debts: impl IntoIterator<Item = (Pubkey, u64)>,
+    prices: &[DebtPrice],
+) -> std::result::Result<u64, LegasiError> {
+    let mut total: u64 = 0;
+    for (mint, amount) in debts {
+        if amount == 0 {
+            continue;
+        }
+        let price = prices
+            .iter()
+            .find(|p| p.mint == mint)
+            .ok_or(LegasiError::DebtPriceFeedMissing)?;
+        total = total
+            .checked_add(price.value_usd(amount)?)
+            .ok_or(LegasiError::MathOverflow)?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(mint: Pubkey, decimals: u8, price_usd_6dec: u64) -> DebtPrice {
+        DebtPrice {
+            mint,
+            decimals,
+            price_usd_6dec,
+        }
+    }
+
+    #[test]
+    fn test_value_usd_uses_asset_decimals() {
+        let (usdc, eurc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // 100 USDC at par
+        assert_eq!(
+            price(usdc, 6, 1_000_000).value_usd(100_000_000).unwrap(),
+            100_000_000
+        );
+        // 100 EURC at EUR/USD 1.08
+        assert_eq!(
+            price(eurc, 6, 1_080_000).value_usd(100_000_000).unwrap(),
+            108_000_000
+        );
+        // 1 unit of a 9-decimal asset at $2
+        assert_eq!(
+            price(usdc, 9, 2_000_000).value_usd(1_000_000_000).unwrap(),
+            2_000_000
+        );
+    }
+
+    #[test]
+    fn test_amount_for_usd_inverts_value() {
+        let mint = Pubkey::new_unique();
+        // $108 of EURC at EUR/USD 1.08
+        let eurc = price(mint, 6, 1_080_000);
+        assert_eq!(eurc.amount_for_usd(108_000_000).unwrap(), 100_000_000);
+        // $3 of a 9-decimal asset at $2
+        let nine = price(mint, 9, 2_000_000);
+        assert_eq!(nine.amount_for_usd(3_000_000).unwrap(), 1_500_000_000);
+        // Rounds down
+        assert_eq!(price(mint, 6, 3_000_000).amount_for_usd(1).unwrap(), 0);
+        assert!(price(mint, 6, 0).amount_for_usd(1).is_err());
+    }
+
+    #[test]
+    fn test_ltv_bps() {
+        assert_eq!(ltv_bps(0, 0), 0);
+        assert_eq!(ltv_bps(750, 1_000), 7_500);
+        assert_eq!(ltv_bps(1, 0), u64::MAX);
+    }
+
+    #[test]
+    fn test_total_debt_reflects_depeg() {
+        let (usdc, eurc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let prices = [
+            price(usdc, 6, 970_000), // USDC at $0.97
+            price(eurc, 6, 1_080_000),
+        ];
+        let total = total_debt_usd([(usdc, 1_000_000_000), (eurc, 500_000_000)], &prices).unwrap();
+        assert_eq!(total, 970_000_000 + 540_000_000);
+    }
+
+    #[test]
+    fn test_total_debt_requires_price_for_each_asset() {
+        let (usdc, eurc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let prices = [price(usdc, 6, 1_000_000)];
+        assert!(matches!(
+            total_debt_usd([(eurc, 1)], &prices),
+            Err(LegasiError::DebtPriceFeedMissing)
+        ));
+        // Fully repaid debts don't need a feed
+        assert_eq!(total_debt_usd([(eurc, 0)], &prices).unwrap(), 0);
+    }
+}
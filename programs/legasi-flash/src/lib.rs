@@ -1,49 +1,32 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
-
-use legasi_core::{constants::*, errors::LegasiError, events::*, state::AssetType};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use legasi_core::{
+    accounting::ACCOUNTING_SEED,
+    constants::*,
+    errors::LegasiError,
+    events::*,
+    program::LegasiCore,
+    state::AccountingSource,
+    tokens::{gross_amount_for, transfer_in},
+};
+use legasi_lp::{program::LegasiLp, PoolCpi};
+use legasi_schema::{lp_program, Borrowable, LpPool, Protocol};
 
 declare_id!("Fj8CJNK1gBAuNR7dFbKLDckSstKmZn8ihTGwFXxfY93m");
 
-// ========== LOCAL STRUCTS (for cross-program account validation) ==========
-
-/// LP Pool (owned by LP program)
-#[account]
-#[derive(InitSpace)]
-pub struct LpPool {
-    pub borrowable_mint: Pubkey,
-    pub lp_token_mint: Pubkey,
-    pub total_deposits: u64,
-    pub total_shares: u64,
-    pub total_borrowed: u64,
-    pub interest_earned: u64,
-    pub bump: u8,
-}
-
-/// Borrowable config (owned by core program)
-#[account]
-#[derive(InitSpace)]
-pub struct Borrowable {
-    pub mint: Pubkey,
-    pub oracle: Pubkey,
-    pub interest_rate_bps: u16,
-    pub decimals: u8,
-    pub is_active: bool,
-    pub asset_type: AssetType,
-    pub bump: u8,
-}
-
-/// Protocol state (owned by core program)
-#[account]
-#[derive(InitSpace)]
-pub struct Protocol {
-    pub admin: Pubkey,
-    pub treasury: Pubkey,
-    pub insurance_fund: u64,
-    pub total_collateral_usd: u64,
-    pub total_borrowed_usd: u64,
-    pub paused: bool,
-    pub bump: u8,
+/// LP pool CPIs, signed by this program's accounting PDA
+fn pool<'info>(
+    lp_program: &Program<'info, LegasiLp>,
+    authority: &UncheckedAccount<'info>,
+    authority_bump: u8,
+) -> PoolCpi<'info> {
+    PoolCpi {
+        lp_program: lp_program.to_account_info(),
+        authority: authority.to_account_info(),
+        authority_bump,
+        source: AccountingSource::Flash,
+    }
 }
 
 /// Flash loan state (tracks outstanding loans in a transaction)
@@ -51,7 +34,7 @@ pub struct Protocol {
 #[derive(InitSpace)]
 pub struct FlashLoanState {
     pub borrower: Pubkey,
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
     pub amount: u64,
     pub fee: u64,
     pub initiated_slot: u64,
@@ -66,6 +49,7 @@ pub mod legasi_flash {
     /// Initiate a flash loan - must be repaid in same transaction
     pub fn flash_borrow(ctx: Context<FlashBorrow>, amount: u64, slot: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_FLASH)?;
 
         // Verify slot matches current slot (prevents replay)
         let current_slot = Clock::get()?.slot;
@@ -88,34 +72,31 @@ pub mod legasi_flash {
         // Initialize flash loan state
         let flash_state = &mut ctx.accounts.flash_state;
         flash_state.borrower = ctx.accounts.borrower.key();
-        flash_state.asset_type = ctx.accounts.borrowable.asset_type;
+        flash_state.mint = ctx.accounts.borrowable.mint;
         flash_state.amount = amount;
         flash_state.fee = fee;
         flash_state.initiated_slot = Clock::get()?.slot;
         flash_state.repaid = false;
         flash_state.bump = ctx.bumps.flash_state;
 
-        // Transfer tokens to borrower
-        let pool_bump = ctx.accounts.lp_pool.bump;
-        let borrowable_mint = ctx.accounts.lp_pool.borrowable_mint;
-        let seeds: &[&[u8]] = &[b"lp_pool", borrowable_mint.as_ref(), &[pool_bump]];
-
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.user_token_account.to_account_info(),
-                    authority: ctx.accounts.lp_pool.to_account_info(),
-                },
-                &[seeds],
-            ),
+        // Lend from the pool to the borrower
+        pool(
+            &ctx.accounts.lp_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .lend(
+            ctx.accounts.lp_pool.to_account_info(),
+            ctx.accounts.vault.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
             amount,
         )?;
 
-        emit!(FlashLoanInitiated {
+        emit_cpi!(FlashLoanInitiated {
             borrower: ctx.accounts.borrower.key(),
-            asset_type: flash_state.asset_type,
+            mint: flash_state.mint,
             amount,
             fee,
         });
@@ -141,52 +122,41 @@ pub mod legasi_flash {
             .checked_add(flash_state.fee)
             .ok_or(LegasiError::MathOverflow)?;
 
-        // Transfer repayment from borrower to vault
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    to: ctx.accounts.vault.to_account_info(),
-                    authority: ctx.accounts.borrower.to_account_info(),
-                },
-            ),
-            total_repayment,
+        // Transfer repayment from borrower to vault, grossed up so the vault
+        // nets the full repayment after any transfer fee
+        let gross_repayment = gross_amount_for(&ctx.accounts.mint, total_repayment)?;
+        let received = transfer_in(
+            &ctx.accounts.token_program,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.vault,
+            ctx.accounts.borrower.to_account_info(),
+            gross_repayment,
         )?;
+        require!(received >= total_repayment, LegasiError::FlashLoanNotRepaid);
 
         // Mark as repaid
         let flash_state = &mut ctx.accounts.flash_state;
         flash_state.repaid = true;
 
-        // Fee goes to LP pool (increases LP token value)
-        let lp_pool = &mut ctx.accounts.lp_pool;
-        let insurance_fee = flash_state
-            .fee
-            .checked_mul(INSURANCE_FEE_BPS)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(LegasiError::MathOverflow)?;
-
-        let lp_fee = flash_state.fee.saturating_sub(insurance_fee);
-        lp_pool.total_deposits = lp_pool
-            .total_deposits
-            .checked_add(lp_fee)
-            .ok_or(LegasiError::MathOverflow)?;
-        lp_pool.interest_earned = lp_pool
-            .interest_earned
-            .checked_add(lp_fee)
-            .ok_or(LegasiError::MathOverflow)?;
-
-        // Update protocol insurance
-        let protocol = &mut ctx.accounts.protocol;
-        protocol.insurance_fund = protocol
-            .insurance_fund
-            .checked_add(insurance_fee)
-            .ok_or(LegasiError::MathOverflow)?;
+        // Fee goes to LP pool (increases LP token value) and insurance
+        pool(
+            &ctx.accounts.lp_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_repayment(
+            ctx.accounts.lp_pool.to_account_info(),
+            ctx.accounts.protocol.to_account_info(),
+            ctx.accounts.lp_accounting_authority.to_account_info(),
+            ctx.accounts.core_program.to_account_info(),
+            flash_state.amount,
+            flash_state.fee,
+        )?;
 
-        emit!(FlashLoanRepaid {
+        emit_cpi!(FlashLoanRepaid {
             borrower: ctx.accounts.borrower.key(),
-            asset_type: flash_state.asset_type,
+            mint: flash_state.mint,
             amount: flash_state.amount,
             fee: flash_state.fee,
         });
@@ -212,6 +182,7 @@ pub mod legasi_flash {
 
 // ========== ACCOUNTS ==========
 
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(amount: u64, slot: u64)]
 pub struct FlashBorrow<'info> {
@@ -224,21 +195,48 @@ pub struct FlashBorrow<'info> {
     )]
     pub flash_state: Account<'info, FlashLoanState>,
     /// LP Pool (owned by LP program)
-    #[account(mut)]
-    pub lp_pool: Account<'info, LpPool>,
+    #[account(
+        mut,
+        seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()],
+        bump = lp_pool.bump,
+        seeds::program = lp_program::ID
+    )]
+    pub lp_pool: Box<Account<'info, LpPool>>,
     /// Borrowable config (owned by core program)
-    pub borrowable: Account<'info, Borrowable>,
-    /// LP Vault
-    #[account(mut)]
-    pub vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"borrowable", lp_pool.borrowable_mint.as_ref()],
+        bump = borrowable.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable: Box<Account<'info, Borrowable>>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// LP Vault (owned by LP program)
+    #[account(
+        mut,
+        seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
+        bump,
+        seeds::program = lp_program::ID,
+        token::mint = mint,
+        token::authority = lp_pool
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, token::mint = mint)]
+    pub user_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
     pub borrower: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    /// CHECK: PDA signing pool CPIs to the LP program
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub lp_program: Program<'info, LegasiLp>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct FlashRepay<'info> {
     #[account(
@@ -248,20 +246,47 @@ pub struct FlashRepay<'info> {
         has_one = borrower
     )]
     pub flash_state: Account<'info, FlashLoanState>,
-    /// LP Pool (owned by LP program)
-    #[account(mut)]
-    pub lp_pool: Account<'info, LpPool>,
-    /// Protocol (owned by core program)
-    #[account(mut)]
-    pub protocol: Account<'info, Protocol>,
-    /// LP Vault
-    #[account(mut)]
-    pub vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    /// LP Pool the loan was drawn from (owned by LP program)
+    #[account(
+        mut,
+        seeds = [b"lp_pool", flash_state.mint.as_ref()],
+        bump = lp_pool.bump,
+        seeds::program = lp_program::ID
+    )]
+    pub lp_pool: Box<Account<'info, LpPool>>,
+    /// Protocol (owned by core program) - insurance fund
+    #[account(
+        mut,
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// LP Vault (owned by LP program)
+    #[account(
+        mut,
+        seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
+        bump,
+        seeds::program = lp_program::ID,
+        token::mint = mint,
+        token::authority = lp_pool
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, token::mint = mint)]
+    pub user_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
     pub borrower: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing pool CPIs to the LP program
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    /// CHECK: LP program's PDA, which signs the insurance fee to core
+    #[account(seeds = [ACCOUNTING_SEED], bump, seeds::program = lp_program::ID)]
+    pub lp_accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+    pub lp_program: Program<'info, LegasiLp>,
 }
 
 #[derive(Accounts)]
@@ -277,3 +302,399 @@ pub struct CloseFlashState<'info> {
     #[account(mut)]
     pub borrower: Signer<'info>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::error::ErrorCode;
+    use anchor_lang::solana_program::entrypoint::ProgramResult;
+    use anchor_lang::InstructionData;
+    use anchor_spl::token::spl_token;
+    use legasi_core::state::{AssetKind, EModeCategory};
+    use legasi_schema::testing::{self, TestAccount};
+
+    const SLOT: u64 = 42;
+    const AMOUNT: u64 = 1_000_000;
+    const FEE: u64 = 500;
+    const LIQUIDITY: u64 = 1_000_000_000;
+
+    // Positions shared by FlashBorrow and FlashRepay
+    const LP_POOL: usize = 1;
+    const PROTOCOL_REPAY: usize = 2;
+    const VAULT_REPAY: usize = 3;
+    const BORROWABLE: usize = 2;
+    const PROTOCOL_BORROW: usize = 3;
+    const VAULT_BORROW: usize = 4;
+    const USER_TOKEN_BORROW: usize = 6;
+    const BORROWER_BORROW: usize = 7;
+    const TOKEN_PROGRAM_BORROW: usize = 8;
+    const USER_TOKEN_REPAY: usize = 5;
+
+    fn pda(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, program_id)
+    }
+
+    fn flash_key(borrower: &Pubkey) -> (Pubkey, u8) {
+        pda(
+            &[b"flash", borrower.as_ref(), &SLOT.to_le_bytes()],
+            &crate::ID,
+        )
+    }
+
+    fn lp_pool(mint: Pubkey) -> TestAccount {
+        let (key, bump) = pda(&[b"lp_pool", mint.as_ref()], &lp_program::ID);
+        let pool = LpPool {
+            borrowable_mint: mint,
+            lp_token_mint: Pubkey::new_unique(),
+            total_deposits: LIQUIDITY,
+            total_shares: LIQUIDITY,
+            total_borrowed: 0,
+            interest_earned: 0,
+            bump,
+        };
+        TestAccount::anchor(key, &pool)
+    }
+
+    fn lp_vault(mint: Pubkey) -> TestAccount {
+        let (pool, _) = pda(&[b"lp_pool", mint.as_ref()], &lp_program::ID);
+        let (key, _) = pda(&[b"lp_vault", mint.as_ref()], &lp_program::ID);
+        TestAccount::token_account(key, mint, pool, LIQUIDITY)
+    }
+
+    fn borrowable(mint: Pubkey) -> TestAccount {
+        let (key, bump) = pda(&[b"borrowable", mint.as_ref()], &legasi_core::ID);
+        let borrowable = Borrowable {
+            mint,
+            oracle: Pubkey::new_unique(),
+            interest_rate_bps: 500,
+            decimals: 6,
+            is_active: true,
+            total_borrowed: 0,
+            total_available: 1_000_000_000,
+            kind: AssetKind::Stablecoin,
+            emode_category: EModeCategory::Stablecoins,
+            pause_flags: 0,
+            bump,
+        };
+        TestAccount::anchor(key, &borrowable)
+    }
+
+    fn protocol() -> TestAccount {
+        let (key, bump) = pda(&[b"protocol"], &legasi_core::ID);
+        let mut protocol: Protocol = testing::zeroed();
+        protocol.bump = bump;
+        TestAccount::anchor(key, &protocol)
+    }
+
+    fn pda_account(seeds: &[&[u8]], program_id: &Pubkey) -> TestAccount {
+        TestAccount::new(pda(seeds, program_id).0, Pubkey::default(), Vec::new())
+    }
+
+    fn event_cpi_accounts() -> [TestAccount; 2] {
+        let (event_authority, _) = pda(&[b"__event_authority"], &crate::ID);
+        [
+            TestAccount::new(event_authority, Pubkey::default(), Vec::new()),
+            TestAccount::program(crate::ID),
+        ]
+    }
+
+    /// Accounts for a valid `flash_borrow` of `mint`, in instruction order
+    fn borrow_accounts(mint: Pubkey) -> Vec<TestAccount> {
+        let borrower = Pubkey::new_unique();
+        let (flash_state, _) = flash_key(&borrower);
+        let mut accounts = vec![
+            // Created by `init`: CPIs are no-ops in tests
+            TestAccount::new(
+                flash_state,
+                crate::ID,
+                vec![0; 8 + FlashLoanState::INIT_SPACE],
+            ),
+            lp_pool(mint),
+            borrowable(mint),
+            protocol(),
+            lp_vault(mint),
+            TestAccount::mint(mint, 6),
+            // Holds the fee the loan is repaid with
+            TestAccount::token_account(Pubkey::new_unique(), mint, borrower, FEE),
+            TestAccount::signer(borrower),
+            TestAccount::program(spl_token::ID),
+            TestAccount::program(System::id()),
+            pda_account(&[ACCOUNTING_SEED], &crate::ID),
+            TestAccount::program(lp_program::ID),
+        ];
+        accounts.extend(event_cpi_accounts());
+        accounts
+    }
+
+    /// Accounts for a valid `flash_repay` of a loan from `mint`'s pool
+    fn repay_accounts(mint: Pubkey) -> Vec<TestAccount> {
+        let borrower = Pubkey::new_unique();
+        let (flash_state, bump) = flash_key(&borrower);
+        let state = FlashLoanState {
+            borrower,
+            mint,
+            amount: AMOUNT,
+            fee: FEE,
+            initiated_slot: SLOT,
+            repaid: false,
+            bump,
+        };
+        let mut accounts = vec![
+            TestAccount::anchor(flash_state, &state),
+            lp_pool(mint),
+            protocol(),
+            lp_vault(mint),
+            TestAccount::mint(mint, 6),
+            TestAccount::token_account(Pubkey::new_unique(), mint, borrower, AMOUNT + FEE),
+            TestAccount::signer(borrower),
+            TestAccount::program(spl_token::ID),
+        ];
+        accounts.extend(repay_cpi_accounts());
+        accounts.extend(event_cpi_accounts());
+        accounts
+    }
+
+    /// Flash and LP accounting PDAs, core and LP programs
+    fn repay_cpi_accounts() -> [TestAccount; 4] {
+        [
+            pda_account(&[ACCOUNTING_SEED], &crate::ID),
+            pda_account(&[ACCOUNTING_SEED], &lp_program::ID),
+            TestAccount::program(legasi_core::ID),
+            TestAccount::program(lp_program::ID),
+        ]
+    }
+
+    /// The `flash_repay` accounts for a loan taken with `borrow_accounts`
+    fn repay_accounts_after(borrow: &[TestAccount]) -> Vec<TestAccount> {
+        let mut accounts: Vec<TestAccount> = [0, LP_POOL, PROTOCOL_BORROW]
+            .into_iter()
+            .chain(VAULT_BORROW..=TOKEN_PROGRAM_BORROW)
+            .map(|i| borrow[i].clone())
+            .collect();
+        accounts.extend(repay_cpi_accounts());
+        accounts.extend(event_cpi_accounts());
+        accounts
+    }
+
+    /// Runs an instruction in `SLOT`, with its CPIs into the LP and core programs
+    fn run(accounts: &mut [TestAccount], ix: impl InstructionData) -> ProgramResult {
+        testing::set_slot(SLOT);
+        testing::run(
+            crate::ID,
+            crate::entry,
+            &[
+                (lp_program::ID, legasi_lp::entry),
+                (legasi_core::ID, legasi_core::entry),
+            ],
+            accounts,
+            &ix.data(),
+        )
+    }
+
+    fn try_borrow(accounts: &mut [TestAccount]) -> Result<()> {
+        testing::install_rent_stub();
+        let ix_data = (AMOUNT, SLOT).try_to_vec().unwrap();
+        let infos = testing::infos(accounts);
+        testing::try_accounts::<FlashBorrow>(&crate::ID, &infos, &ix_data)
+    }
+
+    fn try_repay(accounts: &mut [TestAccount]) -> Result<()> {
+        let infos = testing::infos(accounts);
+        testing::try_accounts::<FlashRepay>(&crate::ID, &infos, &[])
+    }
+
+    #[test]
+    fn test_borrow_accepts_protocol_accounts() {
+        let mut accounts = borrow_accounts(Pubkey::new_unique());
+        assert!(try_borrow(&mut accounts).is_ok());
+    }
+
+    #[test]
+    fn test_repay_accepts_protocol_accounts() {
+        let mut accounts = repay_accounts(Pubkey::new_unique());
+        assert!(try_repay(&mut accounts).is_ok());
+    }
+
+    #[test]
+    fn test_borrow_rejects_pool_not_owned_by_lp() {
+        let mut accounts = borrow_accounts(Pubkey::new_unique());
+        accounts[LP_POOL].owner = crate::ID;
+
+        let result = try_borrow(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::AccountOwnedByWrongProgram,
+            "lp_pool"
+        ));
+    }
+
+    #[test]
+    fn test_borrow_rejects_borrowable_of_other_mint() {
+        let mut accounts = borrow_accounts(Pubkey::new_unique());
+        accounts[BORROWABLE] = borrowable(Pubkey::new_unique());
+
+        let result = try_borrow(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintSeeds,
+            "borrowable"
+        ));
+    }
+
+    #[test]
+    fn test_borrow_rejects_protocol_lookalike() {
+        let mut accounts = borrow_accounts(Pubkey::new_unique());
+        accounts[PROTOCOL_BORROW].owner = crate::ID;
+
+        let result = try_borrow(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::AccountOwnedByWrongProgram,
+            "protocol"
+        ));
+    }
+
+    #[test]
+    fn test_borrow_rejects_vault_outside_pool() {
+        let mint = Pubkey::new_unique();
+        let (pool, _) = pda(&[b"lp_pool", mint.as_ref()], &lp_program::ID);
+
+        // Right mint and authority, but not the pool's vault PDA
+        let mut accounts = borrow_accounts(mint);
+        accounts[VAULT_BORROW] =
+            TestAccount::token_account(Pubkey::new_unique(), mint, pool, 1_000_000_000);
+        let result = try_borrow(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintSeeds,
+            "vault"
+        ));
+
+        // Vault PDA, but not controlled by the pool
+        let mut accounts = borrow_accounts(mint);
+        let vault = accounts[VAULT_BORROW].key;
+        accounts[VAULT_BORROW] =
+            TestAccount::token_account(vault, mint, Pubkey::new_unique(), 1_000_000_000);
+        let result = try_borrow(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintTokenOwner,
+            "vault"
+        ));
+    }
+
+    #[test]
+    fn test_borrow_rejects_user_account_of_other_mint() {
+        let mut accounts = borrow_accounts(Pubkey::new_unique());
+        let borrower = accounts[BORROWER_BORROW].key;
+        accounts[USER_TOKEN_BORROW] =
+            TestAccount::token_account(Pubkey::new_unique(), Pubkey::new_unique(), borrower, 0);
+
+        let result = try_borrow(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintTokenMint,
+            "user_token_account"
+        ));
+    }
+
+    #[test]
+    fn test_repay_rejects_pool_of_other_mint() {
+        // Repaying into a different pool than the loan was drawn from
+        let mint = Pubkey::new_unique();
+        let other_mint = Pubkey::new_unique();
+        let mut accounts = repay_accounts(mint);
+        accounts[LP_POOL] = lp_pool(other_mint);
+        accounts[VAULT_REPAY] = lp_vault(other_mint);
+
+        let result = try_repay(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintSeeds,
+            "lp_pool"
+        ));
+    }
+
+    #[test]
+    fn test_repay_rejects_vault_outside_pool() {
+        let mint = Pubkey::new_unique();
+        let mut accounts = repay_accounts(mint);
+        let attacker = Pubkey::new_unique();
+        accounts[VAULT_REPAY] = TestAccount::token_account(attacker, mint, attacker, 0);
+
+        let result = try_repay(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintSeeds,
+            "vault"
+        ));
+    }
+
+    #[test]
+    fn test_repay_rejects_protocol_lookalike() {
+        let mut accounts = repay_accounts(Pubkey::new_unique());
+        accounts[PROTOCOL_REPAY].owner = lp_program::ID;
+
+        let result = try_repay(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::AccountOwnedByWrongProgram,
+            "protocol"
+        ));
+    }
+
+    #[test]
+    fn test_flash_loan_is_lent_and_repaid_through_pool() {
+        let mint = Pubkey::new_unique();
+        let mut borrow = borrow_accounts(mint);
+        run(
+            &mut borrow,
+            instruction::FlashBorrow {
+                amount: AMOUNT,
+                slot: SLOT,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(borrow[USER_TOKEN_BORROW].token_amount(), AMOUNT + FEE);
+        assert_eq!(borrow[VAULT_BORROW].token_amount(), LIQUIDITY - AMOUNT);
+        assert_eq!(borrow[LP_POOL].read::<LpPool>().total_borrowed, AMOUNT);
+
+        let mut repay = repay_accounts_after(&borrow);
+        run(&mut repay, instruction::FlashRepay {}).unwrap();
+
+        let insurance_fee = FEE * INSURANCE_FEE_BPS / BPS_DENOMINATOR;
+        assert_eq!(repay[USER_TOKEN_REPAY].token_amount(), 0);
+        assert_eq!(repay[VAULT_REPAY].token_amount(), LIQUIDITY + FEE);
+        let pool: LpPool = repay[LP_POOL].read();
+        assert_eq!(pool.total_borrowed, 0);
+        assert_eq!(pool.total_deposits, LIQUIDITY + FEE - insurance_fee);
+        assert_eq!(
+            repay[PROTOCOL_REPAY].read::<Protocol>().insurance_fund,
+            insurance_fee
+        );
+        assert!(repay[0].read::<FlashLoanState>().repaid);
+    }
+
+    #[test]
+    fn test_flash_repay_without_fee_fails() {
+        let mint = Pubkey::new_unique();
+        let mut borrow = borrow_accounts(mint);
+        let user_token = borrow[USER_TOKEN_BORROW].key;
+        let borrower = borrow[BORROWER_BORROW].key;
+        borrow[USER_TOKEN_BORROW] = TestAccount::token_account(user_token, mint, borrower, 0);
+        run(
+            &mut borrow,
+            instruction::FlashBorrow {
+                amount: AMOUNT,
+                slot: SLOT,
+            },
+        )
+        .unwrap();
+
+        let mut repay = repay_accounts_after(&borrow);
+        assert!(run(&mut repay, instruction::FlashRepay {}).is_err());
+        assert_eq!(repay[LP_POOL].read::<LpPool>().total_borrowed, AMOUNT);
+        assert!(!repay[0].read::<FlashLoanState>().repaid);
+    }
+}
@@ -1,9 +1,19 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
 use anchor_lang::solana_program::program::invoke_signed;
 use anchor_lang::solana_program::system_instruction;
-use anchor_spl::token::{Token, TokenAccount};
-
-use legasi_core::{constants::*, errors::LegasiError, events::*, state::*};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use legasi_core::{
+    accounting::{AccountingCpi, ACCOUNTING_SEED},
+    constants::*,
+    errors::LegasiError,
+    events::*,
+    program::LegasiCore,
+    state::*,
+    valuation::*,
+};
+use legasi_schema::{lending_program, Position};
 
 declare_id!("89E84ALdDdGGNuJAxho2H45aC25kqNdGg7QtwTJ3pngK");
 
@@ -28,6 +38,20 @@ fn get_gad_rate_bps(current_ltv_bps: u64, max_ltv_bps: u64) -> u64 {
     std::cmp::min(rate, 1000)
 }
 
+/// Accounting CPIs to core, signed by this program's accounting PDA
+fn accounting<'info>(
+    core_program: &Program<'info, LegasiCore>,
+    authority: &UncheckedAccount<'info>,
+    authority_bump: u8,
+) -> AccountingCpi<'info> {
+    AccountingCpi {
+        core_program: core_program.to_account_info(),
+        authority: authority.to_account_info(),
+        authority_bump,
+        source: AccountingSource::Gad,
+    }
+}
+
 #[program]
 pub mod legasi_gad {
     use super::*;
@@ -38,8 +62,7 @@ pub mod legasi_gad {
         enabled: bool,
         _custom_threshold_bps: Option<u16>,
     ) -> Result<()> {
-        let position = &mut ctx.accounts.position;
-        position.gad_enabled = enabled;
+        ctx.accounts.position.load_mut()?.set_gad_enabled(enabled);
 
         // Custom threshold would need to be stored - for now just toggle
         msg!("GAD configured: enabled={}", enabled);
@@ -47,15 +70,16 @@ pub mod legasi_gad {
     }
 
     /// Crank GAD for a position - anyone can call
-    pub fn crank_gad(ctx: Context<CrankGad>) -> Result<()> {
-        let position = &ctx.accounts.position;
+    pub fn crank_gad<'info>(ctx: Context<'_, '_, 'info, 'info, CrankGad<'info>>) -> Result<()> {
+        ctx.accounts.protocol.require_not_paused(PAUSE_GAD)?;
+        let mut position = ctx.accounts.position.load_mut()?;
 
         // Check GAD is enabled
-        require!(position.gad_enabled, LegasiError::GadDisabled);
+        require!(position.is_gad_enabled(), LegasiError::GadDisabled);
 
         // Check has debt
         require!(
-            !position.borrows.is_empty(),
+            !position.borrows().is_empty(),
             LegasiError::NoDebtToDeleverage
         );
 
@@ -64,68 +88,26 @@ pub mod legasi_gad {
         let elapsed = now.saturating_sub(position.last_gad_crank);
         require!(elapsed >= MIN_GAD_CRANK_INTERVAL, LegasiError::CrankTooSoon);
 
-        // Calculate current LTV
-        let total_collateral_usd =
-            calculate_collateral_value(position, &ctx.accounts.sol_price_feed)?;
-        require!(
-            total_collateral_usd > 0,
-            LegasiError::InsufficientCollateral
-        );
-
-        let total_borrow_usd = calculate_borrow_value(position)?;
-
-        let current_ltv_bps = total_borrow_usd
-            .checked_mul(BPS_DENOMINATOR)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(total_collateral_usd)
-            .ok_or(LegasiError::MathOverflow)?;
-
-        // Check if LTV exceeds max (75% default for SOL)
-        let max_ltv_bps = DEFAULT_SOL_MAX_LTV_BPS as u64;
-        require!(
-            current_ltv_bps > max_ltv_bps,
-            LegasiError::LtvBelowGadThreshold
-        );
-
-        // Calculate GAD rate
-        let gad_rate_bps = get_gad_rate_bps(current_ltv_bps, max_ltv_bps);
-        require!(gad_rate_bps > 0, LegasiError::NothingToLiquidate);
+        // Calculate current LTV at mid TWAP (no confidence haircut, so a single
+        // bad update or a wide confidence band cannot trigger GAD)
+        let sol_price = ctx.accounts.sol_price_feed.fresh_twap_price(now)?;
 
-        // Calculate amount to liquidate (pro-rata based on time elapsed)
-        let time_fraction = (elapsed as u128)
-            .checked_mul(BPS_DENOMINATOR as u128)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(SECONDS_PER_DAY as u128)
-            .ok_or(LegasiError::MathOverflow)? as u64;
-
-        let liquidate_fraction_bps = (gad_rate_bps as u128)
-            .checked_mul(time_fraction as u128)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR as u128)
-            .ok_or(LegasiError::MathOverflow)? as u64;
-
-        // Find SOL collateral and calculate liquidation amount
-        let sol_deposit = position
-            .collaterals
-            .iter()
-            .find(|c| c.asset_type == AssetType::SOL)
-            .ok_or(LegasiError::InsufficientCollateral)?;
-
-        let sol_to_liquidate = (sol_deposit.amount as u128)
-            .checked_mul(liquidate_fraction_bps as u128)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR as u128)
-            .ok_or(LegasiError::MathOverflow)? as u64;
+        // remaining_accounts: (Borrowable, PriceFeed) pairs for each borrowed asset,
+        // Borrowables writable (debt written down is recorded on them)
+        let debt_prices = load_debt_prices(ctx.remaining_accounts, now, DebtPriceMode::Mid)?;
 
-        require!(sol_to_liquidate > 0, LegasiError::NothingToLiquidate);
+        // GAD runs above the SOL collateral's liquidation threshold
+        let threshold_bps = ctx.accounts.sol_collateral.liquidation_threshold_bps as u64;
+        let GadSchedule {
+            total_collateral_usd,
+            total_borrow_usd,
+            current_ltv_bps,
+            gad_rate_bps,
+            sol_to_liquidate,
+        } = gad_schedule(&position, sol_price, &debt_prices, threshold_bps, elapsed)?;
 
         // Calculate USD value of liquidated SOL
-        let sol_price = ctx.accounts.sol_price_feed.price_usd_6dec;
-        let liquidated_usd = (sol_to_liquidate as u128)
-            .checked_mul(sol_price as u128)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(LAMPORTS_PER_SOL as u128)
-            .ok_or(LegasiError::MathOverflow)? as u64;
+        let liquidated_usd = sol_value_usd(sol_to_liquidate, sol_price)?;
 
         // Reduce debt by liquidated amount
         let debt_reduction = std::cmp::min(liquidated_usd, total_borrow_usd);
@@ -177,39 +159,17 @@ pub mod legasi_gad {
             )?;
         }
 
-        // Update position
-        let position = &mut ctx.accounts.position;
-
         // Reduce SOL collateral
         if let Some(sol_deposit) = position
-            .collaterals
+            .collaterals_mut()
             .iter_mut()
-            .find(|c| c.asset_type == AssetType::SOL)
+            .find(|c| c.mint == NATIVE_SOL_MINT)
         {
             sol_deposit.amount = sol_deposit.amount.saturating_sub(total_sol_deducted);
         }
 
-        // Reduce debt (proportionally across all borrows)
-        let mut remaining_reduction = debt_reduction;
-        for borrow in position.borrows.iter_mut() {
-            if remaining_reduction == 0 {
-                break;
-            }
-            let borrow_total = borrow
-                .amount
-                .checked_add(borrow.accrued_interest)
-                .unwrap_or(0);
-            let reduction = std::cmp::min(remaining_reduction, borrow_total);
-
-            // First reduce interest, then principal
-            let interest_reduction = std::cmp::min(reduction, borrow.accrued_interest);
-            borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_reduction);
-
-            let principal_reduction = reduction.saturating_sub(interest_reduction);
-            borrow.amount = borrow.amount.saturating_sub(principal_reduction);
-
-            remaining_reduction = remaining_reduction.saturating_sub(reduction);
-        }
+        // Reduce debt by the liquidated value, converted to each asset's units
+        let principal_written_down = write_down_debt(&mut position, debt_reduction, &debt_prices)?;
 
         // Update GAD stats
         position.last_gad_crank = now;
@@ -220,10 +180,34 @@ pub mod legasi_gad {
         position.last_update = now;
 
         // Clean up empty entries
-        position.collaterals.retain(|c| c.amount > 0);
-        position
-            .borrows
-            .retain(|b| b.amount > 0 || b.accrued_interest > 0);
+        position.retain_collaterals(|c| c.amount > 0);
+        position.retain_borrows(|b| b.amount > 0 || b.accrued_interest > 0);
+        let collateral_amount = position.collateral_amount(&NATIVE_SOL_MINT);
+        let seq = position.next_event_seq();
+
+        // Record the seized collateral and the debt written down (no liquidity returns)
+        let accounting_cpi = accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        );
+        accounting_cpi.record_collateral(
+            ctx.accounts.sol_collateral.to_account_info(),
+            CollateralChange::Withdrawn(total_sol_deducted),
+        )?;
+        for (mint, principal) in principal_written_down {
+            let index = debt_prices
+                .iter()
+                .position(|p| p.mint == mint)
+                .ok_or(LegasiError::DebtPriceFeedMissing)?;
+            accounting_cpi.record_borrowable(
+                ctx.remaining_accounts[2 * index].clone(),
+                BorrowableChange::Repaid {
+                    principal,
+                    received: 0,
+                },
+            )?;
+        }
 
         // Calculate new LTV for event
         let new_collateral_usd = total_collateral_usd.saturating_sub(liquidated_usd);
@@ -238,8 +222,11 @@ pub mod legasi_gad {
             0
         };
 
-        emit!(GadExecuted {
+        emit_cpi!(GadExecuted {
             position: ctx.accounts.position.key(),
+            collateral_mint: NATIVE_SOL_MINT,
+            collateral_seized: total_sol_deducted,
+            collateral_amount,
             collateral_liquidated_usd: liquidated_usd,
             debt_reduced_usd: debt_reduction,
             ltv_before_bps: current_ltv_bps,
@@ -247,6 +234,7 @@ pub mod legasi_gad {
             gad_rate_bps,
             cranker: ctx.accounts.cranker.key(),
             cranker_reward,
+            seq,
         });
 
         msg!(
@@ -258,49 +246,60 @@ pub mod legasi_gad {
     }
 
     /// Execute GAD with Jupiter swap - converts liquidated collateral to USDC
-    /// This is the production version that actually swaps via Jupiter
-    pub fn crank_gad_with_swap(
-        ctx: Context<CrankGadWithSwap>,
+    /// This is the production version that actually swaps via Jupiter. The
+    /// swap may sell at most what `crank_gad` would seize, and all of its
+    /// proceeds land in the lending vault to repay the debt in that asset
+    pub fn crank_gad_with_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankGadWithSwap<'info>>,
         jupiter_swap_data: Vec<u8>, // Serialized Jupiter swap instruction data
         min_out_amount: u64,        // Minimum USDC to receive (slippage protection)
     ) -> Result<()> {
-        let position = &ctx.accounts.position;
+        ctx.accounts.protocol.require_not_paused(PAUSE_GAD)?;
+        let position = ctx.accounts.position.load()?;
 
-        require!(position.gad_enabled, LegasiError::GadDisabled);
+        require!(position.is_gad_enabled(), LegasiError::GadDisabled);
         require!(
-            !position.borrows.is_empty(),
+            !position.borrows().is_empty(),
             LegasiError::NoDebtToDeleverage
         );
 
-        // Calculate amount to liquidate (same logic as crank_gad)
         let now = Clock::get()?.unix_timestamp;
         let elapsed = now.saturating_sub(position.last_gad_crank);
         require!(elapsed >= MIN_GAD_CRANK_INTERVAL, LegasiError::CrankTooSoon);
 
-        // ... (LTV calculation same as above)
+        // remaining_accounts: (Borrowable, PriceFeed) pairs for each borrowed
+        // asset, then the accounts of the Jupiter swap
+        let price_accounts_len = 2 * position.borrows().len();
+        require!(
+            ctx.remaining_accounts.len() >= price_accounts_len,
+            LegasiError::DebtPriceFeedMissing
+        );
+        let (price_accounts, swap_accounts) = ctx.remaining_accounts.split_at(price_accounts_len);
 
-        // Execute Jupiter swap: SOL → USDC
-        // CPI to Jupiter aggregator
-        let jupiter_program = &ctx.accounts.jupiter_program;
-        let _swap_accounts = vec![
-            ctx.accounts.sol_vault.to_account_info(),
-            ctx.accounts.usdc_vault.to_account_info(),
-            ctx.accounts.token_program.to_account_info(),
-            // Jupiter requires additional accounts passed via remaining_accounts
-        ];
+        // Same schedule as crank_gad
+        let sol_price = ctx.accounts.sol_price_feed.fresh_twap_price(now)?;
+        let debt_prices = load_debt_prices(price_accounts, now, DebtPriceMode::Mid)?;
+        let threshold_bps = ctx.accounts.sol_collateral.liquidation_threshold_bps as u64;
+        let schedule = gad_schedule(&position, sol_price, &debt_prices, threshold_bps, elapsed)?;
 
-        // Build Jupiter CPI
+        let usdc_mint = ctx.accounts.usdc_mint.key();
+        let usdc_owed = position.debt_amount(&usdc_mint);
+        require!(usdc_owed > 0, LegasiError::NoDebtToDeleverage);
+        drop(position);
+
+        // Execute Jupiter swap: SOL → USDC, signed by the SOL vault
         let position_key = ctx.accounts.position.key();
         let vault_bump = ctx.bumps.sol_vault;
         let seeds: &[&[u8]] = &[b"sol_vault", position_key.as_ref(), &[vault_bump]];
+        let sol_before = ctx.accounts.sol_vault.lamports();
+        let usdc_before = ctx.accounts.usdc_vault.amount;
 
-        anchor_lang::solana_program::program::invoke_signed(
-            &anchor_lang::solana_program::instruction::Instruction {
-                program_id: jupiter_program.key(),
-                accounts: ctx
-                    .remaining_accounts
+        invoke_signed(
+            &Instruction {
+                program_id: ctx.accounts.jupiter_program.key(),
+                accounts: swap_accounts
                     .iter()
-                    .map(|a| anchor_lang::solana_program::instruction::AccountMeta {
+                    .map(|a| AccountMeta {
                         pubkey: a.key(),
                         is_signer: a.is_signer,
                         is_writable: a.is_writable,
@@ -308,107 +307,261 @@ pub mod legasi_gad {
                     .collect(),
                 data: jupiter_swap_data,
             },
-            ctx.remaining_accounts,
+            swap_accounts,
             &[seeds],
         )?;
 
-        // Verify we received minimum USDC
+        // Measure what the swap actually sold and received
         ctx.accounts.usdc_vault.reload()?;
+        let sol_liquidated = sol_before.saturating_sub(ctx.accounts.sol_vault.lamports());
+        let usdc_received = ctx.accounts.usdc_vault.amount.saturating_sub(usdc_before);
+        require!(sol_liquidated > 0, LegasiError::NothingToLiquidate);
         require!(
-            ctx.accounts.usdc_vault.amount >= min_out_amount,
+            sol_liquidated <= schedule.sol_to_liquidate,
+            LegasiError::GadLimitExceeded
+        );
+        require!(
+            usdc_received >= min_out_amount,
             LegasiError::SlippageExceeded
         );
+        // Proceeds stay in the lending vault, so all of them must repay debt
+        require!(usdc_received <= usdc_owed, LegasiError::InvalidAmount);
 
-        // Use received USDC to repay debt
-        let usdc_received = ctx.accounts.usdc_vault.amount;
-
-        // Update position (reduce debt by USDC received)
-        let position = &mut ctx.accounts.position;
-        for borrow in position.borrows.iter_mut() {
-            if borrow.asset_type == AssetType::USDC {
-                let total_debt = borrow
-                    .amount
-                    .checked_add(borrow.accrued_interest)
-                    .unwrap_or(0);
-                let reduction = std::cmp::min(usdc_received, total_debt);
-
-                let interest_reduction = std::cmp::min(reduction, borrow.accrued_interest);
-                borrow.accrued_interest =
-                    borrow.accrued_interest.saturating_sub(interest_reduction);
-                borrow.amount = borrow
-                    .amount
-                    .saturating_sub(reduction.saturating_sub(interest_reduction));
+        // Update position: remove the SOL sold, repay interest then principal
+        let mut position = ctx.accounts.position.load_mut()?;
+        if let Some(sol_deposit) = position
+            .collaterals_mut()
+            .iter_mut()
+            .find(|c| c.mint == NATIVE_SOL_MINT)
+        {
+            sol_deposit.amount = sol_deposit.amount.saturating_sub(sol_liquidated);
+        }
+        let mut principal_repaid: u64 = 0;
+        for borrow in position.borrows_mut() {
+            if borrow.mint == usdc_mint {
+                let interest_paid = std::cmp::min(usdc_received, borrow.accrued_interest);
+                borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_paid);
+                principal_repaid =
+                    std::cmp::min(usdc_received.saturating_sub(interest_paid), borrow.amount);
+                borrow.amount = borrow.amount.saturating_sub(principal_repaid);
                 break;
             }
         }
 
         position.last_gad_crank = now;
+        position.total_gad_liquidated_usd = position
+            .total_gad_liquidated_usd
+            .saturating_add(sol_value_usd(sol_liquidated, sol_price)?);
         position.reputation.gad_events = position.reputation.gad_events.saturating_add(1);
+        position.last_update = now;
+        position.retain_collaterals(|c| c.amount > 0);
+        position.retain_borrows(|b| b.amount > 0 || b.accrued_interest > 0);
+        let collateral_amount = position.collateral_amount(&NATIVE_SOL_MINT);
+        let debt_amount = position.debt_amount(&usdc_mint);
+        let seq = position.next_event_seq();
+
+        let accounting_cpi = accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        );
+        accounting_cpi.record_collateral(
+            ctx.accounts.sol_collateral.to_account_info(),
+            CollateralChange::Withdrawn(sol_liquidated),
+        )?;
+        accounting_cpi.record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Repaid {
+                principal: principal_repaid,
+                received: usdc_received,
+            },
+        )?;
 
-        emit!(GadSwapExecuted {
-            position: ctx.accounts.position.key(),
-            sol_liquidated: 0, // TODO: track actual amount
+        emit_cpi!(GadSwapExecuted {
+            position: position_key,
+            sol_liquidated,
             usdc_received,
+            collateral_amount,
+            debt_amount,
             cranker: ctx.accounts.cranker.key(),
+            seq,
         });
 
-        msg!("GAD swap executed: received {} USDC", usdc_received);
+        msg!(
+            "GAD swap executed: sold {} lamports for {} USDC",
+            sol_liquidated,
+            usdc_received
+        );
         Ok(())
     }
 }
 
 // ========== HELPER FUNCTIONS ==========
 
-fn calculate_collateral_value(position: &Position, sol_price_feed: &PriceFeed) -> Result<u64> {
-    let mut total_usd: u64 = 0;
+/// What one crank may seize: the GAD rate for the position's LTV above
+/// `threshold_bps`, applied pro rata to the time since the last crank
+struct GadSchedule {
+    total_collateral_usd: u64,
+    total_borrow_usd: u64,
+    current_ltv_bps: u64,
+    gad_rate_bps: u64,
+    sol_to_liquidate: u64,
+}
 
-    for deposit in &position.collaterals {
-        match deposit.asset_type {
-            AssetType::SOL => {
-                let value = (deposit.amount as u128)
-                    .checked_mul(sol_price_feed.price_usd_6dec as u128)
-                    .ok_or(LegasiError::MathOverflow)?
-                    .checked_div(LAMPORTS_PER_SOL as u128)
-                    .ok_or(LegasiError::MathOverflow)? as u64;
-                total_usd = total_usd
-                    .checked_add(value)
-                    .ok_or(LegasiError::MathOverflow)?;
-            }
-            _ => {}
+fn gad_schedule(
+    position: &Position,
+    sol_price: u64,
+    debt_prices: &[DebtPrice],
+    threshold_bps: u64,
+    elapsed: i64,
+) -> Result<GadSchedule> {
+    let total_collateral_usd = calculate_collateral_value(position, sol_price)?;
+    require!(
+        total_collateral_usd > 0,
+        LegasiError::InsufficientCollateral
+    );
+    let total_borrow_usd = calculate_borrow_value(position, debt_prices)?;
+
+    let current_ltv_bps = total_borrow_usd
+        .checked_mul(BPS_DENOMINATOR)
+        .ok_or(LegasiError::MathOverflow)?
+        .checked_div(total_collateral_usd)
+        .ok_or(LegasiError::MathOverflow)?;
+    require!(
+        current_ltv_bps > threshold_bps,
+        LegasiError::LtvBelowGadThreshold
+    );
+
+    let gad_rate_bps = get_gad_rate_bps(current_ltv_bps, threshold_bps);
+    require!(gad_rate_bps > 0, LegasiError::NothingToLiquidate);
+
+    // Calculate amount to liquidate (pro-rata based on time elapsed)
+    let time_fraction = (elapsed as u128)
+        .checked_mul(BPS_DENOMINATOR as u128)
+        .ok_or(LegasiError::MathOverflow)?
+        .checked_div(SECONDS_PER_DAY as u128)
+        .ok_or(LegasiError::MathOverflow)? as u64;
+
+    let liquidate_fraction_bps = (gad_rate_bps as u128)
+        .checked_mul(time_fraction as u128)
+        .ok_or(LegasiError::MathOverflow)?
+        .checked_div(BPS_DENOMINATOR as u128)
+        .ok_or(LegasiError::MathOverflow)? as u64;
+
+    let sol_deposit = position.collateral_amount(&NATIVE_SOL_MINT);
+    require!(sol_deposit > 0, LegasiError::InsufficientCollateral);
+
+    let sol_to_liquidate = (sol_deposit as u128)
+        .checked_mul(liquidate_fraction_bps as u128)
+        .ok_or(LegasiError::MathOverflow)?
+        .checked_div(BPS_DENOMINATOR as u128)
+        .ok_or(LegasiError::MathOverflow)? as u64;
+    require!(sol_to_liquidate > 0, LegasiError::NothingToLiquidate);
+
+    Ok(GadSchedule {
+        total_collateral_usd,
+        total_borrow_usd,
+        current_ltv_bps,
+        gad_rate_bps,
+        sol_to_liquidate,
+    })
+}
+
+/// Write `debt_usd` (6 decimals) off the position's borrows in order,
+/// interest before principal, converting it to each asset's base units at its
+/// debt price. Returns the principal written down per mint, in base units
+fn write_down_debt(
+    position: &mut Position,
+    debt_usd: u64,
+    debt_prices: &[DebtPrice],
+) -> Result<Vec<(Pubkey, u64)>> {
+    let mut remaining_usd = debt_usd;
+    let mut principal_written_down: Vec<(Pubkey, u64)> = Vec::new();
+    for borrow in position.borrows_mut() {
+        if remaining_usd == 0 {
+            break;
+        }
+        let price = debt_prices
+            .iter()
+            .find(|p| p.mint == borrow.mint)
+            .ok_or(LegasiError::DebtPriceFeedMissing)?;
+        let borrow_total = borrow.amount.saturating_add(borrow.accrued_interest);
+        let borrow_total_usd = price.value_usd(borrow_total)?;
+
+        // Clear the whole borrow if the value covers it, else convert what's left
+        let reduction = if remaining_usd >= borrow_total_usd {
+            remaining_usd = remaining_usd.saturating_sub(borrow_total_usd);
+            borrow_total
+        } else {
+            let reduction = std::cmp::min(price.amount_for_usd(remaining_usd)?, borrow_total);
+            remaining_usd = 0;
+            reduction
+        };
+
+        // First reduce interest, then principal
+        let interest_reduction = std::cmp::min(reduction, borrow.accrued_interest);
+        borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_reduction);
+
+        let principal_reduction =
+            std::cmp::min(reduction.saturating_sub(interest_reduction), borrow.amount);
+        borrow.amount = borrow.amount.saturating_sub(principal_reduction);
+        if principal_reduction > 0 {
+            principal_written_down.push((borrow.mint, principal_reduction));
         }
     }
+    Ok(principal_written_down)
+}
 
-    Ok(total_usd)
+/// USD value (6 decimals) of `lamports` at `sol_price`
+fn sol_value_usd(lamports: u64, sol_price: u64) -> Result<u64> {
+    Ok((lamports as u128)
+        .checked_mul(sol_price as u128)
+        .ok_or(LegasiError::MathOverflow)?
+        .checked_div(LAMPORTS_PER_SOL as u128)
+        .ok_or(LegasiError::MathOverflow)? as u64)
 }
 
-fn calculate_borrow_value(position: &Position) -> Result<u64> {
+fn calculate_collateral_value(position: &Position, sol_price: u64) -> Result<u64> {
     let mut total_usd: u64 = 0;
 
-    for borrow in &position.borrows {
-        match borrow.asset_type {
-            AssetType::USDC | AssetType::EURC => {
-                let value = borrow
-                    .amount
-                    .checked_add(borrow.accrued_interest)
-                    .ok_or(LegasiError::MathOverflow)?;
-                total_usd = total_usd
-                    .checked_add(value)
-                    .ok_or(LegasiError::MathOverflow)?;
-            }
-            _ => {}
+    for deposit in position.collaterals() {
+        if deposit.mint == NATIVE_SOL_MINT {
+            let value = (deposit.amount as u128)
+                .checked_mul(sol_price as u128)
+                .ok_or(LegasiError::MathOverflow)?
+                .checked_div(LAMPORTS_PER_SOL as u128)
+                .ok_or(LegasiError::MathOverflow)? as u64;
+            total_usd = total_usd
+                .checked_add(value)
+                .ok_or(LegasiError::MathOverflow)?;
         }
     }
 
     Ok(total_usd)
 }
 
+fn calculate_borrow_value(position: &Position, debt_prices: &[DebtPrice]) -> Result<u64> {
+    Ok(total_debt_usd(
+        position
+            .borrows()
+            .iter()
+            .map(|b| (b.mint, b.amount.saturating_add(b.accrued_interest))),
+        debt_prices,
+    )?)
+}
+
 // GAD swap event
 #[event]
 pub struct GadSwapExecuted {
     pub position: Pubkey,
     pub sol_liquidated: u64,
     pub usdc_received: u64,
+    /// Position's SOL deposit afterwards
+    pub collateral_amount: u64,
+    /// Position's debt in the swap output mint afterwards
+    pub debt_amount: u64,
     pub cranker: Pubkey,
+    pub seq: u64,
 }
 
 // ========== ACCOUNTS ==========
@@ -417,22 +570,31 @@ pub struct GadSwapExecuted {
 pub struct ConfigureGad<'info> {
     #[account(
         mut,
-        seeds = [b"position", owner.key().as_ref()],
-        bump = position.bump,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
         has_one = owner
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     pub owner: Signer<'info>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct CrankGad<'info> {
     #[account(
         mut,
-        seeds = [b"position", position.owner.as_ref()],
-        bump = position.bump
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     #[account(seeds = [b"protocol"], bump = protocol.bump, has_one = treasury)]
     pub protocol: Account<'info, Protocol>,
     /// CHECK: SOL vault PDA
@@ -442,25 +604,46 @@ pub struct CrankGad<'info> {
         bump
     )]
     pub sol_vault: UncheckedAccount<'info>,
+    /// SOL collateral config (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_collateral: Box<Account<'info, Collateral>>,
     /// CHECK: Treasury
     #[account(mut)]
     pub treasury: UncheckedAccount<'info>,
-    #[account(seeds = [b"price", &[AssetType::SOL as u8]], bump)]
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
+    )]
     pub sol_price_feed: Account<'info, PriceFeed>,
     #[account(mut)]
     pub cranker: Signer<'info>,
     pub system_program: Program<'info, System>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
 /// Accounts for GAD with Jupiter swap
+#[event_cpi]
 #[derive(Accounts)]
 pub struct CrankGadWithSwap<'info> {
     #[account(
         mut,
-        seeds = [b"position", position.owner.as_ref()],
-        bump = position.bump
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     #[account(seeds = [b"protocol"], bump = protocol.bump)]
     pub protocol: Account<'info, Protocol>,
     /// CHECK: SOL vault PDA (source for swap)
@@ -470,15 +653,128 @@ pub struct CrankGadWithSwap<'info> {
         bump
     )]
     pub sol_vault: UncheckedAccount<'info>,
-    /// USDC vault to receive swap output
-    #[account(mut)]
-    pub usdc_vault: Account<'info, TokenAccount>,
+    /// Lending vault of the swap output mint, which the proceeds repay into
+    #[account(
+        mut,
+        seeds = [b"lending_vault", usdc_mint.key().as_ref()],
+        bump,
+        seeds::program = lending_program::ID,
+        token::mint = usdc_mint,
+        token::token_program = token_program
+    )]
+    pub usdc_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub usdc_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// SOL collateral config (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_collateral: Box<Account<'info, Collateral>>,
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_price_feed: Box<Account<'info, PriceFeed>>,
+    /// Borrowable config for the swap output mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", usdc_mint.key().as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
     /// CHECK: Jupiter Aggregator v6
     #[account(address = jupiter::ID)]
     pub jupiter_program: UncheckedAccount<'info>,
     #[account(mut)]
     pub cranker: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     // Additional Jupiter accounts passed via remaining_accounts
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use legasi_schema::testing;
+
+    fn price(mint: Pubkey, decimals: u8, price_usd_6dec: u64) -> DebtPrice {
+        DebtPrice {
+            mint,
+            decimals,
+            price_usd_6dec,
+        }
+    }
+
+    #[test]
+    fn test_write_down_converts_usd_to_asset_units() {
+        let eurc = Pubkey::new_unique();
+        let mut position: Position = testing::zeroed_copy();
+        position.add_debt(eurc, 1_000_000_000).unwrap(); // 1,000 EURC
+
+        // $540 at EUR/USD 1.08 is 500 EURC
+        let prices = [price(eurc, 6, 1_080_000)];
+        let written_down = write_down_debt(&mut position, 540_000_000, &prices).unwrap();
+
+        assert_eq!(written_down, vec![(eurc, 500_000_000)]);
+        assert_eq!(position.debt_amount(&eurc), 500_000_000);
+    }
+
+    #[test]
+    fn test_write_down_spills_into_next_borrow() {
+        let (usdc, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut position: Position = testing::zeroed_copy();
+        position.add_debt(usdc, 100_000_000).unwrap();
+        position.borrows_mut()[0].accrued_interest = 1_000_000;
+        position.add_debt(other, 10_000_000_000).unwrap(); // 10 units, 9 decimals
+
+        // $110: clears the $101 USDC debt, then $9 of a $2 asset is 4.5 units
+        let prices = [price(usdc, 6, 1_000_000), price(other, 9, 2_000_000)];
+        let written_down = write_down_debt(&mut position, 110_000_000, &prices).unwrap();
+
+        assert_eq!(
+            written_down,
+            vec![(usdc, 100_000_000), (other, 4_500_000_000)]
+        );
+        assert_eq!(position.debt_amount(&usdc), 0);
+        assert_eq!(position.debt_amount(&other), 5_500_000_000);
+    }
+
+    #[test]
+    fn test_schedule_starts_at_configured_threshold() {
+        let usdc = Pubkey::new_unique();
+        let mut position: Position = testing::zeroed_copy();
+        position
+            .add_collateral(NATIVE_SOL_MINT, 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        position.add_debt(usdc, 780_000_000).unwrap();
+        let prices = [price(usdc, 6, 1_000_000)];
+        let sol_price = 100_000_000; // 10 SOL at $100: 78% LTV
+
+        let result = gad_schedule(&position, sol_price, &prices, 8000, SECONDS_PER_DAY);
+        assert!(result.is_err());
+
+        let schedule = gad_schedule(&position, sol_price, &prices, 7500, SECONDS_PER_DAY).unwrap();
+        assert_eq!(schedule.current_ltv_bps, 7800);
+        // (300 bps excess)^2 / 100 = 900 bps/day, over one day
+        assert_eq!(schedule.gad_rate_bps, 900);
+        assert_eq!(schedule.sol_to_liquidate, 900_000_000);
+    }
+
+    #[test]
+    fn test_write_down_requires_price_for_each_borrow() {
+        let eurc = Pubkey::new_unique();
+        let mut position: Position = testing::zeroed_copy();
+        position.add_debt(eurc, 1_000_000).unwrap();
+
+        let prices = [price(Pubkey::new_unique(), 6, 1_000_000)];
+        assert!(write_down_debt(&mut position, 1_000_000, &prices).is_err());
+    }
 }
@@ -2,13 +2,28 @@ use anchor_lang::prelude::*;
 use anchor_lang::solana_program::program::invoke;
 use anchor_lang::solana_program::program::invoke_signed;
 use anchor_lang::solana_program::system_instruction;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
 
-// Import only read-only types from core (not Position, AgentConfig, etc. which are init'ed here)
 use legasi_core::{
+    accounting::{AccountingCpi, ACCOUNTING_SEED},
     constants::*,
     errors::LegasiError,
-    state::{Protocol, Borrowable, Collateral, PriceFeed, AssetType, LpPool},
+    events::{
+        Borrowed, CollateralDeposited, CollateralWithdrawn, InterestAccrued, MarginModeChanged,
+        PositionClosed, PositionCreated, Repaid,
+    },
+    program::LegasiCore,
+    state::{AccountingSource, BorrowableChange, CollateralChange, MarginMode, ProtocolRole},
+    tokens::{transfer_in, transfer_out},
+    valuation::{
+        load_core_account, load_debt_prices, ltv_bps, total_debt_usd, DebtPrice, DebtPriceMode,
+    },
+};
+use legasi_lp::{program::LegasiLp, PoolCpi};
+use legasi_schema::{
+    lp_program,
+    migrate::{upgrade_agent_config, upgrade_position, zero_copy_data},
+    AgentConfig, Borrowable, Collateral, Delegate, LpPool, Position, PriceFeed, Protocol,
 };
 
 pub mod x402;
@@ -17,117 +32,74 @@ pub use x402::*;
 declare_id!("9356RoSbLTzWE55ab6GktcTocaNhPuBEDZvsmqjkCZYw");
 
 // ========== LOCAL STATE (owned by this program) ==========
-// These structs are defined here (not imported from core) to ensure proper PDA ownership
+// Position and AgentConfig are shared through legasi_schema
 
-/// User lending position
+/// X402 payment receipt
 #[account]
 #[derive(InitSpace)]
-pub struct Position {
-    pub owner: Pubkey,
-    #[max_len(8)]
-    pub collaterals: Vec<CollateralDeposit>,
-    #[max_len(4)]
-    pub borrows: Vec<BorrowedAmount>,
-    pub last_update: i64,
-    pub last_gad_crank: i64,
-    pub gad_enabled: bool,
-    pub total_gad_liquidated_usd: u64,
-    pub reputation: Reputation,
-    pub bump: u8,
-}
-
-/// Single collateral deposit entry
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
-pub struct CollateralDeposit {
-    pub asset_type: AssetType,
-    pub amount: u64,
-}
-
-/// Single borrow entry
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
-pub struct BorrowedAmount {
-    pub asset_type: AssetType,
+pub struct X402Receipt {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub recipient: Pubkey,
     pub amount: u64,
-    pub accrued_interest: u64,
-}
-
-/// On-chain reputation score
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, Default)]
-pub struct Reputation {
-    pub successful_repayments: u32,
-    pub total_repaid_usd: u64,
-    pub gad_events: u32,
-    pub account_age_days: u32,
+    pub paid_at: i64,
+    pub tx_signature: [u8; 64],
+    pub bump: u8,
 }
 
-impl Reputation {
-    pub fn get_score(&self) -> u32 {
-        let base = std::cmp::min(self.successful_repayments * 50, 500);
-        let age_bonus = std::cmp::min(self.account_age_days / 30 * 10, 100);
-        base.saturating_add(age_bonus)
-            .saturating_sub(self.gad_events * 100)
-    }
-
-    /// Returns LTV bonus in basis points based on reputation
-    pub fn get_ltv_bonus_bps(&self) -> u16 {
-        match self.get_score() {
-            s if s >= 400 => 500, // +5% LTV
-            s if s >= 200 => 300, // +3% LTV
-            s if s >= 100 => 100, // +1% LTV
-            _ => 0,
-        }
+/// Accounting CPIs to core, signed by this program's accounting PDA
+fn accounting<'info>(
+    core_program: &Program<'info, LegasiCore>,
+    authority: &UncheckedAccount<'info>,
+    authority_bump: u8,
+) -> AccountingCpi<'info> {
+    AccountingCpi {
+        core_program: core_program.to_account_info(),
+        authority: authority.to_account_info(),
+        authority_bump,
+        source: AccountingSource::Lending,
     }
 }
 
-/// Agent configuration for autonomous operations
-#[account]
-#[derive(InitSpace)]
-pub struct AgentConfig {
-    pub position: Pubkey,
-    pub operator: Pubkey,
-    pub daily_borrow_limit: u64,
-    pub daily_borrowed: u64,
-    pub period_start: i64,
-    pub auto_repay_enabled: bool,
-    pub x402_enabled: bool,
-    pub alerts_enabled: bool,
-    pub alert_threshold_bps: u16,
-    pub bump: u8,
-}
-
-impl AgentConfig {
-    /// Check if agent can borrow more today
-    pub fn can_borrow(&self, amount: u64, current_time: i64) -> bool {
-        let seconds_per_day: i64 = 86400;
-        if current_time - self.period_start >= seconds_per_day {
-            return amount <= self.daily_borrow_limit;
-        }
-        self.daily_borrowed.saturating_add(amount) <= self.daily_borrow_limit
+/// LP pool CPIs, signed by this program's accounting PDA
+fn pool<'info>(
+    lp_program: &Program<'info, LegasiLp>,
+    authority: &UncheckedAccount<'info>,
+    authority_bump: u8,
+) -> PoolCpi<'info> {
+    PoolCpi {
+        lp_program: lp_program.to_account_info(),
+        authority: authority.to_account_info(),
+        authority_bump,
+        source: AccountingSource::Lending,
     }
+}
 
-    /// Record a borrow against daily limit
-    pub fn record_borrow(&mut self, amount: u64, current_time: i64) {
-        let seconds_per_day: i64 = 86400;
-        if current_time - self.period_start >= seconds_per_day {
-            self.period_start = current_time;
-            self.daily_borrowed = amount;
-        } else {
-            self.daily_borrowed = self.daily_borrowed.saturating_add(amount);
-        }
+/// Rewrite a migrated account in place, resizing it to `data` with rent from `payer`
+fn rewrite_account<'info>(
+    account: &UncheckedAccount<'info>,
+    data: &[u8],
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let space = data.len();
+    let top_up = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[
+                payer.to_account_info(),
+                account.to_account_info(),
+                system_program.to_account_info(),
+            ],
+        )?;
     }
-}
 
-/// X402 payment receipt
-#[account]
-#[derive(InitSpace)]
-pub struct X402Receipt {
-    pub payment_id: [u8; 32],
-    pub payer: Pubkey,
-    pub recipient: Pubkey,
-    pub amount: u64,
-    pub paid_at: i64,
-    pub tx_signature: [u8; 64],
-    pub bump: u8,
+    account.realloc(space, true)?;
+    account.try_borrow_mut_data()?.copy_from_slice(data);
+    Ok(())
 }
 
 #[program]
@@ -140,26 +112,125 @@ pub mod legasi_lending {
         Ok(())
     }
 
-    /// Initialize a user position
-    pub fn initialize_position(ctx: Context<InitializePosition>) -> Result<()> {
-        let position = &mut ctx.accounts.position;
+    /// Initialize a user position. `position_id` 0 is the owner's primary
+    /// position; other ids open sub-accounts with separate collateral and risk
+    pub fn initialize_position(ctx: Context<InitializePosition>, position_id: u8) -> Result<()> {
+        // Slots and counters start zeroed
+        let mut position = ctx.accounts.position.load_init()?;
         position.owner = ctx.accounts.owner.key();
-        position.collaterals = Vec::new();
-        position.borrows = Vec::new();
         position.last_update = Clock::get()?.unix_timestamp;
         position.last_gad_crank = Clock::get()?.unix_timestamp;
-        position.gad_enabled = true;
-        position.total_gad_liquidated_usd = 0;
-        position.reputation = Reputation::default();
+        position.set_gad_enabled(true);
         position.bump = ctx.bumps.position;
+        position.version = Position::VERSION;
+        position.position_id = position_id;
+
+        emit_cpi!(PositionCreated {
+            owner: ctx.accounts.owner.key(),
+            position: ctx.accounts.position.key(),
+            position_id,
+            seq: 0,
+        });
+
+        msg!(
+            "Position {} initialized for {}",
+            position_id,
+            ctx.accounts.owner.key()
+        );
+        Ok(())
+    }
+
+    /// Upgrade a position to the current layout (permissionless, payer funds the rent)
+    pub fn migrate_position(ctx: Context<MigratePosition>) -> Result<()> {
+        let upgraded = upgrade_position(&ctx.accounts.position.try_borrow_data()?)?;
+        let Some(position) = upgraded else {
+            msg!("Position already at layout v{}", Position::VERSION);
+            return Ok(());
+        };
+
+        rewrite_account(
+            &ctx.accounts.position,
+            &zero_copy_data(&*position),
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        msg!("Position migrated to layout v{}", Position::VERSION);
+        Ok(())
+    }
 
-        msg!("Position initialized for {}", ctx.accounts.owner.key());
+    /// Switch a position between cross and isolated margin. An isolated
+    /// position holds one collateral and one borrow asset; open a sub-account
+    /// per pair to keep their risk apart
+    pub fn set_margin_mode(ctx: Context<SetMarginMode>, margin_mode: MarginMode) -> Result<()> {
+        let mut position = ctx.accounts.position.load_mut()?;
+        position.set_margin_mode(margin_mode)?;
+        let seq = position.next_event_seq();
+
+        emit_cpi!(MarginModeChanged {
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            margin_mode,
+            seq,
+        });
+
+        msg!("Margin mode set to {:?}", margin_mode);
+        Ok(())
+    }
+
+    /// Close an empty position and return its rent to the owner. Any dust
+    /// left in the SOL vault is swept to the owner too; the agent config has
+    /// to be closed first
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        let mut position = ctx.accounts.position.load_mut()?;
+        let position_id = position.position_id;
+        let seq = position.next_event_seq();
+        drop(position);
+
+        let dust_swept = ctx.accounts.sol_vault.lamports();
+        if dust_swept > 0 {
+            let position_key = ctx.accounts.position.key();
+            let vault_bump = ctx.bumps.sol_vault;
+            let seeds: &[&[u8]] = &[b"sol_vault", position_key.as_ref(), &[vault_bump]];
+
+            invoke_signed(
+                &system_instruction::transfer(
+                    ctx.accounts.sol_vault.key,
+                    ctx.accounts.owner.key,
+                    dust_swept,
+                ),
+                &[
+                    ctx.accounts.sol_vault.to_account_info(),
+                    ctx.accounts.owner.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                &[seeds],
+            )?;
+        }
+
+        emit_cpi!(PositionClosed {
+            owner: ctx.accounts.owner.key(),
+            position: ctx.accounts.position.key(),
+            position_id,
+            dust_swept,
+            seq,
+        });
+
+        msg!("Position {} closed", position_id);
         Ok(())
     }
 
     /// Deposit SOL as collateral
     pub fn deposit_sol(ctx: Context<DepositSol>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_DEPOSIT)?;
+        ctx.accounts
+            .sol_collateral
+            .require_not_paused(PAUSE_DEPOSIT)?;
+        require!(
+            ctx.accounts.sol_collateral.is_active,
+            LegasiError::AssetNotActive
+        );
 
         invoke(
             &system_instruction::transfer(
@@ -174,33 +245,31 @@ pub mod legasi_lending {
             ],
         )?;
 
-        let position = &mut ctx.accounts.position;
-
-        // Find or create SOL deposit
-        let mut found = false;
-        for deposit in position.collaterals.iter_mut() {
-            if deposit.asset_type == AssetType::SOL {
-                deposit.amount = deposit
-                    .amount
-                    .checked_add(amount)
-                    .ok_or(LegasiError::MathOverflow)?;
-                found = true;
-                break;
-            }
-        }
+        let mut position = ctx.accounts.position.load_mut()?;
+        position.add_collateral(NATIVE_SOL_MINT, amount)?;
+        position.last_update = Clock::get()?.unix_timestamp;
+        let collateral_amount = position.collateral_amount(&NATIVE_SOL_MINT);
+        let seq = position.next_event_seq();
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_collateral(
+            ctx.accounts.sol_collateral.to_account_info(),
+            CollateralChange::Deposited(amount),
+        )?;
 
-        if !found {
-            require!(
-                position.collaterals.len() < MAX_COLLATERAL_TYPES,
-                LegasiError::MaxCollateralTypesReached
-            );
-            position.collaterals.push(CollateralDeposit {
-                asset_type: AssetType::SOL,
-                amount,
-            });
-        }
+        emit_cpi!(CollateralDeposited {
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            mint: NATIVE_SOL_MINT,
+            amount,
+            collateral_amount,
+            seq,
+        });
 
-        position.last_update = Clock::get()?.unix_timestamp;
         msg!("Deposited {} lamports", amount);
         Ok(())
     }
@@ -208,64 +277,63 @@ pub mod legasi_lending {
     /// Deposit SPL token as collateral (cbBTC)
     pub fn deposit_token(ctx: Context<DepositToken>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_DEPOSIT)?;
+        ctx.accounts
+            .collateral_config
+            .require_not_paused(PAUSE_DEPOSIT)?;
         require!(
             ctx.accounts.collateral_config.is_active,
             LegasiError::AssetNotActive
         );
 
-        let asset_type = ctx.accounts.collateral_config.asset_type;
+        let mint = ctx.accounts.collateral_config.mint;
 
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    to: ctx.accounts.token_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(),
-                },
-            ),
+        // Credit what the vault received (net of any transfer fee)
+        let amount = transfer_in(
+            &ctx.accounts.token_program,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.token_vault,
+            ctx.accounts.owner.to_account_info(),
             amount,
         )?;
 
-        let position = &mut ctx.accounts.position;
-
-        let mut found = false;
-        for deposit in position.collaterals.iter_mut() {
-            if deposit.asset_type == asset_type {
-                deposit.amount = deposit
-                    .amount
-                    .checked_add(amount)
-                    .ok_or(LegasiError::MathOverflow)?;
-                found = true;
-                break;
-            }
-        }
-
-        if !found {
-            require!(
-                position.collaterals.len() < MAX_COLLATERAL_TYPES,
-                LegasiError::MaxCollateralTypesReached
-            );
-            position
-                .collaterals
-                .push(CollateralDeposit { asset_type, amount });
-        }
-
+        let mut position = ctx.accounts.position.load_mut()?;
+        position.add_collateral(mint, amount)?;
         position.last_update = Clock::get()?.unix_timestamp;
+        let collateral_amount = position.collateral_amount(&mint);
+        let seq = position.next_event_seq();
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_collateral(
+            ctx.accounts.collateral_config.to_account_info(),
+            CollateralChange::Deposited(amount),
+        )?;
 
-        let collateral_config = &mut ctx.accounts.collateral_config;
-        collateral_config.total_deposited = collateral_config
-            .total_deposited
-            .checked_add(amount)
-            .ok_or(LegasiError::MathOverflow)?;
+        emit_cpi!(CollateralDeposited {
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            mint,
+            amount,
+            collateral_amount,
+            seq,
+        });
 
-        msg!("Deposited {} {:?}", amount, asset_type);
+        msg!("Deposited {} of {}", amount, mint);
         Ok(())
     }
 
     /// Borrow stablecoins (USDC, EURC)
     pub fn borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_BORROW)?;
+        ctx.accounts
+            .borrowable_config
+            .require_not_paused(PAUSE_BORROW)?;
         require!(
             ctx.accounts.borrowable_config.is_active,
             LegasiError::AssetNotActive
@@ -275,13 +343,18 @@ pub mod legasi_lending {
             LegasiError::InsufficientLiquidity
         );
 
-        let asset_type = ctx.accounts.borrowable_config.asset_type;
-        let sol_price = ctx.accounts.sol_price_feed.price_usd_6dec;
+        let now = Clock::get()?.unix_timestamp;
+        // Conservative collateral price: lower of spot and TWAP
+        let sol_price = ctx
+            .accounts
+            .sol_price_feed
+            .fresh_collateral_price_for_borrow(now)?;
 
         // Calculate collateral value
+        let mut position = ctx.accounts.position.load_mut()?;
         let mut total_collateral_usd: u64 = 0;
-        for deposit in &ctx.accounts.position.collaterals {
-            if deposit.asset_type == AssetType::SOL || deposit.asset_type == AssetType::CbBTC {
+        for deposit in position.collaterals() {
+            if deposit.mint == NATIVE_SOL_MINT {
                 let value = (deposit.amount as u128)
                     .checked_mul(sol_price as u128)
                     .ok_or(LegasiError::MathOverflow)?
@@ -293,31 +366,35 @@ pub mod legasi_lending {
             }
         }
 
-        // Calculate borrow value
-        let mut current_borrow_usd: u64 = 0;
-        for borrow in &ctx.accounts.position.borrows {
-            let value = borrow
-                .amount
-                .checked_add(borrow.accrued_interest)
-                .ok_or(LegasiError::MathOverflow)?;
-            current_borrow_usd = current_borrow_usd
-                .checked_add(value)
-                .ok_or(LegasiError::MathOverflow)?;
-        }
+        // Calculate borrow value through each borrowable's price feed
+        // remaining_accounts: (Borrowable, PriceFeed) pairs for the other borrowed assets
+        let borrow_price = DebtPrice::from_feed(
+            &ctx.accounts.borrowable_config,
+            &ctx.accounts.borrow_price_feed,
+            now,
+            DebtPriceMode::Conservative,
+        )?;
+        let mut debt_prices =
+            load_debt_prices(ctx.remaining_accounts, now, DebtPriceMode::Conservative)?;
+        debt_prices.insert(0, borrow_price);
+        let current_borrow_usd = total_debt_usd(
+            position
+                .borrows()
+                .iter()
+                .map(|b| (b.mint, b.amount.saturating_add(b.accrued_interest))),
+            &debt_prices,
+        )?;
         let new_borrow_usd = current_borrow_usd
-            .checked_add(amount)
+            .checked_add(borrow_price.value_usd(amount)?)
             .ok_or(LegasiError::MathOverflow)?;
 
-        // Check LTV
-        let base_ltv = DEFAULT_SOL_MAX_LTV_BPS as u64;
-        let reputation_bonus = ctx.accounts.position.reputation.get_ltv_bonus_bps() as u64;
-        let effective_max_ltv = base_ltv.saturating_add(reputation_bonus);
-
-        let max_borrow = total_collateral_usd
-            .checked_mul(effective_max_ltv)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(LegasiError::MathOverflow)?;
+        // Check LTV against the collateral's max LTV in force, plus the reputation bonus
+        let reputation_bonus = position.reputation.get_ltv_bonus_bps() as u64;
+        let max_borrow = ctx.accounts.sol_collateral.max_borrow_usd(
+            total_collateral_usd,
+            reputation_bonus,
+            now,
+        )?;
 
         require!(new_borrow_usd <= max_borrow, LegasiError::ExceedsLTV);
 
@@ -326,61 +403,61 @@ pub mod legasi_lending {
         let vault_bump = ctx.bumps.borrow_vault;
         let seeds: &[&[u8]] = &[b"lending_vault", mint.as_ref(), &[vault_bump]];
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.borrow_vault.to_account_info(),
-                    to: ctx.accounts.user_token_account.to_account_info(),
-                    authority: ctx.accounts.borrow_vault.to_account_info(),
-                },
-                &[seeds],
-            ),
+        transfer_out(
+            &ctx.accounts.token_program,
+            ctx.accounts.borrow_vault.to_account_info(),
+            &ctx.accounts.mint,
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.borrow_vault.to_account_info(),
+            &[seeds],
             amount,
         )?;
 
         // Update position
-        let position = &mut ctx.accounts.position;
-
-        let mut found = false;
-        for borrow in position.borrows.iter_mut() {
-            if borrow.asset_type == asset_type {
-                borrow.amount = borrow
-                    .amount
-                    .checked_add(amount)
-                    .ok_or(LegasiError::MathOverflow)?;
-                found = true;
-                break;
-            }
-        }
+        position.add_debt(mint, amount)?;
+        position.last_update = now;
+        let debt_amount = position.debt_amount(&mint);
+        let seq = position.next_event_seq();
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Borrowed(amount),
+        )?;
 
-        if !found {
-            require!(
-                position.borrows.len() < MAX_BORROW_TYPES,
-                LegasiError::MaxBorrowTypesReached
-            );
-            position.borrows.push(BorrowedAmount {
-                asset_type,
-                amount,
-                accrued_interest: 0,
-            });
-        }
+        emit_cpi!(Borrowed {
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            mint,
+            amount,
+            debt_amount,
+            new_ltv_bps: Some(ltv_bps(new_borrow_usd, total_collateral_usd)),
+            seq,
+        });
 
-        position.last_update = Clock::get()?.unix_timestamp;
-        msg!("Borrowed {} {:?}", amount, asset_type);
+        msg!("Borrowed {} of {}", amount, mint);
         Ok(())
     }
 
     /// Repay borrowed amount
     pub fn repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_REPAY)?;
+        ctx.accounts
+            .borrowable_config
+            .require_not_paused(PAUSE_REPAY)?;
 
-        let asset_type = ctx.accounts.borrowable_config.asset_type;
+        let mint = ctx.accounts.borrowable_config.mint;
 
         // Find borrow
+        let mut position = ctx.accounts.position.load_mut()?;
         let mut total_owed: u64 = 0;
-        for borrow in &ctx.accounts.position.borrows {
-            if borrow.asset_type == asset_type {
+        for borrow in position.borrows() {
+            if borrow.mint == mint {
                 total_owed = borrow
                     .amount
                     .checked_add(borrow.accrued_interest)
@@ -390,37 +467,32 @@ pub mod legasi_lending {
         }
         require!(total_owed > 0, LegasiError::PositionNotFound);
 
-        let repay_amount = std::cmp::min(amount, total_owed);
-
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    to: ctx.accounts.repay_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(),
-                },
-            ),
-            repay_amount,
+        // Credit what the vault received (net of any transfer fee)
+        let repay_amount = transfer_in(
+            &ctx.accounts.token_program,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.repay_vault,
+            ctx.accounts.owner.to_account_info(),
+            std::cmp::min(amount, total_owed),
         )?;
 
         // Update position
-        let position = &mut ctx.accounts.position;
-
-        for borrow in position.borrows.iter_mut() {
-            if borrow.asset_type == asset_type {
-                let interest_payment = std::cmp::min(repay_amount, borrow.accrued_interest);
-                borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_payment);
-                let principal = repay_amount.saturating_sub(interest_payment);
-                borrow.amount = borrow.amount.saturating_sub(principal);
+        let mut interest_paid: u64 = 0;
+        let mut principal_repaid: u64 = 0;
+        for borrow in position.borrows_mut() {
+            if borrow.mint == mint {
+                interest_paid = std::cmp::min(repay_amount, borrow.accrued_interest);
+                borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_paid);
+                principal_repaid =
+                    std::cmp::min(repay_amount.saturating_sub(interest_paid), borrow.amount);
+                borrow.amount = borrow.amount.saturating_sub(principal_repaid);
                 break;
             }
         }
 
         // Remove empty borrows
-        position
-            .borrows
-            .retain(|b| b.amount > 0 || b.accrued_interest > 0);
+        position.retain_borrows(|b| b.amount > 0 || b.accrued_interest > 0);
 
         position.reputation.successful_repayments =
             position.reputation.successful_repayments.saturating_add(1);
@@ -429,21 +501,134 @@ pub mod legasi_lending {
             .total_repaid_usd
             .saturating_add(repay_amount);
         position.last_update = Clock::get()?.unix_timestamp;
+        let debt_amount = position.debt_amount(&mint);
+        let seq = position.next_event_seq();
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Repaid {
+                principal: principal_repaid,
+                received: repay_amount,
+            },
+        )?;
+
+        emit_cpi!(Repaid {
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            payer: ctx.accounts.owner.key(),
+            mint,
+            amount: principal_repaid.saturating_add(interest_paid),
+            interest_paid,
+            debt_amount,
+            seq,
+        });
+
+        msg!("Repaid {} of {}", repay_amount, mint);
+        Ok(())
+    }
+
+    /// Repay a position's debt from any signer's token account. The payer
+    /// gets no other rights over the position, and the owner's reputation
+    /// only counts their own repayments
+    pub fn repay_for(ctx: Context<RepayFor>, amount: u64) -> Result<()> {
+        require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_REPAY)?;
+        ctx.accounts
+            .borrowable_config
+            .require_not_paused(PAUSE_REPAY)?;
+
+        let mint = ctx.accounts.borrowable_config.mint;
+
+        let mut position = ctx.accounts.position.load_mut()?;
+        let total_owed = position.debt_amount(&mint);
+        require!(total_owed > 0, LegasiError::PositionNotFound);
+
+        // Credit what the vault received (net of any transfer fee)
+        let repay_amount = transfer_in(
+            &ctx.accounts.token_program,
+            ctx.accounts.payer_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.repay_vault,
+            ctx.accounts.payer.to_account_info(),
+            std::cmp::min(amount, total_owed),
+        )?;
+
+        let mut interest_paid: u64 = 0;
+        let mut principal_repaid: u64 = 0;
+        for borrow in position.borrows_mut() {
+            if borrow.mint == mint {
+                interest_paid = std::cmp::min(repay_amount, borrow.accrued_interest);
+                borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_paid);
+                principal_repaid =
+                    std::cmp::min(repay_amount.saturating_sub(interest_paid), borrow.amount);
+                borrow.amount = borrow.amount.saturating_sub(principal_repaid);
+                break;
+            }
+        }
+        position.retain_borrows(|b| b.amount > 0 || b.accrued_interest > 0);
+        position.last_update = Clock::get()?.unix_timestamp;
+        let owner = position.owner;
+        let debt_amount = position.debt_amount(&mint);
+        let seq = position.next_event_seq();
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Repaid {
+                principal: principal_repaid,
+                received: repay_amount,
+            },
+        )?;
 
-        msg!("Repaid {} {:?}", repay_amount, asset_type);
+        emit_cpi!(Repaid {
+            position: ctx.accounts.position.key(),
+            owner,
+            payer: ctx.accounts.payer.key(),
+            mint,
+            amount: principal_repaid.saturating_add(interest_paid),
+            interest_paid,
+            debt_amount,
+            seq,
+        });
+
+        msg!(
+            "{} repaid {} of {} for {}",
+            ctx.accounts.payer.key(),
+            repay_amount,
+            mint,
+            owner
+        );
         Ok(())
     }
 
     /// Withdraw SOL collateral
     pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_WITHDRAW)?;
+        ctx.accounts
+            .sol_collateral
+            .require_not_paused(PAUSE_WITHDRAW)?;
 
-        let sol_price = ctx.accounts.sol_price_feed.price_usd_6dec;
+        let now = Clock::get()?.unix_timestamp;
+        let sol_price = ctx
+            .accounts
+            .sol_price_feed
+            .fresh_collateral_price_for_borrow(now)?;
 
         // Find SOL deposit
+        let mut position = ctx.accounts.position.load_mut()?;
         let mut sol_amount: u64 = 0;
-        for deposit in &ctx.accounts.position.collaterals {
-            if deposit.asset_type == AssetType::SOL {
+        for deposit in position.collaterals() {
+            if deposit.mint == NATIVE_SOL_MINT {
                 sol_amount = deposit.amount;
                 break;
             }
@@ -451,7 +636,9 @@ pub mod legasi_lending {
         require!(sol_amount >= amount, LegasiError::InsufficientCollateral);
 
         // Check LTV after withdrawal if has borrows
-        if !ctx.accounts.position.borrows.is_empty() {
+        let new_ltv_bps = if position.borrows().is_empty() {
+            0
+        } else {
             let remaining = sol_amount
                 .checked_sub(amount)
                 .ok_or(LegasiError::MathOverflow)?;
@@ -461,24 +648,25 @@ pub mod legasi_lending {
                 .checked_div(LAMPORTS_PER_SOL as u128)
                 .ok_or(LegasiError::MathOverflow)? as u64;
 
-            let mut total_borrow: u64 = 0;
-            for borrow in &ctx.accounts.position.borrows {
-                total_borrow = total_borrow
-                    .checked_add(borrow.amount)
-                    .ok_or(LegasiError::MathOverflow)?;
-                total_borrow = total_borrow
-                    .checked_add(borrow.accrued_interest)
-                    .ok_or(LegasiError::MathOverflow)?;
-            }
+            // remaining_accounts: (Borrowable, PriceFeed) pairs for each borrowed asset
+            let debt_prices =
+                load_debt_prices(ctx.remaining_accounts, now, DebtPriceMode::Conservative)?;
+            let total_borrow = total_debt_usd(
+                position
+                    .borrows()
+                    .iter()
+                    .map(|b| (b.mint, b.amount.saturating_add(b.accrued_interest))),
+                &debt_prices,
+            )?;
 
-            let max_borrow = remaining_value
-                .checked_mul(DEFAULT_SOL_MAX_LTV_BPS as u64)
-                .ok_or(LegasiError::MathOverflow)?
-                .checked_div(BPS_DENOMINATOR)
-                .ok_or(LegasiError::MathOverflow)?;
+            let max_borrow = ctx
+                .accounts
+                .sol_collateral
+                .max_borrow_usd(remaining_value, 0, now)?;
 
             require!(total_borrow <= max_borrow, LegasiError::ExceedsLTV);
-        }
+            ltv_bps(total_borrow, remaining_value)
+        };
 
         // Transfer SOL
         let position_key = ctx.accounts.position.key();
@@ -500,16 +688,36 @@ pub mod legasi_lending {
         )?;
 
         // Update position
-        let position = &mut ctx.accounts.position;
-
-        for deposit in position.collaterals.iter_mut() {
-            if deposit.asset_type == AssetType::SOL {
+        for deposit in position.collaterals_mut() {
+            if deposit.mint == NATIVE_SOL_MINT {
                 deposit.amount = deposit.amount.saturating_sub(amount);
                 break;
             }
         }
-        position.collaterals.retain(|c| c.amount > 0);
-        position.last_update = Clock::get()?.unix_timestamp;
+        position.retain_collaterals(|c| c.amount > 0);
+        position.last_update = now;
+        let collateral_amount = position.collateral_amount(&NATIVE_SOL_MINT);
+        let seq = position.next_event_seq();
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_collateral(
+            ctx.accounts.sol_collateral.to_account_info(),
+            CollateralChange::Withdrawn(amount),
+        )?;
+
+        emit_cpi!(CollateralWithdrawn {
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            mint: NATIVE_SOL_MINT,
+            amount,
+            collateral_amount,
+            new_ltv_bps,
+            seq,
+        });
 
         msg!("Withdrew {} lamports", amount);
         Ok(())
@@ -518,7 +726,7 @@ pub mod legasi_lending {
     /// Accrue interest on a position's borrows
     /// Can be called by anyone (cranker) to update interest
     pub fn accrue_position_interest(ctx: Context<AccruePositionInterest>) -> Result<()> {
-        let position = &mut ctx.accounts.position;
+        let mut position = ctx.accounts.position.load_mut()?;
         let now = Clock::get()?.unix_timestamp;
         let elapsed = now.saturating_sub(position.last_update);
 
@@ -527,13 +735,21 @@ pub mod legasi_lending {
             return Ok(());
         }
 
-        for borrow in position.borrows.iter_mut() {
+        // remaining_accounts: Borrowable config for each borrowed asset
+        let borrowables = ctx
+            .remaining_accounts
+            .iter()
+            .map(load_core_account::<Borrowable>)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut accrued: Vec<(Pubkey, u64)> = Vec::new();
+        for borrow in position.borrows_mut() {
             // Get interest rate for this asset (from borrowable config)
-            let annual_rate_bps = match borrow.asset_type {
-                AssetType::USDC => 800, // 8% APR
-                AssetType::EURC => 700, // 7% APR
-                _ => 0,
-            };
+            let annual_rate_bps = borrowables
+                .iter()
+                .find(|b| b.mint == borrow.mint)
+                .map(|b| b.interest_rate_bps)
+                .ok_or(LegasiError::AssetNotSupported)?;
 
             if annual_rate_bps == 0 || borrow.amount == 0 {
                 continue;
@@ -553,14 +769,78 @@ pub mod legasi_lending {
                 .unwrap_or(0) as u64;
 
             borrow.accrued_interest = borrow.accrued_interest.saturating_add(interest);
+            if interest > 0 {
+                accrued.push((borrow.mint, interest));
+            }
         }
 
         position.last_update = now;
 
+        let position_key = ctx.accounts.position.key();
+        let events: Vec<InterestAccrued> = accrued
+            .into_iter()
+            .map(|(mint, interest)| InterestAccrued {
+                position: position_key,
+                mint,
+                interest,
+                debt_amount: position.debt_amount(&mint),
+                seq: position.next_event_seq(),
+            })
+            .collect();
+        for event in events {
+            emit_cpi!(event);
+        }
+
         msg!("Interest accrued for position");
         Ok(())
     }
 
+    /// Apply changes the leverage program made to a position's funds, which
+    /// it has already moved through its own vaults and this program's SOL vault
+    /// CPI only, signed by the leverage program's accounting PDA
+    pub fn record_position_changes(
+        ctx: Context<RecordPositionChanges>,
+        source: AccountingSource,
+        changes: Vec<PositionChange>,
+    ) -> Result<()> {
+        require!(
+            source == AccountingSource::Leverage,
+            LegasiError::Unauthorized
+        );
+        source.require_authority(&ctx.accounts.authority.key())?;
+
+        let mut position = ctx.accounts.position.load_mut()?;
+        for change in changes {
+            match change {
+                PositionChange::Deposited { mint, amount } => {
+                    position.add_collateral(mint, amount)?;
+                }
+                PositionChange::Borrowed { mint, amount } => {
+                    position.add_debt(mint, amount)?;
+                }
+                PositionChange::Repaid { mint, amount } => {
+                    // Interest first, then principal
+                    for borrow in position.borrows_mut() {
+                        if borrow.mint == mint {
+                            let interest_paid = std::cmp::min(amount, borrow.accrued_interest);
+                            borrow.accrued_interest -= interest_paid;
+                            borrow.amount = borrow.amount.saturating_sub(amount - interest_paid);
+                            break;
+                        }
+                    }
+                    position.retain_borrows(|b| b.amount > 0 || b.accrued_interest > 0);
+                    position.reputation.successful_repayments =
+                        position.reputation.successful_repayments.saturating_add(1);
+                    position.reputation.total_repaid_usd =
+                        position.reputation.total_repaid_usd.saturating_add(amount);
+                }
+            }
+        }
+        position.last_update = Clock::get()?.unix_timestamp;
+        position.next_event_seq();
+        Ok(())
+    }
+
     /// Off-ramp borrowed stablecoins via Bridge.xyz
     /// Burns the borrowed tokens and initiates fiat transfer
     pub fn offramp_via_bridge(
@@ -574,10 +854,10 @@ pub mod legasi_lending {
         require!(destination_iban.len() > 10, LegasiError::InvalidAmount); // Basic IBAN validation
 
         // Check user has borrowed this amount
-        let position = &ctx.accounts.position;
+        let position = ctx.accounts.position.load()?;
         let mut borrowed_amount: u64 = 0;
-        for borrow in &position.borrows {
-            if borrow.asset_type == AssetType::USDC || borrow.asset_type == AssetType::EURC {
+        for borrow in position.borrows() {
+            if borrow.mint == ctx.accounts.stablecoin_mint.key() {
                 borrowed_amount = borrowed_amount.saturating_add(borrow.amount);
             }
         }
@@ -587,10 +867,10 @@ pub mod legasi_lending {
         );
 
         // Burn tokens from user's account
-        token::burn(
+        token_interface::burn(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                token::Burn {
+                token_interface::Burn {
                     mint: ctx.accounts.stablecoin_mint.to_account_info(),
                     from: ctx.accounts.user_token_account.to_account_info(),
                     authority: ctx.accounts.owner.to_account_info(),
@@ -609,11 +889,11 @@ pub mod legasi_lending {
         offramp.created_at = Clock::get()?.unix_timestamp;
         offramp.bump = ctx.bumps.offramp_request;
 
-        emit!(OfframpRequested {
+        emit_cpi!(OfframpRequested {
             owner: ctx.accounts.owner.key(),
             amount,
             destination_iban,
-            asset_type: AssetType::USDC, // TODO: detect from mint
+            mint: ctx.accounts.stablecoin_mint.key(),
         });
 
         msg!(
@@ -624,6 +904,44 @@ pub mod legasi_lending {
         Ok(())
     }
 
+    /// Record the bridge's progress on an off-ramp request (treasury manager only)
+    pub fn update_offramp_status(
+        ctx: Context<UpdateOfframpStatus>,
+        _request_id: u64,
+        status: OfframpStatus,
+    ) -> Result<()> {
+        let offramp = &mut ctx.accounts.offramp_request;
+        require!(
+            offramp.status.can_transition_to(status),
+            LegasiError::InvalidStatusTransition
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        offramp.status = status;
+        if status.is_terminal() {
+            offramp.completed_at = now;
+        }
+
+        emit_cpi!(OfframpStatusUpdated {
+            request: offramp.key(),
+            owner: offramp.owner,
+            status,
+            updated_at: now,
+        });
+
+        msg!("Off-ramp request status: {:?}", status);
+        Ok(())
+    }
+
+    /// Close a completed, failed or cancelled off-ramp request and return its rent
+    pub fn close_offramp_request(
+        _ctx: Context<CloseOfframpRequest>,
+        request_id: u64,
+    ) -> Result<()> {
+        msg!("Off-ramp request {} closed", request_id);
+        Ok(())
+    }
+
     // ========== AGENT FUNCTIONS ==========
 
     /// Configure agent settings for a position
@@ -646,14 +964,36 @@ pub mod legasi_lending {
         agent_config.alerts_enabled = true;
         agent_config.alert_threshold_bps = alert_threshold_bps;
         agent_config.bump = ctx.bumps.agent_config;
+        agent_config.version = AgentConfig::VERSION;
 
         msg!("Agent configured with {} daily limit", daily_borrow_limit);
         Ok(())
     }
 
-    /// Update existing agent configuration
-    pub fn update_agent_config(
-        ctx: Context<UpdateAgentConfig>,
+    /// Upgrade an agent config to the current layout (permissionless, payer funds the rent)
+    pub fn migrate_agent_config(ctx: Context<MigrateAgentConfig>) -> Result<()> {
+        let upgraded = upgrade_agent_config(&ctx.accounts.agent_config.try_borrow_data()?)?;
+        let Some(agent_config) = upgraded else {
+            msg!("Agent config already at layout v{}", AgentConfig::VERSION);
+            return Ok(());
+        };
+
+        let mut data = Vec::with_capacity(8 + AgentConfig::INIT_SPACE);
+        agent_config.try_serialize(&mut data)?;
+        rewrite_account(
+            &ctx.accounts.agent_config,
+            &data,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        msg!("Agent config migrated to layout v{}", AgentConfig::VERSION);
+        Ok(())
+    }
+
+    /// Update existing agent configuration
+    pub fn update_agent_config(
+        ctx: Context<UpdateAgentConfig>,
         daily_borrow_limit: u64,
         auto_repay_enabled: bool,
         x402_enabled: bool,
@@ -669,26 +1009,104 @@ pub mod legasi_lending {
         Ok(())
     }
 
+    /// Close a position's agent config and return its rent to the owner
+    pub fn close_agent_config(_ctx: Context<CloseAgentConfig>) -> Result<()> {
+        msg!("Agent config closed");
+        Ok(())
+    }
+
+    /// Grant `authority` scoped access to the agent instructions of a
+    /// position (a session key), so the owner key can stay cold
+    pub fn add_delegate(
+        ctx: Context<AddDelegate>,
+        permissions: u8,
+        daily_borrow_limit: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        require!(
+            permissions != 0 && permissions & !DELEGATE_ALL == 0,
+            LegasiError::InvalidDelegatePermissions
+        );
+        let now = Clock::get()?.unix_timestamp;
+        require!(expires_at > now, LegasiError::InvalidExpiry);
+
+        let delegate = &mut ctx.accounts.delegate;
+        delegate.position = ctx.accounts.position.key();
+        delegate.owner = ctx.accounts.owner.key();
+        delegate.authority = ctx.accounts.authority.key();
+        delegate.permissions = permissions;
+        delegate.daily_borrow_limit = daily_borrow_limit;
+        delegate.daily_borrowed = 0;
+        delegate.period_start = now;
+        delegate.expires_at = expires_at;
+        delegate.bump = ctx.bumps.delegate;
+        delegate.version = Delegate::VERSION;
+        ctx.accounts.position.load_mut()?.record_delegate_added()?;
+
+        emit_cpi!(DelegateAdded {
+            position: delegate.position,
+            owner: delegate.owner,
+            authority: delegate.authority,
+            permissions,
+            daily_borrow_limit,
+            expires_at,
+        });
+
+        msg!(
+            "Delegate {} added until {}",
+            ctx.accounts.authority.key(),
+            expires_at
+        );
+        Ok(())
+    }
+
+    /// Revoke a delegate and return its rent to the owner
+    pub fn revoke_delegate(ctx: Context<RevokeDelegate>) -> Result<()> {
+        ctx.accounts.position.load_mut()?.record_delegate_revoked();
+        let delegate = &ctx.accounts.delegate;
+        emit_cpi!(DelegateRevoked {
+            position: delegate.position,
+            owner: delegate.owner,
+            authority: delegate.authority,
+        });
+
+        msg!("Delegate {} revoked", delegate.authority);
+        Ok(())
+    }
+
     /// Agent borrow - respects daily limits
     /// Can be called by the agent (position owner) autonomously
     pub fn agent_borrow(ctx: Context<AgentBorrow>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_BORROW)?;
+        ctx.accounts
+            .borrowable_config
+            .require_not_paused(PAUSE_BORROW)?;
+        require!(
+            ctx.accounts.borrowable_config.is_active,
+            LegasiError::AssetNotActive
+        );
 
         let agent_config = &ctx.accounts.agent_config;
         let now = Clock::get()?.unix_timestamp;
 
         // Check daily limit
-        require!(
-            agent_config.can_borrow(amount, now),
-            LegasiError::ExceedsLTV // Reuse error for "exceeds limit"
-        );
+        agent_config.require_within_limit(amount, now)?;
+        if let Some(delegate) = &ctx.accounts.delegate {
+            delegate.authorize(DELEGATE_BORROW, now)?;
+            delegate.require_within_limit(amount, now)?;
+        }
 
         // Get price and calculate max borrow (same as regular borrow)
-        let sol_price = ctx.accounts.sol_price_feed.price_usd_6dec;
+        let sol_price = ctx
+            .accounts
+            .sol_price_feed
+            .fresh_collateral_price_for_borrow(now)?;
 
+        let mut position = ctx.accounts.position.load_mut()?;
         let mut total_collateral_usd: u64 = 0;
-        for deposit in &ctx.accounts.position.collaterals {
-            if deposit.asset_type == AssetType::SOL || deposit.asset_type == AssetType::CbBTC {
+        for deposit in position.collaterals() {
+            if deposit.mint == NATIVE_SOL_MINT {
                 let value = (deposit.amount as u128)
                     .checked_mul(sol_price as u128)
                     .ok_or(LegasiError::MathOverflow)?
@@ -700,96 +1118,91 @@ pub mod legasi_lending {
             }
         }
 
-        let mut current_borrow_usd: u64 = 0;
-        for borrow in &ctx.accounts.position.borrows {
-            let value = borrow
-                .amount
-                .checked_add(borrow.accrued_interest)
-                .ok_or(LegasiError::MathOverflow)?;
-            current_borrow_usd = current_borrow_usd
-                .checked_add(value)
-                .ok_or(LegasiError::MathOverflow)?;
-        }
-
-        // Apply reputation bonus to LTV
-        let base_ltv = DEFAULT_SOL_MAX_LTV_BPS as u64;
-        let reputation_bonus = ctx.accounts.position.reputation.get_ltv_bonus_bps() as u64;
-        let effective_ltv = base_ltv.saturating_add(reputation_bonus);
+        // remaining_accounts: (Borrowable, PriceFeed) pairs for each borrowed asset,
+        // including the pool's borrowable mint
+        let debt_prices =
+            load_debt_prices(ctx.remaining_accounts, now, DebtPriceMode::Conservative)?;
+        let borrow_price = debt_prices
+            .iter()
+            .find(|p| p.mint == ctx.accounts.lp_pool.borrowable_mint)
+            .ok_or(LegasiError::DebtPriceFeedMissing)?;
+        let current_borrow_usd = total_debt_usd(
+            position
+                .borrows()
+                .iter()
+                .map(|b| (b.mint, b.amount.saturating_add(b.accrued_interest))),
+            &debt_prices,
+        )?;
 
-        let max_borrow = total_collateral_usd
-            .checked_mul(effective_ltv)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(LegasiError::MathOverflow)?;
+        // Apply reputation bonus to the collateral's max LTV in force
+        let reputation_bonus = position.reputation.get_ltv_bonus_bps() as u64;
+        let max_borrow = ctx.accounts.sol_collateral.max_borrow_usd(
+            total_collateral_usd,
+            reputation_bonus,
+            now,
+        )?;
 
         let new_total_borrow = current_borrow_usd
-            .checked_add(amount)
+            .checked_add(borrow_price.value_usd(amount)?)
             .ok_or(LegasiError::MathOverflow)?;
         require!(new_total_borrow <= max_borrow, LegasiError::ExceedsLTV);
-
-        // Transfer from vault to agent
-        let pool_bump = ctx.accounts.lp_pool.bump;
-        let borrowable_mint = ctx.accounts.lp_pool.borrowable_mint;
-        let seeds: &[&[u8]] = &[b"lp_pool", borrowable_mint.as_ref(), &[pool_bump]];
-
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.borrow_vault.to_account_info(),
-                    to: ctx.accounts.agent_token_account.to_account_info(),
-                    authority: ctx.accounts.lp_pool.to_account_info(),
-                },
-                &[seeds],
-            ),
+        let new_ltv_bps = ltv_bps(new_total_borrow, total_collateral_usd);
+
+        // Borrow from the pool to the agent
+        pool(
+            &ctx.accounts.lp_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .lend(
+            ctx.accounts.lp_pool.to_account_info(),
+            ctx.accounts.borrow_vault.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.agent_token_account.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
             amount,
         )?;
 
         // Update position
-        let position = &mut ctx.accounts.position;
-        let asset_type = AssetType::USDC; // Default to USDC for agents
-
-        let mut found = false;
-        for borrow in position.borrows.iter_mut() {
-            if borrow.asset_type == asset_type {
-                borrow.amount = borrow
-                    .amount
-                    .checked_add(amount)
-                    .ok_or(LegasiError::MathOverflow)?;
-                found = true;
-                break;
-            }
-        }
-        if !found {
-            require!(
-                position.borrows.len() < 4,
-                LegasiError::MaxBorrowTypesReached
-            );
-            position.borrows.push(BorrowedAmount {
-                asset_type,
-                amount,
-                accrued_interest: 0,
-            });
-        }
+        let mint = ctx.accounts.lp_pool.borrowable_mint;
+        position.add_debt(mint, amount)?;
         position.last_update = now;
+        let debt_amount = position.debt_amount(&mint);
+        let seq = position.next_event_seq();
 
         // Update agent config daily borrowed
         let agent_config = &mut ctx.accounts.agent_config;
         agent_config.record_borrow(amount, now);
+        if let Some(delegate) = &mut ctx.accounts.delegate {
+            delegate.record_borrow(amount, now);
+        }
+        let daily_remaining = agent_config
+            .daily_borrow_limit
+            .saturating_sub(agent_config.daily_borrowed);
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Borrowed(amount),
+        )?;
 
-        // Update pool
-        let lp_pool = &mut ctx.accounts.lp_pool;
-        lp_pool.total_borrowed = lp_pool
-            .total_borrowed
-            .checked_add(amount)
-            .ok_or(LegasiError::MathOverflow)?;
-
-        emit!(AgentBorrowed {
+        emit_cpi!(Borrowed {
+            position: ctx.accounts.position.key(),
+            owner: position.owner,
+            mint,
+            amount,
+            debt_amount,
+            new_ltv_bps: Some(new_ltv_bps),
+            seq,
+        });
+        emit_cpi!(AgentBorrowed {
             position: ctx.accounts.position.key(),
             amount,
-            daily_remaining: agent_config
-                .daily_borrow_limit
-                .saturating_sub(agent_config.daily_borrowed),
+            daily_remaining,
         });
 
         msg!("Agent borrowed {} USDC", amount);
@@ -799,63 +1212,99 @@ pub mod legasi_lending {
     /// Agent auto-repay - automatically repay debt when USDC is received
     pub fn agent_auto_repay(ctx: Context<AgentAutoRepay>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_REPAY)?;
+        ctx.accounts
+            .borrowable_config
+            .require_not_paused(PAUSE_REPAY)?;
         require!(
             ctx.accounts.agent_config.auto_repay_enabled,
             LegasiError::Unauthorized
         );
+        if let Some(delegate) = &ctx.accounts.delegate {
+            delegate.authorize(DELEGATE_REPAY, Clock::get()?.unix_timestamp)?;
+        }
 
-        // Transfer from agent to vault
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.agent_token_account.to_account_info(),
-                    to: ctx.accounts.borrow_vault.to_account_info(),
-                    authority: ctx.accounts.agent.to_account_info(),
-                },
-            ),
-            amount,
-        )?;
+        let mint = ctx.accounts.lp_pool.borrowable_mint;
+        let mut position = ctx.accounts.position.load_mut()?;
+        let total_owed = position.debt_amount(&mint);
+        require!(total_owed > 0, LegasiError::PositionNotFound);
 
-        // Reduce debt
-        let position = &mut ctx.accounts.position;
-        let mut remaining = amount;
+        // Transfer from agent to vault (never more than is owed), crediting
+        // what the vault received
+        let repay_amount = transfer_in(
+            &ctx.accounts.token_program,
+            ctx.accounts.agent_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.borrow_vault,
+            ctx.accounts.agent.to_account_info(),
+            std::cmp::min(amount, total_owed),
+        )?;
 
-        for borrow in position.borrows.iter_mut() {
-            if remaining == 0 {
+        // Only debt in the pool's asset is repaid: interest first, then principal
+        let mut interest_paid: u64 = 0;
+        let mut principal_repaid: u64 = 0;
+        for borrow in position.borrows_mut() {
+            if borrow.mint == mint {
+                interest_paid = std::cmp::min(repay_amount, borrow.accrued_interest);
+                borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_paid);
+                principal_repaid =
+                    std::cmp::min(repay_amount.saturating_sub(interest_paid), borrow.amount);
+                borrow.amount = borrow.amount.saturating_sub(principal_repaid);
                 break;
             }
-
-            // First reduce interest
-            let interest_payment = std::cmp::min(remaining, borrow.accrued_interest);
-            borrow.accrued_interest = borrow.accrued_interest.saturating_sub(interest_payment);
-            remaining = remaining.saturating_sub(interest_payment);
-
-            // Then principal
-            let principal_payment = std::cmp::min(remaining, borrow.amount);
-            borrow.amount = borrow.amount.saturating_sub(principal_payment);
-            remaining = remaining.saturating_sub(principal_payment);
         }
 
-        position
-            .borrows
-            .retain(|b| b.amount > 0 || b.accrued_interest > 0);
+        position.retain_borrows(|b| b.amount > 0 || b.accrued_interest > 0);
         position.last_update = Clock::get()?.unix_timestamp;
         position.reputation.successful_repayments =
             position.reputation.successful_repayments.saturating_add(1);
-        position.reputation.total_repaid_usd =
-            position.reputation.total_repaid_usd.saturating_add(amount);
+        position.reputation.total_repaid_usd = position
+            .reputation
+            .total_repaid_usd
+            .saturating_add(repay_amount);
+        let debt_amount = position.debt_amount(&mint);
+        let seq = position.next_event_seq();
+
+        // Return the principal to the pool and accrue the interest to its LPs
+        pool(
+            &ctx.accounts.lp_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_repayment(
+            ctx.accounts.lp_pool.to_account_info(),
+            ctx.accounts.protocol.to_account_info(),
+            ctx.accounts.lp_accounting_authority.to_account_info(),
+            ctx.accounts.core_program.to_account_info(),
+            principal_repaid,
+            interest_paid,
+        )?;
 
-        // Update pool
-        let lp_pool = &mut ctx.accounts.lp_pool;
-        lp_pool.total_borrowed = lp_pool
-            .total_borrowed
-            .saturating_sub(amount.saturating_sub(remaining));
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Repaid {
+                principal: principal_repaid,
+                received: repay_amount,
+            },
+        )?;
 
-        msg!(
-            "Agent auto-repaid {} USDC",
-            amount.saturating_sub(remaining)
-        );
+        emit_cpi!(Repaid {
+            position: ctx.accounts.position.key(),
+            owner: position.owner,
+            payer: ctx.accounts.agent.key(),
+            mint,
+            amount: principal_repaid.saturating_add(interest_paid),
+            interest_paid,
+            debt_amount,
+            seq,
+        });
+
+        msg!("Agent auto-repaid {} of {}", repay_amount, mint);
         Ok(())
     }
 
@@ -876,8 +1325,12 @@ pub mod legasi_lending {
             ctx.accounts.agent_config.x402_enabled,
             LegasiError::Unauthorized
         );
+        if let Some(delegate) = &ctx.accounts.delegate {
+            delegate.authorize(DELEGATE_X402, now)?;
+        }
 
         let amount = payment_request.amount;
+        let mut borrowed_event = None;
 
         // Check agent has enough balance
         let agent_balance = ctx.accounts.agent_token_account.amount;
@@ -885,77 +1338,133 @@ pub mod legasi_lending {
         if agent_balance < amount && auto_borrow {
             // Need to borrow the difference
             let borrow_amount = amount.saturating_sub(agent_balance);
-
-            // Check daily limit
+            ctx.accounts.protocol.require_not_paused(PAUSE_BORROW)?;
+            ctx.accounts
+                .borrowable_config
+                .require_not_paused(PAUSE_BORROW)?;
             require!(
-                ctx.accounts.agent_config.can_borrow(borrow_amount, now),
-                LegasiError::ExceedsLTV
+                ctx.accounts.borrowable_config.is_active,
+                LegasiError::AssetNotActive
             );
+            let sol_price = ctx
+                .accounts
+                .sol_price_feed
+                .fresh_collateral_price_for_borrow(now)?;
+
+            // Check daily limit
+            ctx.accounts
+                .agent_config
+                .require_within_limit(borrow_amount, now)?;
+            if let Some(delegate) = &ctx.accounts.delegate {
+                // Paying is DELEGATE_X402; borrowing to pay also needs DELEGATE_BORROW
+                delegate.authorize(DELEGATE_BORROW, now)?;
+                delegate.require_within_limit(borrow_amount, now)?;
+            }
+
+            // Health check (same as agent_borrow)
+            let position = ctx.accounts.position.load()?;
+            let mut total_collateral_usd: u64 = 0;
+            for deposit in position.collaterals() {
+                if deposit.mint == NATIVE_SOL_MINT {
+                    let value = (deposit.amount as u128)
+                        .checked_mul(sol_price as u128)
+                        .ok_or(LegasiError::MathOverflow)?
+                        .checked_div(LAMPORTS_PER_SOL as u128)
+                        .ok_or(LegasiError::MathOverflow)? as u64;
+                    total_collateral_usd = total_collateral_usd
+                        .checked_add(value)
+                        .ok_or(LegasiError::MathOverflow)?;
+                }
+            }
+
+            // remaining_accounts: (Borrowable, PriceFeed) pairs for each borrowed asset,
+            // including the pool's borrowable mint
+            let debt_prices =
+                load_debt_prices(ctx.remaining_accounts, now, DebtPriceMode::Conservative)?;
+            let borrow_price = debt_prices
+                .iter()
+                .find(|p| p.mint == ctx.accounts.lp_pool.borrowable_mint)
+                .ok_or(LegasiError::DebtPriceFeedMissing)?;
+            let current_borrow_usd = total_debt_usd(
+                position
+                    .borrows()
+                    .iter()
+                    .map(|b| (b.mint, b.amount.saturating_add(b.accrued_interest))),
+                &debt_prices,
+            )?;
+
+            let max_borrow = ctx.accounts.sol_collateral.max_borrow_usd(
+                total_collateral_usd,
+                position.reputation.get_ltv_bonus_bps() as u64,
+                now,
+            )?;
+            let new_total_borrow = current_borrow_usd
+                .checked_add(borrow_price.value_usd(borrow_amount)?)
+                .ok_or(LegasiError::MathOverflow)?;
+            require!(new_total_borrow <= max_borrow, LegasiError::ExceedsLTV);
+            let new_ltv_bps = ltv_bps(new_total_borrow, total_collateral_usd);
+            drop(position);
 
             // Borrow from pool
-            let pool_bump = ctx.accounts.lp_pool.bump;
-            let borrowable_mint = ctx.accounts.lp_pool.borrowable_mint;
-            let seeds: &[&[u8]] = &[b"lp_pool", borrowable_mint.as_ref(), &[pool_bump]];
-
-            token::transfer(
-                CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.borrow_vault.to_account_info(),
-                        to: ctx.accounts.agent_token_account.to_account_info(),
-                        authority: ctx.accounts.lp_pool.to_account_info(),
-                    },
-                    &[seeds],
-                ),
+            pool(
+                &ctx.accounts.lp_program,
+                &ctx.accounts.accounting_authority,
+                ctx.bumps.accounting_authority,
+            )
+            .lend(
+                ctx.accounts.lp_pool.to_account_info(),
+                ctx.accounts.borrow_vault.to_account_info(),
+                ctx.accounts.mint.to_account_info(),
+                ctx.accounts.agent_token_account.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
                 borrow_amount,
             )?;
 
             // Update position debt
-            let position = &mut ctx.accounts.position;
-            let asset_type = AssetType::USDC;
-
-            let mut found = false;
-            for borrow in position.borrows.iter_mut() {
-                if borrow.asset_type == asset_type {
-                    borrow.amount = borrow
-                        .amount
-                        .checked_add(borrow_amount)
-                        .ok_or(LegasiError::MathOverflow)?;
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                position.borrows.push(BorrowedAmount {
-                    asset_type,
-                    amount: borrow_amount,
-                    accrued_interest: 0,
-                });
-            }
+            let mut position = ctx.accounts.position.load_mut()?;
+            let mint = ctx.accounts.lp_pool.borrowable_mint;
+            position.add_debt(mint, borrow_amount)?;
+            borrowed_event = Some(Borrowed {
+                position: ctx.accounts.position.key(),
+                owner: position.owner,
+                mint,
+                amount: borrow_amount,
+                debt_amount: position.debt_amount(&mint),
+                new_ltv_bps: Some(new_ltv_bps),
+                seq: position.next_event_seq(),
+            });
 
             // Update agent config
             let agent_config = &mut ctx.accounts.agent_config;
             agent_config.record_borrow(borrow_amount, now);
+            if let Some(delegate) = &mut ctx.accounts.delegate {
+                delegate.record_borrow(borrow_amount, now);
+            }
 
-            // Update pool
-            let lp_pool = &mut ctx.accounts.lp_pool;
-            lp_pool.total_borrowed = lp_pool
-                .total_borrowed
-                .checked_add(borrow_amount)
-                .ok_or(LegasiError::MathOverflow)?;
+            accounting(
+                &ctx.accounts.core_program,
+                &ctx.accounts.accounting_authority,
+                ctx.bumps.accounting_authority,
+            )
+            .record_borrowable(
+                ctx.accounts.borrowable_config.to_account_info(),
+                BorrowableChange::Borrowed(borrow_amount),
+            )?;
         }
 
         // Now pay the recipient
-        token::transfer(
+        token_interface::transfer_checked(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
+                TransferChecked {
                     from: ctx.accounts.agent_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
                     to: ctx.accounts.recipient_token_account.to_account_info(),
                     authority: ctx.accounts.agent.to_account_info(),
                 },
             ),
             amount,
+            ctx.accounts.mint.decimals,
         )?;
 
         // Create receipt
@@ -968,7 +1477,10 @@ pub mod legasi_lending {
         receipt.tx_signature = [0u8; 64]; // Filled by runtime
         receipt.bump = ctx.bumps.receipt;
 
-        emit!(X402PaymentMade {
+        if let Some(event) = borrowed_event {
+            emit_cpi!(event);
+        }
+        emit_cpi!(X402PaymentMade {
             payer: ctx.accounts.agent.key(),
             recipient: payment_request.recipient,
             amount,
@@ -997,6 +1509,68 @@ pub struct AgentBorrowed {
     pub daily_remaining: u64,
 }
 
+#[event]
+pub struct DelegateAdded {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub authority: Pubkey,
+    pub permissions: u8,
+    pub daily_borrow_limit: u64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct DelegateRevoked {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub authority: Pubkey,
+}
+
+/// Change the leverage program made to a position's funds (instruction argument)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PositionChange {
+    /// Collateral transferred into the position's vault
+    Deposited { mint: Pubkey, amount: u64 },
+    /// Debt paid out of the caller's vault
+    Borrowed { mint: Pubkey, amount: u64 },
+    /// Debt repaid into the caller's vault
+    Repaid { mint: Pubkey, amount: u64 },
+}
+
+/// Accounts a program signs position CPIs with
+#[cfg(feature = "cpi")]
+pub struct PositionCpi<'info> {
+    pub lending_program: AccountInfo<'info>,
+    /// The calling program's `[ACCOUNTING_SEED]` PDA
+    pub authority: AccountInfo<'info>,
+    pub authority_bump: u8,
+    pub source: AccountingSource,
+}
+
+#[cfg(feature = "cpi")]
+impl<'info> PositionCpi<'info> {
+    pub fn record_changes(
+        &self,
+        position: AccountInfo<'info>,
+        changes: Vec<PositionChange>,
+    ) -> Result<()> {
+        let bump = [self.authority_bump];
+        let seeds: &[&[u8]] = &[ACCOUNTING_SEED, &bump];
+        cpi::record_position_changes(
+            CpiContext::new_with_signer(
+                self.lending_program.clone(),
+                cpi::accounts::RecordPositionChanges {
+                    authority: self.authority.clone(),
+                    position,
+                },
+                &[seeds],
+            ),
+            self.source,
+            changes,
+        )
+    }
+}
+
 /// Off-ramp request status
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
 pub enum OfframpStatus {
@@ -1004,6 +1578,24 @@ pub enum OfframpStatus {
     Processing,
     Completed,
     Failed,
+    Cancelled,
+}
+
+impl OfframpStatus {
+    /// Whether the bridge is done with the request
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+
+    /// Pending requests can be picked up, settled or cancelled; once the
+    /// bridge is processing one it can only complete or fail
+    pub fn can_transition_to(&self, next: Self) -> bool {
+        match self {
+            Self::Pending => next != Self::Pending,
+            Self::Processing => matches!(next, Self::Completed | Self::Failed),
+            Self::Completed | Self::Failed | Self::Cancelled => false,
+        }
+    }
 }
 
 /// Off-ramp request account
@@ -1027,7 +1619,15 @@ pub struct OfframpRequested {
     pub owner: Pubkey,
     pub amount: u64,
     pub destination_iban: String,
-    pub asset_type: AssetType,
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct OfframpStatusUpdated {
+    pub request: Pubkey,
+    pub owner: Pubkey,
+    pub status: OfframpStatus,
+    pub updated_at: i64,
 }
 
 // ========== ACCOUNTS ==========
@@ -1039,121 +1639,333 @@ pub struct InitializeLendingVault<'info> {
         payer = admin,
         token::mint = mint,
         token::authority = lending_vault,
+        token::token_program = token_program,
         seeds = [b"lending_vault", mint.key().as_ref()],
         bump
     )]
-    pub lending_vault: Account<'info, TokenAccount>,
-    pub mint: Account<'info, Mint>,
+    pub lending_vault: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// Protocol state (owned by core program) - signer must be the risk admin
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID,
+        constraint = protocol.has_role(ProtocolRole::Risk, &admin.key()) @ LegasiError::Unauthorized
+    )]
+    pub protocol: Account<'info, Protocol>,
     #[account(mut)]
     pub admin: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
+#[instruction(position_id: u8)]
 pub struct InitializePosition<'info> {
     #[account(
         init,
         payer = owner,
         space = 8 + Position::INIT_SPACE,
-        seeds = [b"position", owner.key().as_ref()],
+        seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position_id)],
         bump
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     #[account(mut)]
     pub owner: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
+    /// CHECK: older layouts don't decode as `Position`; the discriminator and
+    /// layout are checked by `upgrade_position`
+    #[account(mut, owner = crate::ID)]
+    pub position: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetMarginMode<'info> {
+    #[account(mut, seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position.load()?.position_id)], bump = position.load()?.bump, has_one = owner)]
+    pub position: AccountLoader<'info, Position>,
+    pub owner: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
+        has_one = owner,
+        constraint = position.load()?.is_empty() @ LegasiError::PositionNotEmpty,
+        close = owner
+    )]
+    pub position: AccountLoader<'info, Position>,
+    /// CHECK: must not exist, so the config isn't left without a position
+    #[account(
+        seeds = [b"agent_config", position.key().as_ref()],
+        bump,
+        constraint = agent_config.data_is_empty() @ LegasiError::PositionNotEmpty
+    )]
+    pub agent_config: UncheckedAccount<'info>,
+    /// CHECK: SOL vault PDA
+    #[account(mut, seeds = [b"sol_vault", position.key().as_ref()], bump)]
+    pub sol_vault: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct DepositSol<'info> {
-    #[account(mut, seeds = [b"position", owner.key().as_ref()], bump = position.bump, has_one = owner)]
-    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position.load()?.position_id)], bump = position.load()?.bump, has_one = owner)]
+    pub position: AccountLoader<'info, Position>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// SOL collateral config (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_collateral: Box<Account<'info, Collateral>>,
     /// CHECK: SOL vault PDA
     #[account(mut, seeds = [b"sol_vault", position.key().as_ref()], bump)]
     pub sol_vault: UncheckedAccount<'info>,
     #[account(mut)]
     pub owner: Signer<'info>,
     pub system_program: Program<'info, System>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct DepositToken<'info> {
-    #[account(mut, seeds = [b"position", owner.key().as_ref()], bump = position.bump, has_one = owner)]
-    pub position: Account<'info, Position>,
-    #[account(mut, seeds = [b"collateral", collateral_config.mint.as_ref()], bump = collateral_config.bump)]
-    pub collateral_config: Account<'info, Collateral>,
+    #[account(mut, seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position.load()?.position_id)], bump = position.load()?.bump, has_one = owner)]
+    pub position: AccountLoader<'info, Position>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// Collateral config (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"collateral", collateral_config.mint.as_ref()],
+        bump = collateral_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub collateral_config: Box<Account<'info, Collateral>>,
+    #[account(address = collateral_config.mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut, seeds = [b"token_vault", collateral_config.mint.as_ref()], bump)]
-    pub token_vault: Account<'info, TokenAccount>,
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
     pub owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Borrow<'info> {
-    #[account(mut, seeds = [b"position", owner.key().as_ref()], bump = position.bump, has_one = owner)]
-    pub position: Account<'info, Position>,
-    /// Protocol state (owned by core program - no seeds validation)
-    pub protocol: Account<'info, Protocol>,
-    /// Borrowable config (owned by core program - no seeds validation)
-    pub borrowable_config: Account<'info, Borrowable>,
+    #[account(mut, seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position.load()?.position_id)], bump = position.load()?.bump, has_one = owner)]
+    pub position: AccountLoader<'info, Position>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// Borrowable config for the borrowed mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", borrowable_config.mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
     /// Lending vault (owned by this program)
     #[account(
         mut,
         seeds = [b"lending_vault", borrowable_config.mint.as_ref()],
         bump
     )]
-    pub borrow_vault: Account<'info, TokenAccount>,
+    pub borrow_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = borrowable_config.mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    /// Price feed (owned by core program - no seeds validation)
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// SOL price feed (owned by core program)
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
+    )]
     pub sol_price_feed: Account<'info, PriceFeed>,
+    /// SOL collateral config (owned by core program) - max LTV source
+    #[account(
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_collateral: Box<Account<'info, Collateral>>,
+    /// Borrowed asset price feed (owned by core program)
+    #[account(
+        seeds = [b"price", borrowable_config.mint.as_ref()],
+        bump = borrow_price_feed.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrow_price_feed: Box<Account<'info, PriceFeed>>,
     /// CHECK: SOL mint
     pub sol_mint: UncheckedAccount<'info>,
     pub owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Repay<'info> {
-    #[account(mut, seeds = [b"position", owner.key().as_ref()], bump = position.bump, has_one = owner)]
-    pub position: Account<'info, Position>,
-    /// Borrowable config (owned by core program)
-    pub borrowable_config: Account<'info, Borrowable>,
-    /// LP vault to transfer repaid tokens to
-    #[account(mut)]
-    pub repay_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position.load()?.position_id)], bump = position.load()?.bump, has_one = owner)]
+    pub position: AccountLoader<'info, Position>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// Borrowable config for the repaid mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", borrowable_config.mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
+    /// Lending vault `borrow` pays out of (owned by this program)
+    #[account(
+        mut,
+        seeds = [b"lending_vault", borrowable_config.mint.as_ref()],
+        bump
+    )]
+    pub repay_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = borrowable_config.mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, token::mint = mint)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
     pub owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+}
+
+/// Repay on behalf of a position's owner (any signer can pay)
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RepayFor<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
+    )]
+    pub position: AccountLoader<'info, Position>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// Borrowable config for the repaid mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", borrowable_config.mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
+    /// Lending vault `borrow` pays out of (owned by this program)
+    #[account(
+        mut,
+        seeds = [b"lending_vault", borrowable_config.mint.as_ref()],
+        bump
+    )]
+    pub repay_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = borrowable_config.mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, token::mint = mint)]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub payer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct WithdrawSol<'info> {
-    #[account(mut, seeds = [b"position", owner.key().as_ref()], bump = position.bump, has_one = owner)]
-    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position.load()?.position_id)], bump = position.load()?.bump, has_one = owner)]
+    pub position: AccountLoader<'info, Position>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// SOL collateral config (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_collateral: Box<Account<'info, Collateral>>,
     /// CHECK: SOL vault PDA
     #[account(mut, seeds = [b"sol_vault", position.key().as_ref()], bump)]
     pub sol_vault: UncheckedAccount<'info>,
-    /// Price feed (owned by core - no seeds validation)
+    /// SOL price feed (owned by core program)
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
+    )]
     pub sol_price_feed: Account<'info, PriceFeed>,
     /// CHECK: SOL mint
     pub sol_mint: UncheckedAccount<'info>,
     #[account(mut)]
     pub owner: Signer<'info>,
     pub system_program: Program<'info, System>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(request_id: u64)]
 pub struct OfframpViaBridge<'info> {
-    #[account(mut, seeds = [b"position", owner.key().as_ref()], bump = position.bump, has_one = owner)]
-    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [b"position", owner.key().as_ref(), Position::id_seed(&position.load()?.position_id)], bump = position.load()?.bump, has_one = owner)]
+    pub position: AccountLoader<'info, Position>,
     #[account(
         init,
         payer = owner,
@@ -1163,24 +1975,82 @@ pub struct OfframpViaBridge<'info> {
     )]
     pub offramp_request: Account<'info, OfframpRequest>,
     #[account(mut)]
-    pub stablecoin_mint: Account<'info, Mint>,
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
     pub owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct UpdateOfframpStatus<'info> {
+    #[account(
+        mut,
+        seeds = [b"offramp", offramp_request.owner.as_ref(), &request_id.to_le_bytes()],
+        bump = offramp_request.bump
+    )]
+    pub offramp_request: Account<'info, OfframpRequest>,
+    /// Protocol state (owned by core program) - signer must be the treasury manager
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID,
+        constraint = protocol.has_role(ProtocolRole::TreasuryManager, &treasury_manager.key()) @ LegasiError::Unauthorized
+    )]
+    pub protocol: Account<'info, Protocol>,
+    pub treasury_manager: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct CloseOfframpRequest<'info> {
+    #[account(
+        mut,
+        seeds = [b"offramp", owner.key().as_ref(), &request_id.to_le_bytes()],
+        bump = offramp_request.bump,
+        has_one = owner,
+        constraint = offramp_request.status.is_terminal() @ LegasiError::AccountStillActive,
+        close = owner
+    )]
+    pub offramp_request: Account<'info, OfframpRequest>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
 /// Accrue interest on a position (permissionless - anyone can crank)
+#[event_cpi]
 #[derive(Accounts)]
 pub struct AccruePositionInterest<'info> {
     #[account(
         mut,
-        seeds = [b"position", position.owner.as_ref()],
-        bump = position.bump
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
+}
+
+#[derive(Accounts)]
+pub struct RecordPositionChanges<'info> {
+    /// Accounting PDA of the calling program
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
+    )]
+    pub position: AccountLoader<'info, Position>,
 }
 
 // ========== AGENT ACCOUNTS ==========
@@ -1188,11 +2058,15 @@ pub struct AccruePositionInterest<'info> {
 #[derive(Accounts)]
 pub struct ConfigureAgent<'info> {
     #[account(
-        seeds = [b"position", owner.key().as_ref()],
-        bump = position.bump,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
         has_one = owner
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     #[account(
         init,
         payer = owner,
@@ -1206,14 +2080,29 @@ pub struct ConfigureAgent<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateAgentConfig<'info> {
+    /// CHECK: older layouts don't decode as `AgentConfig`; the discriminator
+    /// and layout are checked by `upgrade_agent_config`
+    #[account(mut, owner = crate::ID)]
+    pub agent_config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateAgentConfig<'info> {
     #[account(
-        seeds = [b"position", owner.key().as_ref()],
-        bump = position.bump,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
         has_one = owner
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     #[account(
         mut,
         seeds = [b"agent_config", position.key().as_ref()],
@@ -1223,14 +2112,89 @@ pub struct UpdateAgentConfig<'info> {
     pub owner: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct CloseAgentConfig<'info> {
+    #[account(
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
+        has_one = owner
+    )]
+    pub position: AccountLoader<'info, Position>,
+    #[account(
+        mut,
+        seeds = [b"agent_config", position.key().as_ref()],
+        bump = agent_config.bump,
+        close = owner
+    )]
+    pub agent_config: Account<'info, AgentConfig>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AddDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
+        has_one = owner
+    )]
+    pub position: AccountLoader<'info, Position>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Delegate::INIT_SPACE,
+        seeds = [b"delegate", position.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub delegate: Account<'info, Delegate>,
+    /// CHECK: the key being granted access; only its address is stored
+    pub authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"delegate", delegate.position.as_ref(), delegate.authority.as_ref()],
+        bump = delegate.bump,
+        has_one = owner,
+        close = owner
+    )]
+    pub delegate: Account<'info, Delegate>,
+    /// The delegate's position, which counts its open delegates
+    #[account(mut, address = delegate.position)]
+    pub position: AccountLoader<'info, Position>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct AgentBorrow<'info> {
     #[account(
         mut,
-        seeds = [b"position", position.owner.as_ref()],
-        bump = position.bump
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     #[account(
         mut,
         seeds = [b"agent_config", position.key().as_ref()],
@@ -1238,71 +2202,157 @@ pub struct AgentBorrow<'info> {
         constraint = agent_config.position == position.key()
     )]
     pub agent_config: Account<'info, AgentConfig>,
+    /// LP pool lent from (owned by LP program)
     #[account(
         mut,
         seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()],
-        bump = lp_pool.bump
+        bump = lp_pool.bump,
+        seeds::program = lp_program::ID
     )]
     pub lp_pool: Account<'info, LpPool>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// Borrowable config for the pool's mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", lp_pool.borrowable_mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
+    /// LP vault (owned by LP program)
     #[account(
         mut,
         seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
-        bump
+        bump,
+        seeds::program = lp_program::ID
     )]
-    pub borrow_vault: Account<'info, TokenAccount>,
+    pub borrow_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(mut)]
-    pub agent_token_account: Account<'info, TokenAccount>,
-    #[account(seeds = [b"price", &[AssetType::SOL as u8]], bump = sol_price_feed.bump)]
+    pub agent_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
+    )]
     pub sol_price_feed: Account<'info, PriceFeed>,
-    /// The agent (position owner) executing the borrow
-    #[account(constraint = agent.key() == position.owner)]
+    /// SOL collateral config (owned by core program) - max LTV source
+    #[account(
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_collateral: Box<Account<'info, Collateral>>,
+    /// The agent executing the borrow: the position owner or a delegate
+    #[account(constraint = agent.key() == position.load()?.owner || delegate.is_some())]
     pub agent: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    /// `agent`'s delegate account when it isn't the owner
+    #[account(
+        mut,
+        seeds = [b"delegate", position.key().as_ref(), agent.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Box<Account<'info, Delegate>>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+    pub lp_program: Program<'info, LegasiLp>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct AgentAutoRepay<'info> {
     #[account(
         mut,
-        seeds = [b"position", position.owner.as_ref()],
-        bump = position.bump
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     #[account(
         seeds = [b"agent_config", position.key().as_ref()],
         bump = agent_config.bump,
         constraint = agent_config.position == position.key()
     )]
     pub agent_config: Account<'info, AgentConfig>,
+    /// LP pool lent from (owned by LP program)
     #[account(
         mut,
         seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()],
-        bump = lp_pool.bump
+        bump = lp_pool.bump,
+        seeds::program = lp_program::ID
     )]
     pub lp_pool: Account<'info, LpPool>,
+    /// Protocol state (owned by core program) - insurance fund
+    #[account(
+        mut,
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// Borrowable config for the pool's mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", lp_pool.borrowable_mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
+    /// LP vault (owned by LP program)
     #[account(
         mut,
         seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
-        bump
+        bump,
+        seeds::program = lp_program::ID
     )]
-    pub borrow_vault: Account<'info, TokenAccount>,
+    pub borrow_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(mut)]
-    pub agent_token_account: Account<'info, TokenAccount>,
-    /// The agent executing auto-repay
-    #[account(constraint = agent.key() == position.owner)]
+    pub agent_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// The agent executing auto-repay: the position owner or a delegate
+    #[account(constraint = agent.key() == position.load()?.owner || delegate.is_some())]
     pub agent: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    /// `agent`'s delegate account when it isn't the owner
+    #[account(
+        seeds = [b"delegate", position.key().as_ref(), agent.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Box<Account<'info, Delegate>>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+    /// CHECK: LP program's PDA, which signs the insurance fee to core
+    #[account(seeds = [ACCOUNTING_SEED], bump, seeds::program = lp_program::ID)]
+    pub lp_accounting_authority: UncheckedAccount<'info>,
+    pub lp_program: Program<'info, LegasiLp>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(payment_request: X402PaymentRequest)]
 pub struct X402Pay<'info> {
     #[account(
         mut,
-        seeds = [b"position", position.owner.as_ref()],
-        bump = position.bump
+        seeds = [
+            b"position",
+            position.load()?.owner.as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump
     )]
-    pub position: Box<Account<'info, Position>>,
+    pub position: AccountLoader<'info, Position>,
     #[account(
         mut,
         seeds = [b"agent_config", position.key().as_ref()],
@@ -1310,25 +2360,56 @@ pub struct X402Pay<'info> {
         constraint = agent_config.position == position.key()
     )]
     pub agent_config: Box<Account<'info, AgentConfig>>,
+    /// LP pool lent from (owned by LP program)
     #[account(
         mut,
         seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()],
-        bump = lp_pool.bump
+        bump = lp_pool.bump,
+        seeds::program = lp_program::ID
     )]
     pub lp_pool: Box<Account<'info, LpPool>>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// Borrowable config for the pool's mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", lp_pool.borrowable_mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
+    /// LP vault (owned by LP program)
     #[account(
         mut,
         seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
-        bump
+        bump,
+        seeds::program = lp_program::ID
+    )]
+    pub borrow_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Collateral price feed - auto-borrow is blocked while its circuit breaker is tripped
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_price_feed: Box<Account<'info, PriceFeed>>,
+    /// SOL collateral config (owned by core program) - max LTV source
+    #[account(
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
     )]
-    pub borrow_vault: Account<'info, TokenAccount>,
+    pub sol_collateral: Box<Account<'info, Collateral>>,
     #[account(mut)]
-    pub agent_token_account: Account<'info, TokenAccount>,
+    pub agent_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         constraint = recipient_token_account.owner == payment_request.recipient
     )]
-    pub recipient_token_account: Account<'info, TokenAccount>,
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(
         init,
         payer = agent,
@@ -1337,9 +2418,490 @@ pub struct X402Pay<'info> {
         bump
     )]
     pub receipt: Box<Account<'info, X402Receipt>>,
-    /// The agent making the payment
-    #[account(mut, constraint = agent.key() == position.owner)]
+    /// The agent making the payment: the position owner or a delegate
+    #[account(mut, constraint = agent.key() == position.load()?.owner || delegate.is_some())]
     pub agent: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    /// `agent`'s delegate account when it isn't the owner
+    #[account(
+        mut,
+        seeds = [b"delegate", position.key().as_ref(), agent.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Box<Account<'info, Delegate>>>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+    pub lp_program: Program<'info, LegasiLp>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::entrypoint::ProgramResult;
+    use anchor_lang::InstructionData;
+    use anchor_spl::token::spl_token;
+    use legasi_schema::testing::{self, TestAccount};
+
+    const REQUEST_ID: u64 = 7;
+
+    fn pda(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, program_id)
+    }
+
+    fn offramp_request(owner: Pubkey, status: OfframpStatus) -> TestAccount {
+        let (key, bump) = pda(
+            &[b"offramp", owner.as_ref(), &REQUEST_ID.to_le_bytes()],
+            &crate::ID,
+        );
+        let request = OfframpRequest {
+            owner,
+            amount: 1_000_000,
+            destination_iban: "DE89370400440532013000".to_string(),
+            destination_name: "Alice".to_string(),
+            status,
+            created_at: 100,
+            completed_at: 0,
+            bump,
+        };
+        TestAccount::anchor(key, &request)
+    }
+
+    fn protocol(treasury_manager: Pubkey) -> TestAccount {
+        let (key, bump) = pda(&[b"protocol"], &legasi_core::ID);
+        let mut protocol: Protocol = testing::zeroed();
+        protocol.treasury_manager = treasury_manager;
+        protocol.bump = bump;
+        TestAccount::anchor(key, &protocol)
+    }
+
+    /// Accounts for `update_offramp_status`, signed by `signer`
+    fn update_accounts(
+        owner: Pubkey,
+        treasury_manager: Pubkey,
+        signer: Pubkey,
+    ) -> Vec<TestAccount> {
+        let (event_authority, _) = pda(&[b"__event_authority"], &crate::ID);
+        vec![
+            offramp_request(owner, OfframpStatus::Pending),
+            protocol(treasury_manager),
+            TestAccount::signer(signer),
+            TestAccount::new(event_authority, Pubkey::default(), Vec::new()),
+            TestAccount::program(crate::ID),
+        ]
+    }
+
+    /// Runs `update_offramp_status` through its handler, writing the request back
+    fn update_status(accounts: &mut [TestAccount], status: OfframpStatus) -> Result<()> {
+        let ix_data = instruction::UpdateOfframpStatus {
+            _request_id: REQUEST_ID,
+            status,
+        }
+        .data();
+        let infos = testing::infos(accounts);
+        crate::__private::__global::update_offramp_status(&crate::ID, &infos, &ix_data[8..])
+    }
+
+    fn try_close(request: TestAccount, owner: Pubkey) -> Result<()> {
+        let mut accounts = vec![request, TestAccount::signer(owner)];
+        let infos = testing::infos(&mut accounts);
+        let ix_data = REQUEST_ID.try_to_vec().unwrap();
+        testing::try_accounts::<CloseOfframpRequest>(&crate::ID, &infos, &ix_data)
+    }
+
+    fn stored_request(account: &TestAccount) -> OfframpRequest {
+        OfframpRequest::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    #[test]
+    fn test_offramp_status_transitions() {
+        use OfframpStatus::*;
+        assert!(Pending.can_transition_to(Processing));
+        assert!(Pending.can_transition_to(Completed));
+        assert!(Pending.can_transition_to(Cancelled));
+        assert!(Processing.can_transition_to(Failed));
+        assert!(!Processing.can_transition_to(Cancelled));
+        assert!(!Processing.can_transition_to(Pending));
+        for terminal in [Completed, Failed, Cancelled] {
+            assert!(terminal.is_terminal());
+            assert!(!terminal.can_transition_to(Pending));
+        }
+    }
+
+    #[test]
+    fn test_completed_request_can_be_closed() {
+        let owner = Pubkey::new_unique();
+        let treasury_manager = Pubkey::new_unique();
+        let mut accounts = update_accounts(owner, treasury_manager, treasury_manager);
+
+        let result = try_close(offramp_request(owner, OfframpStatus::Pending), owner);
+        assert!(testing::rejected(
+            &result,
+            LegasiError::AccountStillActive,
+            "offramp_request"
+        ));
+
+        testing::set_clock(500);
+        update_status(&mut accounts, OfframpStatus::Processing).unwrap();
+        update_status(&mut accounts, OfframpStatus::Completed).unwrap();
+        let request = stored_request(&accounts[0]);
+        assert_eq!(request.status, OfframpStatus::Completed);
+        assert_eq!(request.completed_at, 500);
+
+        let request = accounts.swap_remove(0);
+        assert!(try_close(request, owner).is_ok());
+    }
+
+    #[test]
+    fn test_cancelled_request_can_be_closed() {
+        let owner = Pubkey::new_unique();
+        let treasury_manager = Pubkey::new_unique();
+        let mut accounts = update_accounts(owner, treasury_manager, treasury_manager);
+
+        testing::set_clock(500);
+        update_status(&mut accounts, OfframpStatus::Cancelled).unwrap();
+        assert!(update_status(&mut accounts, OfframpStatus::Completed).is_err());
+
+        let request = accounts.swap_remove(0);
+        assert!(try_close(request, owner).is_ok());
+    }
+
+    #[test]
+    fn test_update_status_requires_treasury_manager() {
+        let owner = Pubkey::new_unique();
+        let mut accounts = update_accounts(owner, Pubkey::new_unique(), owner);
+
+        testing::set_clock(500);
+        let result = update_status(&mut accounts, OfframpStatus::Cancelled);
+        assert!(testing::rejected(
+            &result,
+            LegasiError::Unauthorized,
+            "protocol"
+        ));
+        assert_eq!(stored_request(&accounts[0]).status, OfframpStatus::Pending);
+    }
+
+    // ========== AGENT POOL BORROWS ==========
+
+    const NOW: i64 = 1_000_000;
+    const SOL_PRICE: u64 = 100_000_000; // $100
+    const POOL_LIQUIDITY: u64 = 1_000_000_000;
+    // AgentBorrow / AgentAutoRepay
+    const POSITION: usize = 0;
+    const LP_POOL: usize = 2;
+    const PROTOCOL: usize = 3;
+    const BORROWABLE: usize = 4;
+    const LP_VAULT: usize = 5;
+    const AGENT_TOKEN: usize = 7;
+
+    fn agent_position(owner: Pubkey, usdc_mint: Pubkey, debt: u64, interest: u64) -> TestAccount {
+        let (key, bump) = Position::address(&owner, 0);
+        let mut position: Position = testing::zeroed_copy();
+        position.owner = owner;
+        position.bump = bump;
+        position.version = Position::VERSION;
+        position
+            .add_collateral(NATIVE_SOL_MINT, 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        if debt > 0 {
+            position.add_debt(usdc_mint, debt).unwrap();
+            position.borrows_mut()[0].accrued_interest = interest;
+        }
+        TestAccount::zero_copy(key, &position)
+    }
+
+    fn agent_config(position: Pubkey) -> TestAccount {
+        let (key, bump) = pda(&[b"agent_config", position.as_ref()], &crate::ID);
+        let mut config: AgentConfig = testing::zeroed();
+        config.position = position;
+        config.daily_borrow_limit = 1_000_000_000;
+        config.auto_repay_enabled = true;
+        config.bump = bump;
+        config.version = AgentConfig::VERSION;
+        TestAccount::anchor(key, &config)
+    }
+
+    fn lp_pool(mint: Pubkey, total_borrowed: u64) -> TestAccount {
+        let (key, bump) = pda(&[b"lp_pool", mint.as_ref()], &lp_program::ID);
+        let pool = LpPool {
+            borrowable_mint: mint,
+            lp_token_mint: Pubkey::new_unique(),
+            total_deposits: POOL_LIQUIDITY,
+            total_shares: POOL_LIQUIDITY,
+            total_borrowed,
+            interest_earned: 0,
+            bump,
+        };
+        TestAccount::anchor(key, &pool)
+    }
+
+    fn lp_vault(mint: Pubkey) -> TestAccount {
+        let (key, _) = pda(&[b"lp_vault", mint.as_ref()], &lp_program::ID);
+        let (pool, _) = pda(&[b"lp_pool", mint.as_ref()], &lp_program::ID);
+        TestAccount::token_account(key, mint, pool, POOL_LIQUIDITY)
+    }
+
+    fn borrowable(mint: Pubkey, total_borrowed: u64) -> TestAccount {
+        let (key, bump) = pda(&[b"borrowable", mint.as_ref()], &legasi_core::ID);
+        let mut borrowable: Borrowable = testing::zeroed();
+        borrowable.mint = mint;
+        borrowable.decimals = 6;
+        borrowable.is_active = true;
+        borrowable.total_borrowed = total_borrowed;
+        borrowable.total_available = POOL_LIQUIDITY;
+        borrowable.bump = bump;
+        TestAccount::anchor(key, &borrowable)
+    }
+
+    fn price_feed(mint: Pubkey, price: u64) -> TestAccount {
+        let (key, bump) = pda(&[b"price", mint.as_ref()], &legasi_core::ID);
+        let mut feed: PriceFeed = testing::zeroed();
+        feed.mint = mint;
+        feed.price_usd_6dec = price;
+        feed.last_update = NOW;
+        feed.bump = bump;
+        TestAccount::anchor(key, &feed)
+    }
+
+    fn sol_collateral() -> TestAccount {
+        let (key, bump) = pda(&[b"collateral", NATIVE_SOL_MINT.as_ref()], &legasi_core::ID);
+        let mut collateral: Collateral = testing::zeroed();
+        collateral.mint = NATIVE_SOL_MINT;
+        collateral.max_ltv_bps = 7_500;
+        collateral.is_active = true;
+        collateral.bump = bump;
+        TestAccount::anchor(key, &collateral)
+    }
+
+    fn pda_account(seeds: &[&[u8]], program_id: &Pubkey) -> TestAccount {
+        TestAccount::new(pda(seeds, program_id).0, Pubkey::default(), Vec::new())
+    }
+
+    /// Position, agent config, pool, protocol, borrowable, vault, mint and agent token account
+    fn agent_pool_accounts(
+        owner: Pubkey,
+        mint: Pubkey,
+        debt: u64,
+        interest: u64,
+    ) -> Vec<TestAccount> {
+        let position = agent_position(owner, mint, debt, interest);
+        let config = agent_config(position.key);
+        vec![
+            position,
+            config,
+            lp_pool(mint, debt),
+            protocol(Pubkey::new_unique()),
+            borrowable(mint, debt),
+            lp_vault(mint),
+            TestAccount::mint(mint, 6),
+            TestAccount::token_account(Pubkey::new_unique(), mint, owner, 200_000_000),
+        ]
+    }
+
+    /// Accounts for `agent_borrow` by the position owner, with the
+    /// pool's `(Borrowable, PriceFeed)` pair as remaining accounts
+    fn agent_borrow_accounts(owner: Pubkey, mint: Pubkey) -> Vec<TestAccount> {
+        let mut accounts = agent_pool_accounts(owner, mint, 0, 0);
+        accounts.extend([
+            price_feed(NATIVE_SOL_MINT, SOL_PRICE),
+            sol_collateral(),
+            TestAccount::signer(owner),
+            TestAccount::program(crate::ID),
+            TestAccount::program(spl_token::ID),
+            pda_account(&[ACCOUNTING_SEED], &crate::ID),
+            TestAccount::program(legasi_core::ID),
+            TestAccount::program(lp_program::ID),
+            pda_account(&[b"__event_authority"], &crate::ID),
+            TestAccount::program(crate::ID),
+            borrowable(mint, 0),
+            price_feed(mint, 1_000_000),
+        ]);
+        accounts
+    }
+
+    /// Accounts for `agent_auto_repay` by the position owner
+    fn auto_repay_accounts(
+        owner: Pubkey,
+        mint: Pubkey,
+        debt: u64,
+        interest: u64,
+    ) -> Vec<TestAccount> {
+        let mut accounts = agent_pool_accounts(owner, mint, debt, interest);
+        accounts.extend([
+            TestAccount::signer(owner),
+            TestAccount::program(crate::ID),
+            TestAccount::program(spl_token::ID),
+            pda_account(&[ACCOUNTING_SEED], &crate::ID),
+            TestAccount::program(legasi_core::ID),
+            pda_account(&[ACCOUNTING_SEED], &lp_program::ID),
+            TestAccount::program(lp_program::ID),
+            pda_account(&[b"__event_authority"], &crate::ID),
+            TestAccount::program(crate::ID),
+        ]);
+        accounts
+    }
+
+    /// Runs an instruction with its CPIs into the LP and core programs
+    fn run(accounts: &mut [TestAccount], ix: impl InstructionData) -> ProgramResult {
+        testing::set_clock(NOW);
+        testing::run(
+            crate::ID,
+            crate::entry,
+            &[
+                (lp_program::ID, legasi_lp::entry),
+                (legasi_core::ID, legasi_core::entry),
+            ],
+            accounts,
+            &ix.data(),
+        )
+    }
+
+    #[test]
+    fn test_agent_borrow_lends_through_lp() {
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = agent_borrow_accounts(owner, mint);
+
+        run(
+            &mut accounts,
+            instruction::AgentBorrow { amount: 50_000_000 },
+        )
+        .unwrap();
+
+        // Signed by the pool through the LP program's CPI
+        assert_eq!(accounts[AGENT_TOKEN].token_amount(), 250_000_000);
+        assert_eq!(
+            accounts[LP_VAULT].token_amount(),
+            POOL_LIQUIDITY - 50_000_000
+        );
+        assert_eq!(
+            accounts[LP_POOL].read::<LpPool>().total_borrowed,
+            50_000_000
+        );
+        assert_eq!(
+            accounts[BORROWABLE].read::<Borrowable>().total_borrowed,
+            50_000_000
+        );
+        let position: Position = accounts[POSITION].read_zero_copy();
+        assert_eq!(position.debt_amount(&mint), 50_000_000);
+    }
+
+    #[test]
+    fn test_agent_borrow_requires_lp_vault() {
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = agent_borrow_accounts(owner, mint);
+        // Same seeds, derived under this program instead of the LP program
+        let (lookalike, _) = pda(&[b"lp_vault", mint.as_ref()], &crate::ID);
+        let pool = accounts[LP_POOL].key;
+        accounts[LP_VAULT] = TestAccount::token_account(lookalike, mint, pool, POOL_LIQUIDITY);
+
+        let infos = testing::infos(&mut accounts);
+        let ix_data = instruction::AgentBorrow { amount: 1 }.data();
+        let result = testing::try_accounts::<AgentBorrow>(&crate::ID, &infos, &ix_data[8..]);
+        assert!(testing::rejected(
+            &result,
+            anchor_lang::error::ErrorCode::ConstraintSeeds,
+            "borrow_vault"
+        ));
+    }
+
+    #[test]
+    fn test_agent_borrow_is_bounded_by_pool_liquidity() {
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = agent_borrow_accounts(owner, mint);
+        let vault = accounts[LP_VAULT].key;
+        let pool = accounts[LP_POOL].key;
+        accounts[LP_VAULT] = TestAccount::token_account(vault, mint, pool, 10_000_000);
+
+        let result = run(
+            &mut accounts,
+            instruction::AgentBorrow { amount: 50_000_000 },
+        );
+        assert!(testing::failed_with(
+            &result,
+            LegasiError::InsufficientLiquidity
+        ));
+        assert_eq!(accounts[AGENT_TOKEN].token_amount(), 200_000_000);
+    }
+
+    #[test]
+    fn test_agent_auto_repay_credits_pool_and_insurance() {
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        // 100 USDC principal + 10 USDC interest
+        let mut accounts = auto_repay_accounts(owner, mint, 100_000_000, 10_000_000);
+
+        run(
+            &mut accounts,
+            instruction::AgentAutoRepay {
+                amount: 150_000_000,
+            },
+        )
+        .unwrap();
+
+        // Capped at what is owed
+        assert_eq!(accounts[AGENT_TOKEN].token_amount(), 90_000_000);
+        assert_eq!(
+            accounts[LP_VAULT].token_amount(),
+            POOL_LIQUIDITY + 110_000_000
+        );
+        let pool: LpPool = accounts[LP_POOL].read();
+        let insurance_fee = 10_000_000 * INSURANCE_FEE_BPS / BPS_DENOMINATOR;
+        assert_eq!(pool.total_borrowed, 0);
+        assert_eq!(pool.interest_earned, 10_000_000 - insurance_fee);
+        assert_eq!(
+            pool.total_deposits,
+            POOL_LIQUIDITY + 10_000_000 - insurance_fee
+        );
+        assert_eq!(
+            accounts[PROTOCOL].read::<Protocol>().insurance_fund,
+            insurance_fee
+        );
+        let position: Position = accounts[POSITION].read_zero_copy();
+        assert_eq!(position.debt_amount(&mint), 0);
+    }
+
+    #[test]
+    fn test_record_position_changes_requires_leverage_authority() {
+        let mint = Pubkey::new_unique();
+        let changes = vec![PositionChange::Borrowed {
+            mint,
+            amount: 100_000_000,
+        }];
+        let record = |authority: Pubkey, source: AccountingSource| {
+            let mut accounts = vec![
+                TestAccount::signer(authority),
+                agent_position(Pubkey::new_unique(), mint, 0, 0),
+            ];
+            let result = run(
+                &mut accounts,
+                instruction::RecordPositionChanges {
+                    source,
+                    changes: changes.clone(),
+                },
+            );
+            (result, accounts[1].read_zero_copy::<Position>())
+        };
+
+        // Any signer other than leverage's accounting PDA
+        let (result, position) = record(Pubkey::new_unique(), AccountingSource::Leverage);
+        assert!(testing::failed_with(&result, LegasiError::Unauthorized));
+        assert_eq!(position.debt_amount(&mint), 0);
+
+        // Another program's accounting PDA, even for its own source
+        let (result, _) = record(AccountingSource::Gad.authority(), AccountingSource::Gad);
+        assert!(testing::failed_with(&result, LegasiError::Unauthorized));
+
+        let (result, position) = record(
+            AccountingSource::Leverage.authority(),
+            AccountingSource::Leverage,
+        );
+        assert!(result.is_ok());
+        assert_eq!(position.debt_amount(&mint), 100_000_000);
+        assert_eq!(position.event_seq, 1);
+    }
 }
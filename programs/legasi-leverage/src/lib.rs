@@ -1,9 +1,19 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::program::invoke;
 use anchor_lang::solana_program::system_instruction;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
-
-use legasi_core::{constants::*, errors::LegasiError, events::*, state::*};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use legasi_core::{
+    accounting::{AccountingCpi, ACCOUNTING_SEED},
+    constants::*,
+    errors::LegasiError,
+    events::*,
+    program::LegasiCore,
+    state::*,
+    tokens::{gross_amount_for, transfer_in, transfer_out},
+};
+use legasi_lending::{program::LegasiLending, PositionChange, PositionCpi};
+use legasi_schema::{lending_program, Position};
 
 declare_id!("AVATHjGrdQ1KqtjHQ4gwRcuAYjwwScwgPsujLDpiA2g3");
 
@@ -13,8 +23,8 @@ declare_id!("AVATHjGrdQ1KqtjHQ4gwRcuAYjwwScwgPsujLDpiA2g3");
 pub struct LeveragePosition {
     pub owner: Pubkey,
     pub position: Pubkey, // Reference to main Position account
-    pub collateral_type: AssetType,
-    pub borrow_type: AssetType,
+    pub collateral_mint: Pubkey,
+    pub borrow_mint: Pubkey,
     pub initial_collateral: u64,
     pub total_collateral: u64,
     pub total_borrowed: u64,
@@ -26,10 +36,44 @@ pub struct LeveragePosition {
     pub bump: u8,
 }
 
+/// Accounting CPIs to core, signed by this program's accounting PDA
+fn accounting<'info>(
+    core_program: &Program<'info, LegasiCore>,
+    authority: &UncheckedAccount<'info>,
+    authority_bump: u8,
+) -> AccountingCpi<'info> {
+    AccountingCpi {
+        core_program: core_program.to_account_info(),
+        authority: authority.to_account_info(),
+        authority_bump,
+        source: AccountingSource::Leverage,
+    }
+}
+
+/// Position CPIs to lending, signed by the same accounting PDA
+fn lending<'info>(
+    lending_program: &Program<'info, LegasiLending>,
+    authority: &UncheckedAccount<'info>,
+    authority_bump: u8,
+) -> PositionCpi<'info> {
+    PositionCpi {
+        lending_program: lending_program.to_account_info(),
+        authority: authority.to_account_info(),
+        authority_bump,
+        source: AccountingSource::Leverage,
+    }
+}
+
 #[program]
 pub mod legasi_leverage {
     use super::*;
 
+    /// Initialize the vault leveraged borrows are paid out of (owned by this program)
+    pub fn initialize_borrow_vault(_ctx: Context<InitializeBorrowVault>) -> Result<()> {
+        msg!("Borrow vault initialized");
+        Ok(())
+    }
+
     /// Open a leveraged long position
     /// Example: 5 SOL at 3x = deposit 5 SOL, borrow USDC, swap to SOL, deposit again (loop)
     /// Result: 15 SOL exposure, 10 SOL worth of USDC debt
@@ -40,12 +84,17 @@ pub mod legasi_leverage {
         min_collateral_received: u64, // Slippage protection
     ) -> Result<()> {
         require!(initial_collateral > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_BORROW)?;
         require!(
             leverage_multiplier >= 2 && leverage_multiplier <= 5,
             LegasiError::InvalidAmount
         );
 
-        let sol_price = ctx.accounts.sol_price_feed.price_usd_6dec;
+        let now = Clock::get()?.unix_timestamp;
+        let sol_price = ctx
+            .accounts
+            .sol_price_feed
+            .fresh_collateral_price_for_borrow(now)?;
 
         // Calculate amounts
         // For 3x leverage: borrow 2x of initial collateral value
@@ -81,19 +130,17 @@ pub mod legasi_leverage {
         )?;
 
         // 2. Borrow USDC (sent to user for swap)
-        let protocol_bump = ctx.accounts.protocol.bump;
-        let seeds: &[&[u8]] = &[b"protocol", &[protocol_bump]];
-
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.usdc_vault.to_account_info(),
-                    to: ctx.accounts.user_usdc_account.to_account_info(),
-                    authority: ctx.accounts.protocol.to_account_info(),
-                },
-                &[seeds],
-            ),
+        let vault_bump = ctx.bumps.usdc_vault;
+        let usdc_mint = ctx.accounts.usdc_mint.key();
+        let seeds: &[&[u8]] = &[b"borrow_vault", usdc_mint.as_ref(), &[vault_bump]];
+
+        transfer_out(
+            &ctx.accounts.token_program,
+            ctx.accounts.usdc_vault.to_account_info(),
+            &ctx.accounts.usdc_mint,
+            ctx.accounts.user_usdc_account.to_account_info(),
+            ctx.accounts.usdc_vault.to_account_info(),
+            &[seeds],
             usdc_to_borrow,
         )?;
 
@@ -114,8 +161,9 @@ pub mod legasi_leverage {
         let leverage_pos = &mut ctx.accounts.leverage_position;
         leverage_pos.owner = ctx.accounts.owner.key();
         leverage_pos.position = ctx.accounts.position.key();
-        leverage_pos.collateral_type = AssetType::SOL;
-        leverage_pos.borrow_type = AssetType::USDC;
+        let borrow_mint = ctx.accounts.usdc_mint.key();
+        leverage_pos.collateral_mint = NATIVE_SOL_MINT;
+        leverage_pos.borrow_mint = borrow_mint;
         leverage_pos.initial_collateral = initial_collateral;
         leverage_pos.total_collateral = initial_collateral; // Will be updated after swap
         leverage_pos.total_borrowed = usdc_to_borrow;
@@ -123,66 +171,66 @@ pub mod legasi_leverage {
         leverage_pos.entry_price_usd = sol_price;
         leverage_pos.is_long = true;
         leverage_pos.is_active = true;
-        leverage_pos.opened_at = Clock::get()?.unix_timestamp;
+        leverage_pos.opened_at = now;
         leverage_pos.bump = ctx.bumps.leverage_position;
 
-        // Update main position
-        let position = &mut ctx.accounts.position;
-
-        // Add collateral
-        let found = position
-            .collaterals
-            .iter_mut()
-            .find(|c| c.asset_type == AssetType::SOL);
-        if let Some(deposit) = found {
-            deposit.amount = deposit
-                .amount
-                .checked_add(initial_collateral)
-                .ok_or(LegasiError::MathOverflow)?;
-        } else {
-            require!(
-                position.collaterals.len() < MAX_COLLATERAL_TYPES,
-                LegasiError::MaxCollateralTypesReached
-            );
-            position.collaterals.push(CollateralDeposit {
-                asset_type: AssetType::SOL,
-                amount: initial_collateral,
-            });
-        }
-
-        // Add borrow
-        let found = position
-            .borrows
-            .iter_mut()
-            .find(|b| b.asset_type == AssetType::USDC);
-        if let Some(borrow) = found {
-            borrow.amount = borrow
-                .amount
-                .checked_add(usdc_to_borrow)
-                .ok_or(LegasiError::MathOverflow)?;
-        } else {
-            require!(
-                position.borrows.len() < MAX_BORROW_TYPES,
-                LegasiError::MaxBorrowTypesReached
-            );
-            position.borrows.push(BorrowedAmount {
-                asset_type: AssetType::USDC,
-                amount: usdc_to_borrow,
-                accrued_interest: 0,
-            });
-        }
-
-        position.last_update = Clock::get()?.unix_timestamp;
-
-        emit!(LeverageOpened {
+        // Update main position (owned by lending program)
+        lending(
+            &ctx.accounts.lending_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_changes(
+            ctx.accounts.position.to_account_info(),
+            vec![
+                PositionChange::Deposited {
+                    mint: NATIVE_SOL_MINT,
+                    amount: initial_collateral,
+                },
+                PositionChange::Borrowed {
+                    mint: borrow_mint,
+                    amount: usdc_to_borrow,
+                },
+            ],
+        )?;
+
+        let position = ctx.accounts.position.load()?;
+        let collateral_amount = position.collateral_amount(&NATIVE_SOL_MINT);
+        let debt_amount = position.debt_amount(&borrow_mint);
+        let seq = position.event_seq;
+        drop(position);
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_collateral(
+            ctx.accounts.sol_collateral.to_account_info(),
+            CollateralChange::Deposited(initial_collateral),
+        )?;
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Borrowed(usdc_to_borrow),
+        )?;
+
+        emit_cpi!(LeverageOpened {
             position: ctx.accounts.position.key(),
             owner: ctx.accounts.owner.key(),
-            collateral_type: AssetType::SOL,
-            borrow_type: AssetType::USDC,
+            collateral_mint: NATIVE_SOL_MINT,
+            borrow_mint,
             initial_collateral,
             total_collateral: initial_collateral,
             total_borrowed: usdc_to_borrow,
             leverage_multiplier,
+            collateral_amount,
+            debt_amount,
+            seq,
         });
 
         msg!(
@@ -220,53 +268,73 @@ pub mod legasi_leverage {
             .saturating_sub(leverage_pos.total_borrowed as i64);
 
         // User needs to have USDC to repay
-        let position = &ctx.accounts.position;
+        let position = ctx.accounts.position.load()?;
         let usdc_borrow = position
-            .borrows
+            .borrows()
             .iter()
-            .find(|b| b.asset_type == AssetType::USDC)
+            .find(|b| b.mint == leverage_pos.borrow_mint)
             .ok_or(LegasiError::PositionNotFound)?;
 
+        let principal = usdc_borrow.amount;
         let total_owed = usdc_borrow
             .amount
             .checked_add(usdc_borrow.accrued_interest)
             .ok_or(LegasiError::MathOverflow)?;
-
-        // Transfer USDC from user to repay
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_usdc_account.to_account_info(),
-                    to: ctx.accounts.usdc_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(),
-                },
-            ),
-            total_owed,
+        drop(position);
+
+        // Transfer USDC from user to repay, grossed up for any transfer fee
+        let gross_owed = gross_amount_for(&ctx.accounts.usdc_mint, total_owed)?;
+        let received = transfer_in(
+            &ctx.accounts.token_program,
+            ctx.accounts.user_usdc_account.to_account_info(),
+            &ctx.accounts.usdc_mint,
+            &mut ctx.accounts.usdc_vault,
+            ctx.accounts.owner.to_account_info(),
+            gross_owed,
+        )?;
+        require!(received >= total_owed, LegasiError::InvalidAmount);
+
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Repaid {
+                principal,
+                received,
+            },
         )?;
 
-        // Update position - remove debt
-        let position = &mut ctx.accounts.position;
-        position.borrows.retain(|b| b.asset_type != AssetType::USDC);
-        position.last_update = Clock::get()?.unix_timestamp;
-
-        // Update reputation
-        position.reputation.successful_repayments =
-            position.reputation.successful_repayments.saturating_add(1);
-        position.reputation.total_repaid_usd = position
-            .reputation
-            .total_repaid_usd
-            .saturating_add(total_owed);
+        // Update position (owned by lending program) - remove debt
+        let borrow_mint = ctx.accounts.leverage_position.borrow_mint;
+        lending(
+            &ctx.accounts.lending_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_changes(
+            ctx.accounts.position.to_account_info(),
+            vec![PositionChange::Repaid {
+                mint: borrow_mint,
+                amount: total_owed,
+            }],
+        )?;
+        let seq = ctx.accounts.position.load()?.event_seq;
 
         // Mark leverage position as closed
         let leverage_pos = &mut ctx.accounts.leverage_position;
         leverage_pos.is_active = false;
 
-        emit!(LeverageClosed {
+        emit_cpi!(LeverageClosed {
             position: ctx.accounts.position.key(),
             owner: ctx.accounts.owner.key(),
+            borrow_mint,
+            debt_repaid: total_owed,
             collateral_returned: leverage_pos.total_collateral,
             pnl_usd,
+            seq,
         });
 
         msg!(
@@ -276,6 +344,13 @@ pub mod legasi_leverage {
         Ok(())
     }
 
+    /// Close a leverage position account once the loop is closed, returning
+    /// its rent to the owner. Works after the lending position is closed too
+    pub fn close_leverage_position(_ctx: Context<CloseLeveragePosition>) -> Result<()> {
+        msg!("Leverage position account closed");
+        Ok(())
+    }
+
     /// Update collateral amount after swap (called after user swaps and deposits)
     pub fn update_leverage_collateral(
         ctx: Context<UpdateLeverageCollateral>,
@@ -300,6 +375,34 @@ pub mod legasi_leverage {
 
 // ========== ACCOUNTS ==========
 
+#[derive(Accounts)]
+pub struct InitializeBorrowVault<'info> {
+    #[account(
+        init,
+        payer = admin,
+        token::mint = mint,
+        token::authority = borrow_vault,
+        token::token_program = token_program,
+        seeds = [b"borrow_vault", mint.key().as_ref()],
+        bump
+    )]
+    pub borrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// Protocol state (owned by core program) - signer must be the risk admin
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID,
+        constraint = protocol.has_role(ProtocolRole::Risk, &admin.key()) @ LegasiError::Unauthorized
+    )]
+    pub protocol: Account<'info, Protocol>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct OpenLong<'info> {
     #[account(
@@ -312,37 +415,73 @@ pub struct OpenLong<'info> {
     pub leverage_position: Account<'info, LeveragePosition>,
     #[account(
         mut,
-        seeds = [b"position", owner.key().as_ref()],
-        bump = position.bump,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
+        seeds::program = lending_program::ID,
         has_one = owner
     )]
-    pub position: Account<'info, Position>,
-    #[account(seeds = [b"protocol"], bump = protocol.bump)]
-    pub protocol: Account<'info, Protocol>,
-    /// CHECK: SOL vault PDA
+    pub position: AccountLoader<'info, Position>,
+    /// Protocol state (owned by core program)
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// CHECK: SOL vault of the position (owned by lending program)
     #[account(
         mut,
         seeds = [b"sol_vault", position.key().as_ref()],
-        bump
+        bump,
+        seeds::program = lending_program::ID
     )]
     pub sol_vault: UncheckedAccount<'info>,
+    /// Borrow vault (owned by this program)
     #[account(
         mut,
         seeds = [b"borrow_vault", usdc_mint.key().as_ref()],
-        bump
+        bump,
+        token::mint = usdc_mint,
+        token::authority = usdc_vault
+    )]
+    pub usdc_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub usdc_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// SOL collateral config (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"collateral", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_collateral.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub sol_collateral: Box<Account<'info, Collateral>>,
+    /// Borrowable config for the borrowed mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", usdc_mint.key().as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
+    #[account(mut, token::mint = usdc_mint)]
+    pub user_usdc_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
     )]
-    pub usdc_vault: Account<'info, TokenAccount>,
-    pub usdc_mint: Account<'info, anchor_spl::token::Mint>,
-    #[account(mut)]
-    pub user_usdc_account: Account<'info, TokenAccount>,
-    #[account(seeds = [b"price", &[AssetType::SOL as u8]], bump)]
     pub sol_price_feed: Account<'info, PriceFeed>,
     #[account(mut)]
     pub owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+    pub lending_program: Program<'info, LegasiLending>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct ClosePosition<'info> {
     #[account(
@@ -354,25 +493,66 @@ pub struct ClosePosition<'info> {
     pub leverage_position: Account<'info, LeveragePosition>,
     #[account(
         mut,
-        seeds = [b"position", owner.key().as_ref()],
-        bump = position.bump,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
+        seeds::program = lending_program::ID,
         has_one = owner
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
+    /// Borrow vault (owned by this program)
     #[account(
         mut,
         seeds = [b"borrow_vault", usdc_mint.key().as_ref()],
-        bump
+        bump,
+        token::mint = usdc_mint,
+        token::authority = usdc_vault
+    )]
+    pub usdc_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = leverage_position.borrow_mint)]
+    pub usdc_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Borrowable config for the borrowed mint (owned by core program)
+    #[account(
+        mut,
+        seeds = [b"borrowable", usdc_mint.key().as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
+    #[account(mut, token::mint = usdc_mint)]
+    pub user_usdc_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [b"price", NATIVE_SOL_MINT.as_ref()],
+        bump = sol_price_feed.bump,
+        seeds::program = legasi_core::ID
     )]
-    pub usdc_vault: Account<'info, TokenAccount>,
-    pub usdc_mint: Account<'info, anchor_spl::token::Mint>,
-    #[account(mut)]
-    pub user_usdc_account: Account<'info, TokenAccount>,
-    #[account(seeds = [b"price", &[AssetType::SOL as u8]], bump)]
     pub sol_price_feed: Account<'info, PriceFeed>,
     #[account(mut)]
     pub owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+    pub lending_program: Program<'info, LegasiLending>,
+}
+
+#[derive(Accounts)]
+pub struct CloseLeveragePosition<'info> {
+    #[account(
+        mut,
+        seeds = [b"leverage", leverage_position.position.as_ref()],
+        bump = leverage_position.bump,
+        has_one = owner,
+        constraint = !leverage_position.is_active @ LegasiError::AccountStillActive,
+        close = owner
+    )]
+    pub leverage_position: Account<'info, LeveragePosition>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
 }
 
 #[derive(Accounts)]
@@ -385,10 +565,447 @@ pub struct UpdateLeverageCollateral<'info> {
     )]
     pub leverage_position: Account<'info, LeveragePosition>,
     #[account(
-        seeds = [b"position", owner.key().as_ref()],
-        bump = position.bump,
+        seeds = [
+            b"position",
+            owner.key().as_ref(),
+            Position::id_seed(&position.load()?.position_id)
+        ],
+        bump = position.load()?.bump,
+        seeds::program = lending_program::ID,
         has_one = owner
     )]
-    pub position: Account<'info, Position>,
+    pub position: AccountLoader<'info, Position>,
     pub owner: Signer<'info>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::error::ErrorCode;
+    use anchor_lang::solana_program::entrypoint::ProgramResult;
+    use anchor_lang::InstructionData;
+    use anchor_spl::token::spl_token;
+    use legasi_schema::testing::{self, TestAccount};
+
+    const NOW: i64 = 1_000_000;
+    const SOL_PRICE: u64 = 100_000_000; // $100
+    const VAULT_LIQUIDITY: u64 = 1_000_000_000;
+    const POSITION: usize = 1;
+    // OpenLong
+    const PROTOCOL: usize = 2;
+    const SOL_VAULT_OPEN: usize = 3;
+    const USDC_VAULT_OPEN: usize = 4;
+    const USER_USDC_OPEN: usize = 8;
+    // ClosePosition
+    const USDC_VAULT_CLOSE: usize = 2;
+    const USDC_MINT_CLOSE: usize = 3;
+    const BORROWABLE_CLOSE: usize = 4;
+    const USER_USDC_CLOSE: usize = 5;
+    const OWNER_CLOSE: usize = 7;
+
+    fn pda(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, program_id)
+    }
+
+    fn position(owner: Pubkey, usdc_mint: Pubkey, position_id: u8) -> TestAccount {
+        let (key, bump) = Position::address(&owner, position_id);
+        let mut position: Position = testing::zeroed_copy();
+        position.owner = owner;
+        position.position_id = position_id;
+        position
+            .add_collateral(NATIVE_SOL_MINT, LAMPORTS_PER_SOL)
+            .unwrap();
+        position.add_debt(usdc_mint, 100_000_000).unwrap();
+        position.bump = bump;
+        position.version = Position::VERSION;
+        TestAccount::zero_copy(key, &position)
+    }
+
+    fn borrow_vault(mint: Pubkey) -> TestAccount {
+        let (key, _) = pda(&[b"borrow_vault", mint.as_ref()], &crate::ID);
+        TestAccount::token_account(key, mint, key, VAULT_LIQUIDITY)
+    }
+
+    fn borrowable(mint: Pubkey) -> TestAccount {
+        let (key, bump) = pda(&[b"borrowable", mint.as_ref()], &legasi_core::ID);
+        let mut borrowable: Borrowable = testing::zeroed();
+        borrowable.mint = mint;
+        borrowable.bump = bump;
+        TestAccount::anchor(key, &borrowable)
+    }
+
+    fn protocol() -> TestAccount {
+        let (key, bump) = pda(&[b"protocol"], &legasi_core::ID);
+        let mut protocol: Protocol = testing::zeroed();
+        protocol.bump = bump;
+        TestAccount::anchor(key, &protocol)
+    }
+
+    fn sol_collateral() -> TestAccount {
+        let (key, bump) = pda(&[b"collateral", NATIVE_SOL_MINT.as_ref()], &legasi_core::ID);
+        let mut collateral: Collateral = testing::zeroed();
+        collateral.mint = NATIVE_SOL_MINT;
+        collateral.bump = bump;
+        TestAccount::anchor(key, &collateral)
+    }
+
+    fn sol_price_feed() -> TestAccount {
+        let (key, bump) = pda(&[b"price", NATIVE_SOL_MINT.as_ref()], &legasi_core::ID);
+        let mut feed: PriceFeed = testing::zeroed();
+        feed.mint = NATIVE_SOL_MINT;
+        feed.price_usd_6dec = SOL_PRICE;
+        feed.last_update = NOW;
+        feed.bump = bump;
+        TestAccount::anchor(key, &feed)
+    }
+
+    /// Accounting authority, core and lending programs and the event CPI accounts
+    fn trailing_accounts() -> Vec<TestAccount> {
+        let (accounting_authority, _) = pda(&[ACCOUNTING_SEED], &crate::ID);
+        let (event_authority, _) = pda(&[b"__event_authority"], &crate::ID);
+        vec![
+            TestAccount::new(accounting_authority, Pubkey::default(), Vec::new()),
+            TestAccount::program(legasi_core::ID),
+            TestAccount::program(lending_program::ID),
+            TestAccount::new(event_authority, Pubkey::default(), Vec::new()),
+            TestAccount::program(crate::ID),
+        ]
+    }
+
+    /// Accounts for a valid `open_long` borrowing `usdc_mint`, in instruction order
+    fn open_accounts(usdc_mint: Pubkey, position_id: u8) -> Vec<TestAccount> {
+        let owner = Pubkey::new_unique();
+        let position = position(owner, usdc_mint, position_id);
+        let (leverage_position, _) = pda(&[b"leverage", position.key.as_ref()], &crate::ID);
+        let (sol_vault, _) = pda(&[b"sol_vault", position.key.as_ref()], &lending_program::ID);
+        let mut accounts = vec![
+            // Created by `init`: CPIs are no-ops in tests
+            TestAccount::new(
+                leverage_position,
+                crate::ID,
+                vec![0; 8 + LeveragePosition::INIT_SPACE],
+            ),
+            position,
+            protocol(),
+            TestAccount::new(sol_vault, Pubkey::default(), Vec::new()),
+            borrow_vault(usdc_mint),
+            TestAccount::mint(usdc_mint, 6),
+            sol_collateral(),
+            borrowable(usdc_mint),
+            TestAccount::token_account(Pubkey::new_unique(), usdc_mint, owner, 0),
+            sol_price_feed(),
+            TestAccount::signer(owner),
+            TestAccount::program(spl_token::ID),
+            TestAccount::program(System::id()),
+        ];
+        accounts.extend(trailing_accounts());
+        accounts
+    }
+
+    /// Accounts for a valid `close_position` of a `usdc_mint` loop
+    fn close_accounts(usdc_mint: Pubkey) -> Vec<TestAccount> {
+        let owner = Pubkey::new_unique();
+        let position = position(owner, usdc_mint, 0);
+        let (key, bump) = pda(&[b"leverage", position.key.as_ref()], &crate::ID);
+        let leverage_position = LeveragePosition {
+            owner,
+            position: position.key,
+            collateral_mint: NATIVE_SOL_MINT,
+            borrow_mint: usdc_mint,
+            initial_collateral: LAMPORTS_PER_SOL,
+            total_collateral: LAMPORTS_PER_SOL,
+            total_borrowed: 100_000_000,
+            leverage_multiplier: 2,
+            entry_price_usd: 100_000_000,
+            is_long: true,
+            is_active: true,
+            opened_at: 0,
+            bump,
+        };
+        let mut accounts = vec![
+            TestAccount::anchor(key, &leverage_position),
+            position,
+            borrow_vault(usdc_mint),
+            TestAccount::mint(usdc_mint, 6),
+            borrowable(usdc_mint),
+            TestAccount::token_account(Pubkey::new_unique(), usdc_mint, owner, 100_000_000),
+            sol_price_feed(),
+            TestAccount::signer(owner),
+            TestAccount::program(spl_token::ID),
+        ];
+        accounts.extend(trailing_accounts());
+        accounts
+    }
+
+    /// Runs an instruction with lending and core executing their CPIs
+    fn run(accounts: &mut [TestAccount], ix: impl InstructionData) -> ProgramResult {
+        testing::set_clock(NOW);
+        testing::run(
+            crate::ID,
+            crate::entry,
+            &[
+                (lending_program::ID, legasi_lending::entry),
+                (legasi_core::ID, legasi_core::entry),
+            ],
+            accounts,
+            &ix.data(),
+        )
+    }
+
+    fn try_close_account(accounts: &mut [TestAccount]) -> Result<()> {
+        let infos = testing::infos(accounts);
+        testing::try_accounts::<CloseLeveragePosition>(&crate::ID, &infos, &[])
+    }
+
+    fn try_open(accounts: &mut [TestAccount]) -> Result<()> {
+        testing::install_rent_stub();
+        let infos = testing::infos(accounts);
+        testing::try_accounts::<OpenLong>(&crate::ID, &infos, &[])
+    }
+
+    fn try_close(accounts: &mut [TestAccount]) -> Result<()> {
+        let infos = testing::infos(accounts);
+        testing::try_accounts::<ClosePosition>(&crate::ID, &infos, &[])
+    }
+
+    #[test]
+    fn test_open_long_accepts_protocol_accounts() {
+        let mut accounts = open_accounts(Pubkey::new_unique(), 0);
+        assert!(try_open(&mut accounts).is_ok());
+    }
+
+    #[test]
+    fn test_close_accepts_protocol_accounts() {
+        let mut accounts = close_accounts(Pubkey::new_unique());
+        assert!(try_close(&mut accounts).is_ok());
+    }
+
+    #[test]
+    fn test_open_long_accepts_sub_account() {
+        let mut accounts = open_accounts(Pubkey::new_unique(), 3);
+        assert!(try_open(&mut accounts).is_ok());
+    }
+
+    #[test]
+    fn test_open_long_rejects_sub_account_at_other_address() {
+        // Sub-account 1's data at the primary position's address
+        let mut accounts = open_accounts(Pubkey::new_unique(), 0);
+        let mut position = Position::try_deserialize(&mut &accounts[POSITION].data[..]).unwrap();
+        position.position_id = 1;
+        accounts[POSITION] = TestAccount::zero_copy(accounts[POSITION].key, &position);
+
+        let result = try_open(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintSeeds,
+            "position"
+        ));
+    }
+
+    #[test]
+    fn test_open_long_rejects_position_not_owned_by_lending() {
+        let mut accounts = open_accounts(Pubkey::new_unique(), 0);
+        accounts[POSITION].owner = crate::ID;
+
+        let result = try_open(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::AccountOwnedByWrongProgram,
+            "position"
+        ));
+    }
+
+    #[test]
+    fn test_open_long_rejects_protocol_lookalike() {
+        let mut accounts = open_accounts(Pubkey::new_unique(), 0);
+        accounts[PROTOCOL].owner = crate::ID;
+
+        let result = try_open(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::AccountOwnedByWrongProgram,
+            "protocol"
+        ));
+    }
+
+    #[test]
+    fn test_open_long_rejects_foreign_vault() {
+        let usdc_mint = Pubkey::new_unique();
+
+        // Right mint, but not this program's vault PDA
+        let mut accounts = open_accounts(usdc_mint, 0);
+        let attacker = Pubkey::new_unique();
+        accounts[USDC_VAULT_OPEN] =
+            TestAccount::token_account(attacker, usdc_mint, attacker, 1_000_000_000);
+        let result = try_open(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintSeeds,
+            "usdc_vault"
+        ));
+
+        // Vault PDA under another authority (e.g. core's protocol PDA, which
+        // this program can't sign for)
+        let mut accounts = open_accounts(usdc_mint, 0);
+        let vault = accounts[USDC_VAULT_OPEN].key;
+        let protocol = accounts[PROTOCOL].key;
+        accounts[USDC_VAULT_OPEN] =
+            TestAccount::token_account(vault, usdc_mint, protocol, 1_000_000_000);
+        let result = try_open(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintTokenOwner,
+            "usdc_vault"
+        ));
+    }
+
+    #[test]
+    fn test_open_long_rejects_user_account_of_other_mint() {
+        let mut accounts = open_accounts(Pubkey::new_unique(), 0);
+        accounts[USER_USDC_OPEN] = TestAccount::token_account(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            0,
+        );
+
+        let result = try_open(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintTokenMint,
+            "user_usdc_account"
+        ));
+    }
+
+    #[test]
+    fn test_close_rejects_other_borrow_mint() {
+        // A consistent vault/config set for a mint the loop didn't borrow
+        let other_mint = Pubkey::new_unique();
+        let mut accounts = close_accounts(Pubkey::new_unique());
+        let owner = accounts[OWNER_CLOSE].key;
+        accounts[USDC_VAULT_CLOSE] = borrow_vault(other_mint);
+        accounts[USDC_MINT_CLOSE] = TestAccount::mint(other_mint, 6);
+        accounts[BORROWABLE_CLOSE] = borrowable(other_mint);
+        accounts[USER_USDC_CLOSE] =
+            TestAccount::token_account(Pubkey::new_unique(), other_mint, owner, 100_000_000);
+
+        let result = try_close(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintAddress,
+            "usdc_mint"
+        ));
+    }
+
+    #[test]
+    fn test_close_rejects_position_not_owned_by_lending() {
+        let mut accounts = close_accounts(Pubkey::new_unique());
+        accounts[POSITION].owner = crate::ID;
+
+        let result = try_close(&mut accounts);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::AccountOwnedByWrongProgram,
+            "position"
+        ));
+    }
+
+    #[test]
+    fn test_close_leverage_position_requires_closed_loop() {
+        let accounts = close_accounts(Pubkey::new_unique());
+        let owner = accounts[OWNER_CLOSE].key;
+        let mut leverage_position =
+            LeveragePosition::try_deserialize(&mut &accounts[0].data[..]).unwrap();
+        let key = accounts[0].key;
+
+        let mut open = vec![
+            TestAccount::anchor(key, &leverage_position),
+            TestAccount::signer(owner),
+        ];
+        let result = try_close_account(&mut open);
+        assert!(matches!(
+            result,
+            Err(Error::AnchorError(err))
+                if err.error_code_number == u32::from(LegasiError::AccountStillActive)
+        ));
+
+        leverage_position.is_active = false;
+        let mut closed = vec![
+            TestAccount::anchor(key, &leverage_position),
+            TestAccount::signer(owner),
+        ];
+        assert!(try_close_account(&mut closed).is_ok());
+
+        // Only the owner gets the rent
+        let mut other = vec![
+            TestAccount::anchor(key, &leverage_position),
+            TestAccount::signer(Pubkey::new_unique()),
+        ];
+        let result = try_close_account(&mut other);
+        assert!(testing::rejected(
+            &result,
+            ErrorCode::ConstraintHasOne,
+            "leverage_position"
+        ));
+    }
+
+    #[test]
+    fn test_open_long_records_position_through_lending() {
+        let usdc_mint = Pubkey::new_unique();
+        let mut accounts = open_accounts(usdc_mint, 0);
+        let sol_vault_lamports = accounts[SOL_VAULT_OPEN].lamports;
+
+        run(
+            &mut accounts,
+            instruction::OpenLong {
+                initial_collateral: LAMPORTS_PER_SOL / 2,
+                leverage_multiplier: 2,
+                min_collateral_received: 0,
+            },
+        )
+        .unwrap();
+
+        // $50 of SOL at 2x borrows $50
+        assert_eq!(
+            accounts[SOL_VAULT_OPEN].lamports,
+            sol_vault_lamports + LAMPORTS_PER_SOL / 2
+        );
+        assert_eq!(accounts[USER_USDC_OPEN].token_amount(), 50_000_000);
+        assert_eq!(
+            accounts[USDC_VAULT_OPEN].token_amount(),
+            VAULT_LIQUIDITY - 50_000_000
+        );
+        let position: Position = accounts[POSITION].read_zero_copy();
+        assert_eq!(
+            position.collateral_amount(&NATIVE_SOL_MINT),
+            LAMPORTS_PER_SOL * 3 / 2
+        );
+        assert_eq!(position.debt_amount(&usdc_mint), 150_000_000);
+        assert_eq!(position.last_update, NOW);
+        assert_eq!(position.event_seq, 1);
+        let leverage_position: LeveragePosition = accounts[0].read();
+        assert_eq!(leverage_position.total_borrowed, 50_000_000);
+        assert!(leverage_position.is_active);
+    }
+
+    #[test]
+    fn test_close_position_repays_through_lending() {
+        let usdc_mint = Pubkey::new_unique();
+        let mut accounts = close_accounts(usdc_mint);
+
+        run(&mut accounts, instruction::ClosePosition {}).unwrap();
+
+        assert_eq!(accounts[USER_USDC_CLOSE].token_amount(), 0);
+        assert_eq!(
+            accounts[USDC_VAULT_CLOSE].token_amount(),
+            VAULT_LIQUIDITY + 100_000_000
+        );
+        let position: Position = accounts[POSITION].read_zero_copy();
+        assert_eq!(position.debt_amount(&usdc_mint), 0);
+        assert!(position.borrows().is_empty());
+        assert_eq!(position.reputation.successful_repayments, 1);
+        assert_eq!(position.reputation.total_repaid_usd, 100_000_000);
+        assert_eq!(position.event_seq, 1);
+        assert!(!accounts[0].read::<LeveragePosition>().is_active);
+    }
+}
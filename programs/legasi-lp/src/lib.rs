@@ -1,22 +1,64 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
-
-use legasi_core::{constants::*, errors::LegasiError, events::*, state::Protocol};
-// Note: LpPool defined locally to avoid cross-program ownership issues
+use anchor_spl::token_interface::{self, Burn, Mint, MintTo, TokenAccount, TokenInterface};
+
+use legasi_core::{
+    accounting::{AccountingCpi, ACCOUNTING_SEED},
+    constants::*,
+    errors::LegasiError,
+    events::*,
+    program::LegasiCore,
+    state::{AccountingSource, BorrowableChange, ProtocolRole},
+    tokens::{transfer_in, transfer_out},
+};
+use legasi_schema::{Borrowable, LpPool, Protocol};
 
 declare_id!("CTwY4VSeueesSBc95G38X3WJYPriJEzyxjcCaZAc5LbY");
 
-/// LP Pool state - defined locally for proper program ownership
-#[account]
-#[derive(InitSpace)]
-pub struct LpPool {
-    pub borrowable_mint: Pubkey,
-    pub lp_token_mint: Pubkey,
-    pub total_deposits: u64,
-    pub total_shares: u64,
-    pub total_borrowed: u64,
-    pub interest_earned: u64,
-    pub bump: u8,
+/// Accounting CPIs to core, signed by this program's accounting PDA
+fn accounting<'info>(
+    core_program: &Program<'info, LegasiCore>,
+    authority: &UncheckedAccount<'info>,
+    authority_bump: u8,
+) -> AccountingCpi<'info> {
+    AccountingCpi {
+        core_program: core_program.to_account_info(),
+        authority: authority.to_account_info(),
+        authority_bump,
+        source: AccountingSource::Lp,
+    }
+}
+
+/// Only the lending and flash programs draw on pool liquidity, through CPIs
+/// signed by their accounting PDA
+fn require_lender(source: AccountingSource, authority: &Pubkey) -> Result<()> {
+    require!(
+        matches!(source, AccountingSource::Lending | AccountingSource::Flash),
+        LegasiError::Unauthorized
+    );
+    source.require_authority(authority)?;
+    Ok(())
+}
+
+/// Credit `interest` to the pool's LPs, returning the insurance fund's share
+fn credit_interest(pool: &mut LpPool, interest: u64) -> Result<u64> {
+    let insurance_fee = interest
+        .checked_mul(INSURANCE_FEE_BPS)
+        .ok_or(LegasiError::MathOverflow)?
+        .checked_div(BPS_DENOMINATOR)
+        .ok_or(LegasiError::MathOverflow)?;
+    let lp_interest = interest.saturating_sub(insurance_fee);
+
+    // Interest increases total_deposits without changing shares, which
+    // increases the value of each LP token
+    pool.total_deposits = pool
+        .total_deposits
+        .checked_add(lp_interest)
+        .ok_or(LegasiError::MathOverflow)?;
+    pool.interest_earned = pool
+        .interest_earned
+        .checked_add(lp_interest)
+        .ok_or(LegasiError::MathOverflow)?;
+    Ok(insurance_fee)
 }
 
 #[program]
@@ -52,6 +94,20 @@ pub mod legasi_lp {
     /// Deposit stablecoins, receive LP tokens (e.g., deposit USDC, get bUSDC)
     pub fn deposit(ctx: Context<LpDeposit>, amount: u64) -> Result<()> {
         require!(amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts.protocol.require_not_paused(PAUSE_LP_DEPOSIT)?;
+        ctx.accounts
+            .borrowable_config
+            .require_not_paused(PAUSE_LP_DEPOSIT)?;
+
+        // Transfer tokens from user to vault, crediting what the vault received
+        let amount = transfer_in(
+            &ctx.accounts.token_program,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.borrowable_mint,
+            &mut ctx.accounts.vault,
+            ctx.accounts.depositor.to_account_info(),
+            amount,
+        )?;
 
         let pool = &ctx.accounts.lp_pool;
 
@@ -70,25 +126,12 @@ pub mod legasi_lp {
 
         require!(shares_to_mint > 0, LegasiError::InvalidAmount);
 
-        // Transfer tokens from user to vault
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    to: ctx.accounts.vault.to_account_info(),
-                    authority: ctx.accounts.depositor.to_account_info(),
-                },
-            ),
-            amount,
-        )?;
-
         // Mint LP tokens to user
         let pool_bump = ctx.accounts.lp_pool.bump;
         let borrowable_mint = ctx.accounts.lp_pool.borrowable_mint;
         let seeds: &[&[u8]] = &[b"lp_pool", borrowable_mint.as_ref(), &[pool_bump]];
 
-        token::mint_to(
+        token_interface::mint_to(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 MintTo {
@@ -112,7 +155,17 @@ pub mod legasi_lp {
             .checked_add(shares_to_mint)
             .ok_or(LegasiError::MathOverflow)?;
 
-        emit!(LpDeposited {
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Supplied(amount),
+        )?;
+
+        emit_cpi!(LpDeposited {
             depositor: ctx.accounts.depositor.key(),
             pool: ctx.accounts.lp_pool.key(),
             amount,
@@ -130,6 +183,12 @@ pub mod legasi_lp {
     /// Withdraw by burning LP tokens (e.g., burn bUSDC, get USDC + yield)
     pub fn withdraw(ctx: Context<LpWithdraw>, shares_amount: u64) -> Result<()> {
         require!(shares_amount > 0, LegasiError::InvalidAmount);
+        ctx.accounts
+            .protocol
+            .require_not_paused(PAUSE_LP_WITHDRAW)?;
+        ctx.accounts
+            .borrowable_config
+            .require_not_paused(PAUSE_LP_WITHDRAW)?;
 
         let pool = &ctx.accounts.lp_pool;
         require!(pool.total_shares > 0, LegasiError::NoLpShares);
@@ -149,7 +208,7 @@ pub mod legasi_lp {
         );
 
         // Burn LP tokens from user
-        token::burn(
+        token_interface::burn(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 Burn {
@@ -166,16 +225,13 @@ pub mod legasi_lp {
         let borrowable_mint = ctx.accounts.lp_pool.borrowable_mint;
         let seeds: &[&[u8]] = &[b"lp_pool", borrowable_mint.as_ref(), &[pool_bump]];
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.user_token_account.to_account_info(),
-                    authority: ctx.accounts.lp_pool.to_account_info(),
-                },
-                &[seeds],
-            ),
+        transfer_out(
+            &ctx.accounts.token_program,
+            ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.borrowable_mint,
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.lp_pool.to_account_info(),
+            &[seeds],
             tokens_to_return,
         )?;
 
@@ -184,7 +240,17 @@ pub mod legasi_lp {
         pool.total_deposits = pool.total_deposits.saturating_sub(tokens_to_return);
         pool.total_shares = pool.total_shares.saturating_sub(shares_amount);
 
-        emit!(LpWithdrawn {
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_borrowable(
+            ctx.accounts.borrowable_config.to_account_info(),
+            BorrowableChange::Withdrawn(tokens_to_return),
+        )?;
+
+        emit_cpi!(LpWithdrawn {
             withdrawer: ctx.accounts.withdrawer.key(),
             pool: ctx.accounts.lp_pool.key(),
             shares_burned: shares_amount,
@@ -199,44 +265,86 @@ pub mod legasi_lp {
         Ok(())
     }
 
-    /// Accrue interest to the pool (called by lending program)
-    pub fn accrue_interest(ctx: Context<AccrueInterest>, interest_amount: u64) -> Result<()> {
+    /// Accrue interest to the pool
+    /// CPI only, signed by the lending or flash program's accounting PDA
+    pub fn accrue_interest(
+        ctx: Context<AccrueInterest>,
+        source: AccountingSource,
+        interest_amount: u64,
+    ) -> Result<()> {
+        require_lender(source, &ctx.accounts.authority.key())?;
         require!(interest_amount > 0, LegasiError::InvalidAmount);
 
-        // Calculate insurance fee (5%)
-        let insurance_fee = interest_amount
-            .checked_mul(INSURANCE_FEE_BPS)
-            .ok_or(LegasiError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(LegasiError::MathOverflow)?;
+        let insurance_fee = credit_interest(&mut ctx.accounts.lp_pool, interest_amount)?;
+        accounting(
+            &ctx.accounts.core_program,
+            &ctx.accounts.accounting_authority,
+            ctx.bumps.accounting_authority,
+        )
+        .record_insurance_fee(ctx.accounts.protocol.to_account_info(), insurance_fee)?;
+
+        msg!(
+            "Accrued {} interest ({} to insurance)",
+            interest_amount,
+            insurance_fee
+        );
+        Ok(())
+    }
 
-        let lp_interest = interest_amount.saturating_sub(insurance_fee);
+    /// Lend pool liquidity to `destination`
+    /// CPI only, signed by the lending or flash program's accounting PDA
+    pub fn lend(ctx: Context<Lend>, source: AccountingSource, amount: u64) -> Result<()> {
+        require_lender(source, &ctx.accounts.authority.key())?;
+        require!(amount > 0, LegasiError::InvalidAmount);
+        require!(
+            ctx.accounts.vault.amount >= amount,
+            LegasiError::InsufficientLiquidity
+        );
+
+        let pool_bump = ctx.accounts.lp_pool.bump;
+        let borrowable_mint = ctx.accounts.lp_pool.borrowable_mint;
+        let seeds: &[&[u8]] = &[b"lp_pool", borrowable_mint.as_ref(), &[pool_bump]];
+
+        transfer_out(
+            &ctx.accounts.token_program,
+            ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.borrowable_mint,
+            ctx.accounts.destination.to_account_info(),
+            ctx.accounts.lp_pool.to_account_info(),
+            &[seeds],
+            amount,
+        )?;
 
-        // Update pool - interest increases total_deposits without changing shares
-        // This automatically increases the value of each LP token
         let pool = &mut ctx.accounts.lp_pool;
-        pool.total_deposits = pool
-            .total_deposits
-            .checked_add(lp_interest)
-            .ok_or(LegasiError::MathOverflow)?;
-        pool.interest_earned = pool
-            .interest_earned
-            .checked_add(lp_interest)
+        pool.total_borrowed = pool
+            .total_borrowed
+            .checked_add(amount)
             .ok_or(LegasiError::MathOverflow)?;
+        Ok(())
+    }
 
-        // Update protocol insurance fund
-        let protocol = &mut ctx.accounts.protocol;
-        protocol.insurance_fund = protocol
-            .insurance_fund
-            .checked_add(insurance_fee)
-            .ok_or(LegasiError::MathOverflow)?;
+    /// Record a repayment the caller has transferred into the vault: `principal`
+    /// leaves the borrowed total and `interest` is accrued to LPs and insurance
+    /// CPI only, signed by the lending or flash program's accounting PDA
+    pub fn record_repayment(
+        ctx: Context<RecordRepayment>,
+        source: AccountingSource,
+        principal: u64,
+        interest: u64,
+    ) -> Result<()> {
+        require_lender(source, &ctx.accounts.authority.key())?;
 
-        msg!(
-            "Accrued {} interest ({} to LPs, {} to insurance)",
-            interest_amount,
-            lp_interest,
-            insurance_fee
-        );
+        let pool = &mut ctx.accounts.lp_pool;
+        pool.total_borrowed = pool.total_borrowed.saturating_sub(principal);
+        let insurance_fee = credit_interest(pool, interest)?;
+        if insurance_fee > 0 {
+            accounting(
+                &ctx.accounts.core_program,
+                &ctx.accounts.accounting_authority,
+                ctx.bumps.accounting_authority,
+            )
+            .record_insurance_fee(ctx.accounts.protocol.to_account_info(), insurance_fee)?;
+        }
         Ok(())
     }
 
@@ -258,6 +366,79 @@ pub mod legasi_lp {
     }
 }
 
+/// Accounts a program signs pool CPIs with
+#[cfg(feature = "cpi")]
+pub struct PoolCpi<'info> {
+    pub lp_program: AccountInfo<'info>,
+    /// The calling program's `[ACCOUNTING_SEED]` PDA
+    pub authority: AccountInfo<'info>,
+    pub authority_bump: u8,
+    pub source: AccountingSource,
+}
+
+#[cfg(feature = "cpi")]
+impl<'info> PoolCpi<'info> {
+    pub fn lend(
+        &self,
+        lp_pool: AccountInfo<'info>,
+        vault: AccountInfo<'info>,
+        borrowable_mint: AccountInfo<'info>,
+        destination: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        let bump = [self.authority_bump];
+        let seeds: &[&[u8]] = &[ACCOUNTING_SEED, &bump];
+        cpi::lend(
+            CpiContext::new_with_signer(
+                self.lp_program.clone(),
+                cpi::accounts::Lend {
+                    authority: self.authority.clone(),
+                    lp_pool,
+                    vault,
+                    borrowable_mint,
+                    destination,
+                    token_program,
+                },
+                &[seeds],
+            ),
+            self.source,
+            amount,
+        )
+    }
+
+    /// `lp_accounting_authority` is the LP program's own accounting PDA,
+    /// which signs the insurance fee to core
+    pub fn record_repayment(
+        &self,
+        lp_pool: AccountInfo<'info>,
+        protocol: AccountInfo<'info>,
+        lp_accounting_authority: AccountInfo<'info>,
+        core_program: AccountInfo<'info>,
+        principal: u64,
+        interest: u64,
+    ) -> Result<()> {
+        let bump = [self.authority_bump];
+        let seeds: &[&[u8]] = &[ACCOUNTING_SEED, &bump];
+        cpi::record_repayment(
+            CpiContext::new_with_signer(
+                self.lp_program.clone(),
+                cpi::accounts::RecordRepayment {
+                    authority: self.authority.clone(),
+                    lp_pool,
+                    protocol,
+                    accounting_authority: lp_accounting_authority,
+                    core_program,
+                },
+                &[seeds],
+            ),
+            self.source,
+            principal,
+            interest,
+        )
+    }
+}
+
 // ========== ACCOUNTS ==========
 
 #[derive(Accounts)]
@@ -270,7 +451,15 @@ pub struct InitializePool<'info> {
         bump
     )]
     pub lp_pool: Account<'info, LpPool>,
-    pub borrowable_mint: Account<'info, Mint>,
+    pub borrowable_mint: InterfaceAccount<'info, Mint>,
+    /// Protocol state (owned by core program) - signer must be the risk admin
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID,
+        constraint = protocol.has_role(ProtocolRole::Risk, &admin.key()) @ LegasiError::Unauthorized
+    )]
+    pub protocol: Account<'info, Protocol>,
     #[account(mut)]
     pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
@@ -289,27 +478,39 @@ pub struct InitializePoolAccounts<'info> {
         payer = admin,
         mint::decimals = 6,
         mint::authority = lp_pool,
+        mint::token_program = token_program,
         seeds = [b"lp_token", lp_pool.borrowable_mint.as_ref()],
         bump
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
     #[account(
         init,
         payer = admin,
         token::mint = borrowable_mint,
         token::authority = lp_pool,
+        token::token_program = token_program,
         seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
         bump
     )]
-    pub vault: Account<'info, TokenAccount>,
-    /// The original borrowable mint (USDC, etc.)
-    pub borrowable_mint: Account<'info, Mint>,
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    /// The original borrowable mint (USDC, etc.) - the LP mint uses the same token program
+    #[account(mint::token_program = token_program)]
+    pub borrowable_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Protocol state (owned by core program) - signer must be the risk admin
+    #[account(
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID,
+        constraint = protocol.has_role(ProtocolRole::Risk, &admin.key()) @ LegasiError::Unauthorized
+    )]
+    pub protocol: Account<'info, Protocol>,
     #[account(mut)]
     pub admin: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct LpDeposit<'info> {
     #[account(
@@ -318,27 +519,43 @@ pub struct LpDeposit<'info> {
         bump = lp_pool.bump
     )]
     pub lp_pool: Account<'info, LpPool>,
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    #[account(
+        mut,
+        seeds = [b"borrowable", lp_pool.borrowable_mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
     #[account(
         mut,
         seeds = [b"lp_token", lp_pool.borrowable_mint.as_ref()],
         bump
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
         bump
     )]
-    pub vault: Account<'info, TokenAccount>,
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub borrowable_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub user_lp_token_account: Account<'info, TokenAccount>,
+    pub user_lp_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
     pub depositor: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct LpWithdraw<'info> {
     #[account(
@@ -347,39 +564,111 @@ pub struct LpWithdraw<'info> {
         bump = lp_pool.bump
     )]
     pub lp_pool: Account<'info, LpPool>,
+    #[account(seeds = [b"protocol"], bump = protocol.bump, seeds::program = legasi_core::ID)]
+    pub protocol: Box<Account<'info, Protocol>>,
+    #[account(
+        mut,
+        seeds = [b"borrowable", lp_pool.borrowable_mint.as_ref()],
+        bump = borrowable_config.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub borrowable_config: Box<Account<'info, Borrowable>>,
     #[account(
         mut,
         seeds = [b"lp_token", lp_pool.borrowable_mint.as_ref()],
         bump
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
         bump
     )]
-    pub vault: Account<'info, TokenAccount>,
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub borrowable_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub user_lp_token_account: Account<'info, TokenAccount>,
+    pub user_lp_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
     pub withdrawer: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
 #[derive(Accounts)]
 pub struct AccrueInterest<'info> {
+    /// Accounting PDA of the calling program
+    pub authority: Signer<'info>,
     #[account(
         mut,
         seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()],
         bump = lp_pool.bump
     )]
     pub lp_pool: Account<'info, LpPool>,
-    #[account(mut, seeds = [b"protocol"], bump = protocol.bump)]
-    pub protocol: Account<'info, Protocol>,
-    /// CHECK: Lending program authority
-    pub lending_authority: Signer<'info>,
+    /// Protocol state (owned by core program) - insurance fund
+    #[account(
+        mut,
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
+}
+
+#[derive(Accounts)]
+pub struct Lend<'info> {
+    /// Accounting PDA of the calling program
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()],
+        bump = lp_pool.bump
+    )]
+    pub lp_pool: Box<Account<'info, LpPool>>,
+    #[account(
+        mut,
+        seeds = [b"lp_vault", lp_pool.borrowable_mint.as_ref()],
+        bump
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = lp_pool.borrowable_mint)]
+    pub borrowable_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, token::mint = borrowable_mint)]
+    pub destination: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RecordRepayment<'info> {
+    /// Accounting PDA of the calling program
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()],
+        bump = lp_pool.bump
+    )]
+    pub lp_pool: Box<Account<'info, LpPool>>,
+    /// Protocol state (owned by core program) - insurance fund
+    #[account(
+        mut,
+        seeds = [b"protocol"],
+        bump = protocol.bump,
+        seeds::program = legasi_core::ID
+    )]
+    pub protocol: Box<Account<'info, Protocol>>,
+    /// CHECK: PDA signing accounting updates to core
+    #[account(seeds = [ACCOUNTING_SEED], bump)]
+    pub accounting_authority: UncheckedAccount<'info>,
+    pub core_program: Program<'info, LegasiCore>,
 }
 
 #[derive(Accounts)]
@@ -387,3 +676,79 @@ pub struct GetExchangeRate<'info> {
     #[account(seeds = [b"lp_pool", lp_pool.borrowable_mint.as_ref()], bump = lp_pool.bump)]
     pub lp_pool: Account<'info, LpPool>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::entrypoint::ProgramResult;
+    use anchor_lang::InstructionData;
+    use anchor_spl::token::spl_token;
+    use legasi_schema::testing::{self, TestAccount};
+
+    const LIQUIDITY: u64 = 1_000_000_000;
+    // Lend
+    const LP_POOL: usize = 1;
+    const VAULT: usize = 2;
+    const DESTINATION: usize = 4;
+
+    fn pda(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, program_id)
+    }
+
+    /// Accounts for `lend`, signed by `authority`
+    fn lend_accounts(authority: Pubkey) -> Vec<TestAccount> {
+        let mint = Pubkey::new_unique();
+        let (pool_key, bump) = pda(&[b"lp_pool", mint.as_ref()], &crate::ID);
+        let (vault, _) = pda(&[b"lp_vault", mint.as_ref()], &crate::ID);
+        let pool = LpPool {
+            borrowable_mint: mint,
+            lp_token_mint: Pubkey::new_unique(),
+            total_deposits: LIQUIDITY,
+            total_shares: LIQUIDITY,
+            total_borrowed: 0,
+            interest_earned: 0,
+            bump,
+        };
+        vec![
+            TestAccount::signer(authority),
+            TestAccount::anchor(pool_key, &pool),
+            TestAccount::token_account(vault, mint, pool_key, LIQUIDITY),
+            TestAccount::mint(mint, 6),
+            TestAccount::token_account(Pubkey::new_unique(), mint, Pubkey::new_unique(), 0),
+            TestAccount::program(spl_token::ID),
+        ]
+    }
+
+    fn lend(accounts: &mut [TestAccount], source: AccountingSource) -> ProgramResult {
+        let ix_data = instruction::Lend {
+            source,
+            amount: 100,
+        }
+        .data();
+        testing::run(crate::ID, crate::entry, &[], accounts, &ix_data)
+    }
+
+    #[test]
+    fn test_lend_transfers_from_vault() {
+        let mut accounts = lend_accounts(AccountingSource::Flash.authority());
+        lend(&mut accounts, AccountingSource::Flash).unwrap();
+
+        assert_eq!(accounts[DESTINATION].token_amount(), 100);
+        assert_eq!(accounts[VAULT].token_amount(), LIQUIDITY - 100);
+        assert_eq!(accounts[LP_POOL].read::<LpPool>().total_borrowed, 100);
+    }
+
+    #[test]
+    fn test_lend_requires_lender_authority() {
+        // Any signer other than the source's accounting PDA
+        let mut accounts = lend_accounts(Pubkey::new_unique());
+        let result = lend(&mut accounts, AccountingSource::Lending);
+        assert!(testing::failed_with(&result, LegasiError::Unauthorized));
+
+        // A program that doesn't borrow from pools
+        let mut accounts = lend_accounts(AccountingSource::Gad.authority());
+        let result = lend(&mut accounts, AccountingSource::Gad);
+        assert!(testing::failed_with(&result, LegasiError::Unauthorized));
+        assert_eq!(accounts[VAULT].token_amount(), LIQUIDITY);
+    }
+}
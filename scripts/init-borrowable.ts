@@ -53,16 +53,14 @@ async function main() {
       name: 'USDC',
       oracle: payer.publicKey, // Use payer as mock oracle for devnet
       interestRate: 850, // 8.5% APY in bps
-      decimals: 6,
-      assetType: { usdc: {} },
+      metadata: { decimals: 6, kind: { stablecoin: {} }, emodeCategory: { stablecoins: {} } },
     },
     { 
       mint: EURC_MINT, 
       name: 'EURC',
       oracle: payer.publicKey,
       interestRate: 720, // 7.2% APY
-      decimals: 6,
-      assetType: { eurc: {} },
+      metadata: { decimals: 6, kind: { stablecoin: {} }, emodeCategory: { stablecoins: {} } },
     },
   ];
 
@@ -88,8 +86,7 @@ async function main() {
         .registerBorrowable(
           b.oracle,
           b.interestRate,
-          b.decimals,
-          b.assetType
+          b.metadata
         )
         .accounts({
           protocol: protocolPda,
@@ -55,13 +55,11 @@ async function main() {
 
   const priceFeeds = [
     { 
-      asset: { sol: {} }, 
       name: 'SOL', 
       price: 100_000000, // $100 (6 decimals)
       mint: WRAPPED_SOL_MINT,
     },
     { 
-      asset: { cbBtc: {} }, 
       name: 'cbBTC', 
       price: 45000_000000, // $45,000
       mint: TEST_CBBTC_MINT, // Placeholder
@@ -87,7 +85,7 @@ async function main() {
     try {
       // @ts-ignore
       const tx = await coreProgram.methods
-        .initializePriceFeed(feed.asset, new anchor.BN(feed.price))
+        .initializePriceFeed(new anchor.BN(feed.price))
         .accounts({
           protocol: protocolPda,
           priceFeed: pricePda,
@@ -82,7 +82,7 @@ async function main() {
     try {
       // @ts-ignore
       const tx = await lendingProgram.methods
-        .initializePosition()
+        .initializePosition(0)
         .accounts({
           position: positionPda,
           owner: payer.publicKey,
@@ -13,7 +13,7 @@ import {
   createAssociatedTokenAccountInstruction,
 } from '@solana/spl-token';
 
-import { PROGRAM_IDS, SEEDS, DEFAULTS, DECIMALS } from './constants';
+import { PROGRAM_IDS, SEEDS, DEFAULTS, DECIMALS, POSITION_LAYOUT } from './constants';
 import {
   Position,
   HealthStatus,
@@ -73,8 +73,12 @@ export class LegasiClient {
 
   /**
    * Get the position PDA for the current wallet
+   * @param positionId Sub-account index (default: primary position)
    */
-  getPositionPda(): [PublicKey, number] {
+  getPositionPda(positionId = 0): [PublicKey, number] {
+    if (positionId !== 0) {
+      return findPositionPda(this.wallet.publicKey, PROGRAM_IDS.LENDING, positionId);
+    }
     if (this.positionPda && this.positionBump !== null) {
       return [this.positionPda, this.positionBump];
     }
@@ -90,6 +94,31 @@ export class LegasiClient {
     return [pda, bump];
   }
 
+  /**
+   * List the wallet's positions: the primary one and any sub-accounts
+   * (accounts not yet migrated to the current layout are not listed)
+   */
+  async listPositions(): Promise<{ positionId: number; address: PublicKey }[]> {
+    const accounts = await this.connection.getProgramAccounts(PROGRAM_IDS.LENDING, {
+      filters: [
+        { dataSize: POSITION_LAYOUT.SIZE },
+        {
+          memcmp: {
+            offset: POSITION_LAYOUT.OWNER_OFFSET,
+            bytes: this.wallet.publicKey.toBase58(),
+          },
+        },
+      ],
+    });
+
+    return accounts
+      .map(({ pubkey, account }) => ({
+        positionId: account.data[POSITION_LAYOUT.POSITION_ID_OFFSET],
+        address: pubkey,
+      }))
+      .sort((a, b) => a.positionId - b.positionId);
+  }
+
   /**
    * Check if position exists
    */
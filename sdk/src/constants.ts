@@ -47,6 +47,16 @@ export const SEEDS = {
   SOL_VAULT: 'sol_vault',
 } as const;
 
+/**
+ * Position account layout (zero-copy, offsets include the 8-byte discriminator)
+ */
+export const POSITION_LAYOUT = {
+  SIZE: 1192,
+  OWNER_OFFSET: 8,
+  POSITION_ID_OFFSET: 1125,
+  MARGIN_MODE_OFFSET: 1126,
+} as const;
+
 /**
  * Default values
  */
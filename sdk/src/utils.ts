@@ -135,13 +135,16 @@ export function fromBaseUnits(baseUnits: BN | number, decimals: number): number
 
 /**
  * Find PDA for position
+ * @param positionId Sub-account index (0 = primary position, no id seed)
  */
 export function findPositionPda(
   owner: PublicKey,
-  programId: PublicKey
+  programId: PublicKey,
+  positionId = 0
 ): [PublicKey, number] {
+  const idSeed = positionId === 0 ? Buffer.alloc(0) : Buffer.from([positionId]);
   return PublicKey.findProgramAddressSync(
-    [Buffer.from('position'), owner.toBuffer()],
+    [Buffer.from('position'), owner.toBuffer(), idSeed],
     programId
   );
 }
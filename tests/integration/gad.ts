@@ -43,7 +43,7 @@ describe("GAD (Gradual Auto-Deleveraging)", () => {
       // Initialize position
       try {
         await lendingProgram.methods
-          .initializePosition()
+          .initializePosition(0)
           .accounts({
             position: positionPda,
             owner: user.publicKey,